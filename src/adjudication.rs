@@ -0,0 +1,388 @@
+// Game adjudication for anything that plays out games. Endings by the rules of chess
+// are always checked, while resign, draw and tablebase adjudication are optional.
+
+use crate::syzygy::{Tablebase, Wdl};
+use crate::{gen_legal_moves, is_in_check, Color, Piece, Position, Variant};
+use hashbrown::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone, Default)]
+pub struct AdjudicationRules {
+    // a side loses once the score has been at least this far against it for resign_moves moves
+    pub resign_score: i32,
+    pub resign_moves: u32,
+    // the game is drawn once the score has stayed within draw_score of 0 for draw_moves moves,
+    // counting only from move draw_after
+    pub draw_score: i32,
+    pub draw_moves: u32,
+    pub draw_after: u16,
+    pub tablebase: Option<Arc<Tablebase>>,
+}
+
+pub struct Adjudication {
+    pub result: &'static str,
+    pub reason: String,
+    // value for the pgn termination tag
+    pub termination: &'static str,
+}
+
+pub struct Adjudicator {
+    rules: AdjudicationRules,
+    repetitions: HashMap<u64, u32>,
+    // consecutive plies with a decisive score, positive when white is winning
+    resign_streak: i32,
+    draw_streak: u32,
+}
+
+impl Adjudication {
+    fn new(result: &'static str, reason: &str, termination: &'static str) -> Self {
+        Self {
+            result,
+            reason: reason.to_string(),
+            termination,
+        }
+    }
+
+    fn win(color: Color, reason: &str, termination: &'static str) -> Self {
+        let result = if color == Color::White { "1-0" } else { "0-1" };
+        return Adjudication::new(result, reason, termination);
+    }
+
+    fn draw(reason: &str, termination: &'static str) -> Self {
+        return Adjudication::new("1/2-1/2", reason, termination);
+    }
+}
+
+impl Adjudicator {
+    pub fn new(rules: AdjudicationRules, start: &Position) -> Self {
+        let mut repetitions = HashMap::new();
+        repetitions.insert(start.gen_hash(), 1);
+
+        Self {
+            rules,
+            repetitions,
+            resign_streak: 0,
+            draw_streak: 0,
+        }
+    }
+
+    // records a played move, given the position after it and the score the mover
+    // reported from its own point of view
+    pub fn add_move(&mut self, position: &Position, score: Option<i32>) {
+        *self.repetitions.entry(position.gen_hash()).or_insert(0) += 1;
+
+        let Some(score) = score else {
+            self.resign_streak = 0;
+            self.draw_streak = 0;
+            return;
+        };
+
+        // the side to move now is the one that didn't report the score
+        let white_score = if position.move_next == Color::Black {
+            score
+        } else {
+            -score
+        };
+
+        self.resign_streak = if white_score >= self.rules.resign_score {
+            self.resign_streak.max(0) + 1
+        } else if white_score <= -self.rules.resign_score {
+            self.resign_streak.min(0) - 1
+        } else {
+            0
+        };
+
+        let move_number = position.fullmove_number;
+        if move_number >= self.rules.draw_after && white_score.abs() <= self.rules.draw_score {
+            self.draw_streak += 1;
+        } else {
+            self.draw_streak = 0;
+        }
+    }
+
+    // checks the current position, rules endings first
+    pub fn check(&self, position: &Position) -> Option<Adjudication> {
+        if let Some(adjudication) = self.check_rules(position) {
+            return Some(adjudication);
+        }
+
+        // both sides have to agree for the given number of moves each
+        let resign_plies = self.rules.resign_moves as i32 * 2;
+        if self.rules.resign_moves > 0 && self.resign_streak.abs() >= resign_plies {
+            let (winner, reason) = if self.resign_streak > 0 {
+                (Color::White, "Black resigns")
+            } else {
+                (Color::Black, "White resigns")
+            };
+            return Some(Adjudication::win(winner, reason, "adjudication"));
+        }
+
+        if self.rules.draw_moves > 0 && self.draw_streak >= self.rules.draw_moves * 2 {
+            return Some(Adjudication::draw("Draw by adjudication", "adjudication"));
+        }
+
+        if let Some(tablebase) = &self.rules.tablebase {
+            if tablebase.can_probe(position, usize::MAX) {
+                let to_move = position.move_next;
+
+                // cursed wins and blessed losses are draws under the fifty moves rule
+                return match tablebase.probe_wdl(position) {
+                    Some(Wdl::Win) => Some(Adjudication::win(
+                        to_move,
+                        "Win by tablebase",
+                        "adjudication",
+                    )),
+                    Some(Wdl::Loss) => Some(Adjudication::win(
+                        to_move.opposite(),
+                        "Win by tablebase",
+                        "adjudication",
+                    )),
+                    Some(_) => Some(Adjudication::draw("Draw by tablebase", "adjudication")),
+                    None => None,
+                };
+            }
+        }
+
+        return None;
+    }
+
+    fn check_rules(&self, position: &Position) -> Option<Adjudication> {
+        let rules = position.variant.rules();
+
+        if let Some(score) = rules.game_result(position) {
+            return Some(variant_result(score));
+        }
+
+        if gen_legal_moves(position).is_empty() {
+            if let Some(score) = rules.no_moves_result(position) {
+                return Some(variant_result(score));
+            }
+
+            if !is_in_check(position) {
+                return Some(Adjudication::draw("Draw by stalemate", "normal"));
+            }
+
+            let winner = position.move_next.opposite();
+            let reason = if winner == Color::White {
+                "White mates"
+            } else {
+                "Black mates"
+            };
+            return Some(Adjudication::win(winner, reason, "normal"));
+        }
+
+        if position.halfmove_clock >= 100 {
+            return Some(Adjudication::draw("Draw by fifty moves rule", "normal"));
+        }
+
+        let repetitions = self.repetitions.get(&position.gen_hash()).copied();
+        if repetitions.unwrap_or(0) >= 3 {
+            return Some(Adjudication::draw("Draw by 3-fold repetition", "normal"));
+        }
+
+        // in the variants a lone king can still win, by a rule or by drops
+        if position.variant == Variant::Standard && is_insufficient_material(position) {
+            return Some(Adjudication::draw(
+                "Draw by insufficient mating material",
+                "normal",
+            ));
+        }
+
+        return None;
+    }
+}
+
+// a white relative score from the variant rules
+fn variant_result(score: i32) -> Adjudication {
+    return match score.signum() {
+        1 => Adjudication::win(Color::White, "White wins", "normal"),
+        -1 => Adjudication::win(Color::Black, "Black wins", "normal"),
+        _ => Adjudication::draw("Draw", "normal"),
+    };
+}
+
+pub fn is_insufficient_material(position: &Position) -> bool {
+    let mut knights = 0;
+    // square colors of the bishops on the board
+    let mut bishop_squares = [false; 2];
+
+    for (index, piece) in position.board.iter().enumerate() {
+        match piece {
+            None | Some(Piece::King(_)) => {}
+            Some(Piece::Knight(_)) => knights += 1,
+            Some(Piece::Bishop(_)) => bishop_squares[(index / 8 + index % 8) % 2] = true,
+            _ => return false,
+        }
+    }
+
+    let bishops_on_both_colors = bishop_squares[0] && bishop_squares[1];
+
+    // a lone minor piece, or bishops that all share one square color
+    return match knights {
+        0 => !bishops_on_both_colors,
+        1 => !bishop_squares[0] && !bishop_squares[1],
+        _ => false,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{execute_halfmove, parse_legal_move};
+
+    fn check(fen: &str) -> Option<Adjudication> {
+        let position = Position::from_fen(fen).unwrap();
+        let adjudicator = Adjudicator::new(AdjudicationRules::default(), &position);
+
+        return adjudicator.check(&position);
+    }
+
+    fn result(fen: &str) -> Option<&'static str> {
+        return check(fen).map(|adjudication| adjudication.result);
+    }
+
+    // plays the moves, each reported with the given score
+    fn play(adjudicator: &mut Adjudicator, position: &mut Position, moves: &[&str], score: i32) {
+        for uci in moves {
+            let halfmove = parse_legal_move(position, uci, false).unwrap();
+            execute_halfmove(position, halfmove);
+            adjudicator.add_move(position, Some(score));
+        }
+    }
+
+    #[test]
+    fn ends_by_the_rules() {
+        let mate = check("k1R5/8/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(mate.result, "1-0");
+        assert_eq!(mate.reason, "White mates");
+        assert_eq!(mate.termination, "normal");
+
+        assert_eq!(result("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some("1/2-1/2"));
+        assert_eq!(result("k7/8/1K6/8/8/8/8/7R b - - 100 80"), Some("1/2-1/2"));
+        assert_eq!(result("k7/8/1K6/8/8/8/8/7R b - - 99 80"), None);
+        assert_eq!(
+            result("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            None
+        );
+    }
+
+    #[test]
+    fn ends_by_the_variant_rules() {
+        let position =
+            Position::from_variant_fen("8/8/8/3K4/8/8/8/k7 b - - 0 1", Variant::KingOfTheHill)
+                .unwrap();
+        let adjudicator = Adjudicator::new(AdjudicationRules::default(), &position);
+        assert_eq!(adjudicator.check(&position).unwrap().result, "1-0");
+
+        // a lone king is no draw in the variants
+        let position =
+            Position::from_variant_fen("8/8/8/8/8/8/8/k1K5 w - - 0 1", Variant::KingOfTheHill)
+                .unwrap();
+        let adjudicator = Adjudicator::new(AdjudicationRules::default(), &position);
+        assert!(adjudicator.check(&position).is_none());
+    }
+
+    #[test]
+    fn finds_insufficient_material() {
+        let insufficient = |fen: &str| is_insufficient_material(&Position::from_fen(fen).unwrap());
+
+        assert!(insufficient("k7/8/8/8/8/8/8/K7 w - - 0 1"));
+        assert!(insufficient("k7/8/8/8/8/8/8/KN6 w - - 0 1"));
+        assert!(insufficient("k7/8/8/8/8/8/8/KB6 w - - 0 1"));
+        // bishops on squares of one colour
+        assert!(insufficient("kb6/8/8/8/8/8/8/K1B5 w - - 0 1"));
+
+        assert!(!insufficient("k1b5/8/8/8/8/8/8/K1B5 w - - 0 1"));
+        assert!(!insufficient("k7/8/8/8/8/8/8/KNN5 w - - 0 1"));
+        assert!(!insufficient("k7/8/8/8/8/8/8/KBN5 w - - 0 1"));
+        assert!(!insufficient("k7/8/8/8/8/8/P7/K7 w - - 0 1"));
+
+        assert_eq!(result("k7/8/8/8/8/8/8/KN6 w - - 0 1"), Some("1/2-1/2"));
+    }
+
+    #[test]
+    fn draws_by_repetition() {
+        let mut position = Position::startpos();
+        let mut adjudicator = Adjudicator::new(AdjudicationRules::default(), &position);
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        play(&mut adjudicator, &mut position, &shuffle, 0);
+        assert!(adjudicator.check(&position).is_none());
+
+        play(&mut adjudicator, &mut position, &shuffle, 0);
+        let draw = adjudicator.check(&position).unwrap();
+        assert_eq!(draw.reason, "Draw by 3-fold repetition");
+    }
+
+    #[test]
+    fn resigns_when_both_sides_agree() {
+        let rules = AdjudicationRules {
+            resign_score: 500,
+            resign_moves: 2,
+            ..Default::default()
+        };
+        let mut position = Position::startpos();
+        let mut adjudicator = Adjudicator::new(rules, &position);
+
+        // white is winning by its own account and by black's
+        play(&mut adjudicator, &mut position, &["e2e4"], 600);
+        play(&mut adjudicator, &mut position, &["e7e5"], -600);
+        play(&mut adjudicator, &mut position, &["g1f3"], 600);
+        assert!(adjudicator.check(&position).is_none());
+
+        play(&mut adjudicator, &mut position, &["b8c6"], -600);
+        let resignation = adjudicator.check(&position).unwrap();
+        assert_eq!(resignation.result, "1-0");
+        assert_eq!(resignation.reason, "Black resigns");
+        assert_eq!(resignation.termination, "adjudication");
+
+        // a score without a mover's opinion starts over
+        adjudicator.add_move(&position, None);
+        assert!(adjudicator.check(&position).is_none());
+    }
+
+    #[test]
+    fn draws_quiet_games_after_the_given_move() {
+        let rules = AdjudicationRules {
+            draw_score: 10,
+            draw_moves: 1,
+            draw_after: 2,
+            ..Default::default()
+        };
+        let mut position = Position::startpos();
+        let mut adjudicator = Adjudicator::new(rules, &position);
+
+        // the first move doesn't count, black's reply is already in move 2
+        play(&mut adjudicator, &mut position, &["e2e4", "e7e5"], 5);
+        assert!(adjudicator.check(&position).is_none());
+
+        play(&mut adjudicator, &mut position, &["g1f3"], -5);
+        assert_eq!(adjudicator.check(&position).unwrap().result, "1/2-1/2");
+
+        play(&mut adjudicator, &mut position, &["b8c6"], 50);
+        assert!(adjudicator.check(&position).is_none());
+    }
+
+    #[test]
+    fn adjudicates_by_tablebase() {
+        let rules = AdjudicationRules {
+            tablebase: Some(Arc::new(Tablebase::open(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/syzygy"
+            )))),
+            ..Default::default()
+        };
+        let adjudicate = |fen: &str| {
+            let position = Position::from_fen(fen).unwrap();
+            let adjudicator = Adjudicator::new(rules.clone(), &position);
+            adjudicator
+                .check(&position)
+                .map(|adjudication| adjudication.result)
+        };
+
+        assert_eq!(adjudicate("8/8/8/4k3/8/8/8/R3K3 b - - 0 1"), Some("1-0"));
+        assert_eq!(adjudicate("8/8/8/4k3/8/8/8/r3K3 w - - 0 1"), Some("0-1"));
+        assert_eq!(adjudicate("k7/8/8/8/P7/8/8/4K3 w - - 0 1"), Some("1/2-1/2"));
+        assert_eq!(adjudicate("8/8/8/4k3/8/8/8/RR2K3 w - - 0 1"), None);
+    }
+}
@@ -0,0 +1,133 @@
+// Antichess, or losing chess. Captures are forced, the king is an ordinary piece that
+// can be captured and promoted to, there is no check and no castling, and a side wins
+// by losing all of its pieces or by having no moves left.
+
+use crate::rules::{is_capture, win_for, Rules};
+use crate::{gen_pseudolegal_moves, Color, HalfMove, HalfmoveFlag, Piece, Position};
+
+pub struct Antichess;
+
+impl Rules for Antichess {
+    fn gen_possible(&self, position: &Position) -> Vec<HalfMove> {
+        let mut moves = gen_pseudolegal_moves(position);
+        moves.retain(|halfmove| halfmove.flag != Some(HalfmoveFlag::Castle));
+
+        let king_promotions: Vec<HalfMove> = moves
+            .iter()
+            .filter(|halfmove| halfmove.flag == Some(HalfmoveFlag::QueenPromotion))
+            .map(|&halfmove| HalfMove {
+                flag: Some(HalfmoveFlag::KingPromotion),
+                ..halfmove
+            })
+            .collect();
+        moves.extend(king_promotions);
+
+        if moves.iter().any(|&halfmove| is_capture(position, halfmove)) {
+            moves.retain(|&halfmove| is_capture(position, halfmove));
+        }
+
+        return moves;
+    }
+
+    // nothing is ever left in check
+    fn is_legal(&self, _position: &Position, _new_pos: &Position, _halfmove: HalfMove) -> bool {
+        return true;
+    }
+
+    fn game_result(&self, position: &Position) -> Option<i32> {
+        let pieces = if position.move_next == Color::White {
+            &position.piece_set.white
+        } else {
+            &position.piece_set.black
+        };
+
+        if pieces.is_empty() {
+            return Some(win_for(position.move_next));
+        }
+
+        return None;
+    }
+
+    fn no_moves_result(&self, position: &Position) -> Option<i32> {
+        return Some(win_for(position.move_next));
+    }
+
+    // every piece is a burden, the king no more than a minor piece. no placement, since
+    // in this game pieces are worth what they can be made to capture
+    fn piece_value(&self, piece: Piece, _index: u8) -> i32 {
+        return -match piece {
+            Piece::Pawn(_) => 100,
+            Piece::Knight(_) => 220,
+            Piece::Bishop(_) => 200,
+            Piece::Rook(_) => 300,
+            Piece::Queen(_) => 400,
+            Piece::King(_) => 250,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::VARIANT_WIN;
+    use crate::{gen_legal_moves, Variant};
+
+    fn antichess(fen: &str) -> Position {
+        return Position::from_variant_fen(fen, Variant::Antichess).unwrap();
+    }
+
+    fn moves(fen: &str) -> Vec<String> {
+        let mut moves: Vec<String> = gen_legal_moves(&antichess(fen))
+            .iter()
+            .map(|halfmove| halfmove.move_to_uci(false))
+            .collect();
+        moves.sort();
+
+        return moves;
+    }
+
+    #[test]
+    fn forces_captures() {
+        assert_eq!(moves("8/8/8/3p4/4P3/8/8/1N6 w - - 0 1"), ["e4d5"]);
+        assert_eq!(moves("8/8/8/3p4/4P3/8/8/1N6 b - - 0 1"), ["d5e4"]);
+    }
+
+    #[test]
+    fn promotes_to_a_king() {
+        assert_eq!(
+            moves("8/P7/8/8/8/8/8/k7 w - - 0 1"),
+            ["a7a8b", "a7a8k", "a7a8n", "a7a8q", "a7a8r"]
+        );
+    }
+
+    #[test]
+    fn has_no_check_or_castling() {
+        // the king may walk into an attack
+        assert_eq!(
+            moves("k7/8/8/8/8/8/8/K6r w - - 0 1"),
+            ["a1a2", "a1b1", "a1b2"]
+        );
+
+        let moves = moves("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(!moves.contains(&"e1g1".to_string()));
+        assert!(!moves.contains(&"e1c1".to_string()));
+    }
+
+    #[test]
+    fn wins_by_losing_everything() {
+        let position = antichess("8/8/8/8/8/8/8/k7 w - - 0 1");
+        assert_eq!(Antichess.game_result(&position), Some(VARIANT_WIN));
+
+        let position = antichess("8/8/8/8/8/8/8/K7 b - - 0 1");
+        assert_eq!(Antichess.game_result(&position), Some(-VARIANT_WIN));
+    }
+
+    #[test]
+    fn wins_without_moves() {
+        let position = antichess("8/8/8/8/8/p7/P7/8 w - - 0 1");
+
+        assert!(gen_legal_moves(&position).is_empty());
+        assert_eq!(Antichess.game_result(&position), None);
+        assert_eq!(Antichess.no_moves_result(&position), Some(VARIANT_WIN));
+    }
+}
@@ -0,0 +1,182 @@
+// Atomic chess. Every capture is an explosion that removes the capturing piece along
+// with every piece but the pawns around the square it happened on. Kings can't capture,
+// and the side whose king explodes loses, so kings standing next to each other can't
+// give or be in check.
+
+use crate::rules::{is_capture, king_square, win_for, Rules};
+use crate::{adjacent_squares, gen_pseudolegal_moves, is_piece_attacked, make_halfmove};
+use crate::{Color, HalfMove, HalfmoveFlag, Piece, Position};
+
+// for each of its own pieces next to the king, a capture there blows up the king as well
+const KING_NEIGHBOUR_PENALTY: i32 = 15;
+
+pub struct Atomic;
+
+impl Rules for Atomic {
+    fn gen_possible(&self, position: &Position) -> Vec<HalfMove> {
+        let mut moves = gen_pseudolegal_moves(position);
+
+        moves.retain(|halfmove| {
+            !is_capture(position, *halfmove)
+                || !matches!(position.board[halfmove.from as usize], Some(Piece::King(_)))
+        });
+
+        return moves;
+    }
+
+    fn execute_halfmove(&self, position: &mut Position, halfmove: HalfMove) {
+        let is_capture = is_capture(position, halfmove);
+
+        make_halfmove(position, halfmove);
+
+        if is_capture {
+            explode(position, halfmove.to);
+        }
+    }
+
+    fn is_legal(&self, position: &Position, new_pos: &Position, halfmove: HalfMove) -> bool {
+        let color = position.move_next;
+
+        // blowing up the other king wins even out of check, unless the own one goes with it
+        if !has_king(new_pos, color) {
+            return false;
+        }
+        if !has_king(new_pos, color.opposite()) {
+            return true;
+        }
+
+        if halfmove.flag == Some(HalfmoveFlag::Castle) && is_in_check(position, color) {
+            return false;
+        }
+
+        return !is_in_check(new_pos, color);
+    }
+
+    fn game_result(&self, position: &Position) -> Option<i32> {
+        // the side that moved blowing up its own king loses, even if the other one is gone too
+        let mover = position.move_next.opposite();
+
+        if !has_king(position, mover) {
+            return Some(win_for(position.move_next));
+        }
+        if !has_king(position, position.move_next) {
+            return Some(win_for(mover));
+        }
+
+        return None;
+    }
+
+    fn eval(&self, position: &Position) -> i32 {
+        let mut eval = 0;
+
+        for color in [Color::White, Color::Black] {
+            let exposed = adjacent_squares(king_square(position, color))
+                .filter(|&square| {
+                    position.board[square as usize].is_some_and(|piece| piece.get_color() == color)
+                })
+                .count() as i32;
+
+            if color == Color::White {
+                eval -= exposed * KING_NEIGHBOUR_PENALTY;
+            } else {
+                eval += exposed * KING_NEIGHBOUR_PENALTY;
+            }
+        }
+
+        return eval;
+    }
+}
+
+// removes the capturing piece on center and everything but pawns around it
+fn explode(position: &mut Position, center: u8) {
+    for square in std::iter::once(center).chain(adjacent_squares(center)) {
+        let Some(piece) = position.board[square as usize] else {
+            continue;
+        };
+
+        if square != center && matches!(piece, Piece::Pawn(_)) {
+            continue;
+        }
+
+        position.board[square as usize] = None;
+        position.piece_set.remove_index(square, piece.get_color());
+        position.castling_rights.remove_rook_square(square);
+    }
+}
+
+// an exploded king leaves its square behind empty
+fn has_king(position: &Position, color: Color) -> bool {
+    return position.board[king_square(position, color) as usize] == Some(Piece::King(color));
+}
+
+// kings next to each other can't be checked, since taking one would blow up both
+fn is_in_check(position: &Position, color: Color) -> bool {
+    let king = king_square(position, color);
+
+    if adjacent_squares(king).any(|square| square == king_square(position, color.opposite())) {
+        return false;
+    }
+
+    return is_piece_attacked(king, color, position);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::VARIANT_WIN;
+    use crate::{coord_to_int, execute_halfmove, gen_legal_moves, parse_legal_move, Variant};
+
+    fn atomic(fen: &str) -> Position {
+        return Position::from_variant_fen(fen, Variant::Atomic).unwrap();
+    }
+
+    fn is_legal(position: &Position, uci: &str) -> bool {
+        return parse_legal_move(position, uci, false).is_some();
+    }
+
+    #[test]
+    fn explodes_all_but_pawns() {
+        let mut position = atomic("4k3/8/2p5/2bpn3/3Q4/8/8/4K3 w - - 0 1");
+        let halfmove = parse_legal_move(&position, "d4d5", false).unwrap();
+        execute_halfmove(&mut position, halfmove);
+
+        // the queen and the pieces around it are gone, the pawn next to it stays
+        for square in ["d4", "d5", "c5", "e5"] {
+            assert_eq!(position.board[coord_to_int(square).unwrap() as usize], None);
+        }
+        assert_eq!(position.board[42], Some(Piece::Pawn(Color::Black)));
+        assert_eq!(position.board.iter().flatten().count(), 3);
+    }
+
+    #[test]
+    fn keeps_kings_from_capturing() {
+        let position = atomic("4k3/8/8/8/8/8/4p3/4K3 w - - 0 1");
+        assert!(!is_legal(&position, "e1e2"));
+
+        // nor may a capture blow up the own king
+        let position = atomic("4k3/8/8/8/8/8/R2n4/4K3 w - - 0 1");
+        assert!(!is_legal(&position, "a2d2"));
+    }
+
+    #[test]
+    fn wins_by_exploding_the_king() {
+        // even out of check
+        let mut position = atomic("rk6/8/8/8/8/8/8/R3K2r w - - 0 1");
+        assert!(is_legal(&position, "a1a8"));
+
+        let halfmove = parse_legal_move(&position, "a1a8", false).unwrap();
+        execute_halfmove(&mut position, halfmove);
+
+        assert_eq!(Atomic.game_result(&position), Some(VARIANT_WIN));
+        assert!(gen_legal_moves(&position).is_empty());
+    }
+
+    #[test]
+    fn ignores_checks_between_kings() {
+        let position = atomic("8/8/8/8/8/3kK2r/8/8 w - - 0 1");
+
+        assert!(!is_in_check(&position, Color::White));
+        assert!(is_legal(&position, "e3e4"));
+        assert!(!is_legal(&position, "e3f3"));
+    }
+}
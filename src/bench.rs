@@ -0,0 +1,150 @@
+// Fixed depth search over a built-in set of positions. The total node count is a
+// signature of the search, so any change to it means search behaviour changed.
+//
+// usage: bench [depth] [threads] [hash]
+
+use crate::info::UciInfo;
+use crate::tt::TranspositionTable;
+use crate::{Engine, Position, SearchLimits};
+use hashbrown::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+const DEFAULT_DEPTH: usize = 2;
+
+const BENCH_FENS: [&str; 50] = [
+    // openings
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+    "rnbqkb1r/pppppppp/5n2/8/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 1 2",
+    "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+    "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 2",
+    "rnbqkbnr/pppppppp/8/8/2P5/8/PP1PPPPP/RNBQKBNR b KQkq - 0 1",
+    "rnbqkb1r/pp2pppp/3p1n2/8/3NP3/8/PPP2PPP/RNBQKB1R w KQkq - 1 5",
+    "rnbqk2r/ppppbppp/4pn2/8/2PP4/5N2/PP2PPPP/RNBQKB1R w KQkq - 2 4",
+    "rnbqkbnr/pppp1ppp/4p3/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq - 0 2",
+    "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 4",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    // middlegames
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 11",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "r1bbk1nr/pp3p1p/2n5/1N4p1/2Np1B2/8/PPP2PPP/2KR1B1R w kq - 0 13",
+    "4r1k1/r1q2ppp/ppp2n2/4P3/5Rb1/1N1BQ3/PPP3PP/R5K1 w - - 1 17",
+    "r1bq1r1k/b1p1npp1/p2p3p/1p6/3PP3/1B2NN2/PP3PPP/R2Q1RK1 w - - 1 16",
+    "r1q2rk1/2p1bppp/2Pp4/p6b/Q1PNp3/4B3/PP1R1PPP/2K4R w - - 2 18",
+    "4k2r/1pb2ppp/1p2p3/1R1p4/3P4/2r1PN2/P4PPP/1R4K1 b - - 3 22",
+    "3q2k1/pb3p1p/4pbp1/2r5/PpN2N2/1P2P2P/5PP1/Q2R2K1 b - - 4 26",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "3b4/5kp1/1p1p1p1p/pP1PpP1P/P1P1P3/3KN3/8/8 w - - 0 1",
+    "2K5/p7/7P/5pR1/8/5k2/r7/8 w - - 0 1",
+    "8/6pk/1p6/8/PP3p1p/5P2/4KP1q/3Q4 w - - 0 1",
+    "7k/3p2pp/4q3/8/4Q3/5Kp1/P6b/8 w - - 0 1",
+    "8/2p5/8/2kPKp1p/2p4P/2P5/3P4/8 w - - 0 1",
+    "8/1p3pp1/7p/5P1P/2k3P1/8/2K2P2/8 w - - 0 1",
+    "8/pp2r1k1/2p1p3/3pP2p/1P1P1P1P/P5KR/8/8 w - - 0 1",
+    "8/3p4/p1bk3p/Pp6/1Kp1PpPp/2P2P1P/2P5/5B2 b - - 0 1",
+    "5k2/7R/4P2p/5K2/p1r2P1p/8/8/8 b - - 0 1",
+    "6k1/6p1/P6p/r1N5/5p2/7P/1b3PP1/4R1K1 w - - 0 1",
+    "1r3k2/4q3/2Pp3b/3Bp3/2Q2p2/1p1P2P1/1P2KP2/3N4 w - - 0 1",
+    "6k1/4pp1p/3p2p1/P1pPb3/R7/1r2P1PP/3B1P2/6K1 w - - 0 1",
+    "8/3p3B/5p2/5P2/p7/PP5b/k7/6K1 w - - 0 1",
+    "5rk1/q6p/2p3bR/1pPp1rP1/1P1Pp3/P3B1Q1/1K3P2/R7 w - - 93 90",
+    // endgames
+    "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1",
+    "8/5k2/8/8/8/8/1R6/4K3 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/8/8/8/8/2k5/2p5/2K5 b - - 0 1",
+    "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1",
+    "r3k3/8/8/8/8/8/8/4K3 b q - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+    "8/8/8/5N2/8/p7/8/2NK3k w - - 0 1",
+    "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1",
+    "8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1",
+    "8/8/1P6/5pr1/8/4R3/7k/2K5 w - - 0 1",
+    "8/2p4P/8/kr6/6R1/8/8/1K6 w - - 0 1",
+    "8/8/3P3k/8/1p6/8/1P6/1K3n2 b - - 0 1",
+    "8/R7/2q5/8/6k1/8/1P5p/K6R w - - 0 124",
+    "r2r1n2/pp2bk2/2p1p2p/3q4/3PN1QP/2P3R1/P4PP1/5RK1 w - - 0 1",
+];
+
+pub fn bench_command(args: &[&str]) {
+    let mut values = [DEFAULT_DEPTH, 1, 16];
+
+    for (i, arg) in args.iter().take(values.len()).enumerate() {
+        match arg.parse::<usize>() {
+            Ok(value) => values[i] = value,
+            Err(_) => {
+                println!("Error - bench arguments must be numbers: bench [depth] [threads] [hash]");
+                return;
+            }
+        }
+    }
+
+    let [depth, threads, hash] = values;
+
+    if threads > 1 {
+        println!("info string node counts are only reproducible with one thread");
+    }
+
+    let start_time = Instant::now();
+    let total_nodes = bench_nodes(&BENCH_FENS, depth, threads, hash);
+
+    let elapsed = start_time.elapsed().as_millis();
+
+    println!("\n===========================");
+    println!("Total time (ms) : {}", elapsed);
+    println!("Nodes searched  : {}", total_nodes);
+    println!(
+        "Nodes/second    : {}",
+        total_nodes as u128 * 1000 / elapsed.max(1)
+    );
+}
+
+// the nodes of a fixed depth search of every position, each from a fresh engine state
+// so the result doesn't depend on anything searched before
+fn bench_nodes(fens: &[&str], depth: usize, threads: usize, hash: usize) -> usize {
+    let mut engine = Engine::new();
+    engine.options.threads = threads.max(1);
+    engine.options.hash_size = hash.max(1);
+    engine.tt = Arc::new(TranspositionTable::new(hash.max(1)));
+
+    let mut total_nodes = 0;
+
+    for (i, fen) in fens.iter().enumerate() {
+        println!("\nPosition: {}/{} ({})", i + 1, fens.len(), fen);
+
+        engine.tt.clear();
+        engine.repetition_map = HashMap::new();
+
+        let position = Position::from_fen(fen).unwrap();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        };
+        let sink = UciInfo::new(&position, &engine.options);
+        let result = engine.searcher().search(position, &limits, &sink);
+
+        total_nodes += result.nodes;
+    }
+
+    return total_nodes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gives_the_same_signature_every_time() {
+        // the first openings, which stay quick in a debug build. the last one already
+        // searches millions of captures
+        let openings = &BENCH_FENS[..9];
+
+        let first = bench_nodes(openings, DEFAULT_DEPTH, 1, 1);
+        let second = bench_nodes(openings, DEFAULT_DEPTH, 1, 1);
+
+        assert!(first > 0);
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,297 @@
+// Crazyhouse rules on top of the normal position. Captured pieces go to the capturer's
+// pocket and can be dropped back on any empty square instead of making a move. Pieces
+// that were promoted are tracked so they go back to the pocket as pawns.
+
+use crate::rules::{king_square, Rules};
+use crate::{adjacent_squares, gen_pseudolegal_moves, make_halfmove, piece_to_char};
+use crate::{Color, HalfMove, HalfmoveFlag, Piece, Position};
+
+// pocket order, shared by the flags and the pockets themselves
+const POCKET_PIECES: [fn(Color) -> Piece; 5] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+];
+
+const DROP_FLAGS: [HalfmoveFlag; 5] = [
+    HalfmoveFlag::PawnDrop,
+    HalfmoveFlag::KnightDrop,
+    HalfmoveFlag::BishopDrop,
+    HalfmoveFlag::RookDrop,
+    HalfmoveFlag::QueenDrop,
+];
+
+// per empty square around the king, for each piece the opponent can drop there
+const KING_DROP_DANGER: i32 = 8;
+
+pub struct Crazyhouse;
+
+impl Rules for Crazyhouse {
+    fn gen_possible(&self, position: &Position) -> Vec<HalfMove> {
+        let mut moves = gen_pseudolegal_moves(position);
+        moves.extend(gen_drops(position));
+
+        return moves;
+    }
+
+    fn execute_halfmove(&self, position: &mut Position, halfmove: HalfMove) {
+        if halfmove.flag != Some(HalfmoveFlag::Castle) {
+            update_pockets(position, halfmove);
+        }
+
+        make_halfmove(position, halfmove);
+    }
+
+    fn eval(&self, position: &Position) -> i32 {
+        return pocket_eval(position);
+    }
+}
+
+fn pocket_side(color: Color) -> usize {
+    return match color {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+}
+
+fn pocket_kind(piece: Piece) -> Option<usize> {
+    return match piece {
+        Piece::Pawn(_) => Some(0),
+        Piece::Knight(_) => Some(1),
+        Piece::Bishop(_) => Some(2),
+        Piece::Rook(_) => Some(3),
+        Piece::Queen(_) => Some(4),
+        Piece::King(_) => None,
+    };
+}
+
+// called before the move is made on the board
+fn update_pockets(position: &mut Position, halfmove: HalfMove) {
+    let side = pocket_side(position.move_next);
+
+    if let Some(kind) = DROP_FLAGS.iter().position(|&f| Some(f) == halfmove.flag) {
+        position.pockets[side][kind] -= 1;
+        return;
+    }
+
+    let captured_square = match halfmove.flag {
+        Some(HalfmoveFlag::EnPassant) if position.move_next == Color::White => halfmove.to - 8,
+        Some(HalfmoveFlag::EnPassant) => halfmove.to + 8,
+        _ => halfmove.to,
+    };
+
+    if let Some(captured) = position.board[captured_square as usize] {
+        let kind = if position.promoted & (1 << captured_square) != 0 {
+            Some(0)
+        } else {
+            pocket_kind(captured)
+        };
+
+        if let Some(kind) = kind {
+            position.pockets[side][kind] += 1;
+        }
+    }
+
+    let was_promoted = position.promoted & (1 << halfmove.from) != 0;
+    position.promoted &= !(1 << halfmove.from | 1 << captured_square);
+
+    if was_promoted || halfmove.promotion_char().is_some() {
+        position.promoted |= 1 << halfmove.to;
+    }
+}
+
+fn gen_drops(position: &Position) -> Vec<HalfMove> {
+    let mut moves = vec![];
+    let pocket = &position.pockets[pocket_side(position.move_next)];
+
+    for (kind, &count) in pocket.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+
+        for square in 0..64 {
+            // no pawns on the first or last rank
+            if position.board[square as usize].is_some()
+                || (kind == 0 && !(8..56).contains(&square))
+            {
+                continue;
+            }
+
+            moves.push(HalfMove {
+                from: square,
+                to: square,
+                flag: Some(DROP_FLAGS[kind]),
+                is_capture: false,
+            });
+        }
+    }
+
+    return moves;
+}
+
+// material in hand, and king danger from what the opponent can drop next to the king.
+// white relative like the rest of the eval
+fn pocket_eval(position: &Position) -> i32 {
+    let mut eval = 0;
+
+    for color in [Color::White, Color::Black] {
+        let pocket = &position.pockets[pocket_side(color)];
+        let opponent_pocket = &position.pockets[pocket_side(color.opposite())];
+
+        let material: i32 = pocket
+            .iter()
+            .enumerate()
+            .map(|(kind, &count)| POCKET_PIECES[kind](color).get_cp_val() as i32 * count as i32)
+            .sum();
+
+        let open_squares = adjacent_squares(king_square(position, color))
+            .filter(|&square| position.board[square as usize].is_none())
+            .count() as i32;
+        let droppable: i32 = opponent_pocket.iter().map(|&count| count as i32).sum();
+
+        let score = material - open_squares * droppable * KING_DROP_DANGER;
+
+        if color == Color::White {
+            eval += score;
+        } else {
+            eval -= score;
+        }
+    }
+
+    return eval;
+}
+
+// the part of a fen between the brackets, e.g. QRbp
+pub fn parse_pocket(pocket: &str) -> Option<[[u8; 5]; 2]> {
+    let mut pockets = [[0; 5]; 2];
+
+    for char in pocket.chars() {
+        if char == '-' {
+            continue;
+        }
+
+        let color = if char.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let kind = "pnbrq".find(char.to_ascii_lowercase())?;
+
+        pockets[pocket_side(color)][kind] += 1;
+    }
+
+    return Some(pockets);
+}
+
+pub fn pocket_string(position: &Position) -> String {
+    let mut pocket = String::from("[");
+
+    // strongest pieces first, white before black
+    for color in [Color::White, Color::Black] {
+        for kind in (0..5).rev() {
+            let letter = piece_to_char(Some(POCKET_PIECES[kind](color)), false);
+
+            for _ in 0..position.pockets[pocket_side(color)][kind] {
+                pocket.push(letter);
+            }
+        }
+    }
+
+    pocket.push(']');
+    return pocket;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{execute_halfmove, gen_legal_moves, parse_legal_move, Variant};
+
+    fn crazyhouse(fen: &str) -> Position {
+        return Position::from_variant_fen(fen, Variant::Crazyhouse).unwrap();
+    }
+
+    fn play(position: &mut Position, moves: &[&str]) {
+        for uci in moves {
+            let halfmove = parse_legal_move(position, uci, false).unwrap();
+            execute_halfmove(position, halfmove);
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_pockets() {
+        let pockets = parse_pocket("pQbR").unwrap();
+        assert_eq!(pockets, [[0, 0, 0, 1, 1], [1, 0, 1, 0, 0]]);
+        assert_eq!(parse_pocket("-"), Some([[0; 5]; 2]));
+        assert_eq!(parse_pocket("Qk"), None);
+
+        let position = crazyhouse("2k5/8/8/8/8/8/8/4K3[pQbRP] w - - 0 1");
+        assert_eq!(pocket_string(&position), "[QRPbp]");
+    }
+
+    #[test]
+    fn pockets_captured_pieces() {
+        let mut position = crazyhouse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1");
+
+        play(&mut position, &["e2e4", "d7d5", "e4d5", "d8d5"]);
+        assert_eq!(position.pockets, [[1, 0, 0, 0, 0], [1, 0, 0, 0, 0]]);
+
+        // the pawn comes back from the pocket
+        play(&mut position, &["P@e4"]);
+        assert_eq!(position.pockets, [[0; 5], [1, 0, 0, 0, 0]]);
+        assert_eq!(position.board[28], Some(Piece::Pawn(Color::White)));
+    }
+
+    #[test]
+    fn pockets_en_passant_captures() {
+        let mut position = crazyhouse("4k3/8/8/3pP3/8/8/8/4K3[] w - d6 0 1");
+
+        play(&mut position, &["e5d6"]);
+        assert_eq!(position.pockets[0], [1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn returns_promoted_pieces_as_pawns() {
+        let mut position = crazyhouse("1r2k3/P7/8/8/8/8/8/4K3[] w - - 0 1");
+
+        // the promoted queen is followed to the square it moves to
+        play(&mut position, &["a7b8q", "e8d7"]);
+        assert_eq!(position.promoted, 1 << 57);
+        play(&mut position, &["b8c7"]);
+        assert_eq!(position.promoted, 1 << 50);
+
+        play(&mut position, &["d7c7"]);
+        assert_eq!(position.promoted, 0);
+        assert_eq!(position.pockets, [[0, 0, 0, 1, 0], [1, 0, 0, 0, 0]]);
+
+        // the same from the fen marker
+        let mut position = crazyhouse("4k3/3Q~4/8/8/8/8/8/4K3[] b - - 0 1");
+        play(&mut position, &["e8d7"]);
+        assert_eq!(position.pockets[1], [1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn drops_on_empty_squares() {
+        let position = crazyhouse("4k3/8/8/8/8/8/8/4K3[Np] w - - 0 1");
+        let drops = |position: &Position| {
+            gen_legal_moves(position)
+                .into_iter()
+                .filter(|halfmove| halfmove.from == halfmove.to)
+                .count()
+        };
+
+        assert_eq!(drops(&position), 62);
+
+        // no pawns on the first or last rank
+        let mut position = position;
+        position.move_next = Color::Black;
+        assert_eq!(drops(&position), 48);
+
+        // in check only the drops in between are left
+        let position = crazyhouse("4k3/8/8/8/8/8/8/K3R3[p] b - - 0 1");
+        assert_eq!(drops(&position), 6);
+        let position = crazyhouse("4k3/8/8/8/Q7/8/8/4K3[p] b - - 0 1");
+        assert_eq!(drops(&position), 3);
+    }
+}
@@ -0,0 +1,377 @@
+// EPD test suite runner. Each position is searched with the given limit and
+// checked against its bm (best move), am (avoid move) or dm (direct mate) operations.
+//
+// usage: endgame2 epdtest <file.epd> [movetime N | depth N | nodes N] [threshold N]
+
+use crate::info::UciInfo;
+use crate::{execute_halfmove, gen_legal_moves, is_in_check, mate_plies};
+use crate::{Color, Engine, HalfMove, Position, SearchLimits, SearchResult};
+use hashbrown::HashMap;
+use std::fs;
+use std::process;
+use std::time::Instant;
+
+enum Limit {
+    MoveTime(u64),
+    Depth(usize),
+    Nodes(usize),
+}
+
+struct EpdEntry {
+    fen: String,
+    operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdEntry {
+    fn operation(&self, opcode: &str) -> Option<&Vec<String>> {
+        return self
+            .operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands);
+    }
+}
+
+pub fn epdtest_command(args: &[String]) {
+    let mut limit = Limit::MoveTime(1000);
+    let mut threshold = 0;
+    let mut path = None;
+
+    let mut tokens = args.iter();
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "movetime" | "depth" | "nodes" | "threshold" => {
+                let Some(value) = tokens.next().and_then(|value| value.parse::<u64>().ok()) else {
+                    println!("Error - expected a number after {}!", token);
+                    return;
+                };
+
+                match token.as_str() {
+                    "movetime" => limit = Limit::MoveTime(value),
+                    "depth" => limit = Limit::Depth(value as usize),
+                    "nodes" => limit = Limit::Nodes(value as usize),
+                    _ => threshold = value as usize,
+                }
+            }
+            _ => path = Some(token.clone()),
+        }
+    }
+
+    let Some(path) = path else {
+        println!("usage: epdtest <file.epd> [movetime N | depth N | nodes N] [threshold N]");
+        return;
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("Error - could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let entries: Vec<EpdEntry> = text.lines().filter_map(parse_epd_line).collect();
+
+    let mut engine = Engine::new();
+    let mut solved = 0;
+    let mut results = vec![];
+    let start_time = Instant::now();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let name = match (entry.operation("id"), entry.operation("c0")) {
+            (Some(id), _) => id.join(" "),
+            (None, Some(comment)) => comment.join(" "),
+            (None, None) => format!("#{}", i + 1),
+        };
+
+        engine.tt.clear();
+        engine.repetition_map = HashMap::new();
+        let searcher = engine.searcher();
+
+        let position = match Position::from_fen(&entry.fen) {
+            Ok(position) => position,
+            Err(e) => {
+                println!("Error - invalid fen in {}: {}", name, e);
+                continue;
+            }
+        };
+
+        let limits = match limit {
+            Limit::MoveTime(ms) => SearchLimits {
+                movetime: Some(ms),
+                ..Default::default()
+            },
+            Limit::Depth(depth) => SearchLimits {
+                depth: Some(depth),
+                ..Default::default()
+            },
+            Limit::Nodes(nodes) => SearchLimits {
+                nodes: Some(nodes),
+                ..Default::default()
+            },
+        };
+        let sink = UciInfo::new(&position, &engine.options);
+        let result = searcher.search(position.clone(), &limits, &sink);
+
+        let parse_moves = |opcode: &str| -> Vec<HalfMove> {
+            entry
+                .operation(opcode)
+                .map(|operands| {
+                    operands
+                        .iter()
+                        .filter_map(|san| HalfMove::from_san(&position, san))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let best_moves = parse_moves("bm");
+        let avoid_moves = parse_moves("am");
+        let mate_in = entry
+            .operation("dm")
+            .and_then(|operands| operands.first())
+            .and_then(|operand| operand.parse::<usize>().ok());
+
+        let is_solution = |halfmove: &HalfMove| {
+            (best_moves.is_empty() || best_moves.contains(halfmove))
+                && !avoid_moves.contains(halfmove)
+        };
+
+        let solve_time = if let Some(mate_in) = mate_in {
+            if found_mate(&result, &position, mate_in) {
+                result.iterations.last().map(|&(time, _)| time)
+            } else {
+                None
+            }
+        } else if best_moves.is_empty() && avoid_moves.is_empty() {
+            None
+        } else {
+            // the solution counts from the iteration it was found and kept until the end
+            let first_kept = result
+                .iterations
+                .iter()
+                .rposition(|(_, halfmove)| !is_solution(halfmove))
+                .map_or(0, |index| index + 1);
+
+            result.iterations.get(first_kept).map(|&(time, _)| time)
+        };
+
+        let best_move = match result.moves.first() {
+            Some(halfmove) => halfmove.move_to_san(&position),
+            None => String::from("(none)"),
+        };
+
+        match solve_time {
+            Some(time) => {
+                solved += 1;
+                results.push(format!(
+                    "{:<24} solved   {:>8} ms  {}",
+                    name, time, best_move
+                ));
+            }
+            None => {
+                let expected = match (entry.operation("bm"), entry.operation("am"), mate_in) {
+                    (_, _, Some(mate_in)) => format!("dm {}", mate_in),
+                    (Some(bm), _, _) => format!("bm {}", bm.join(" ")),
+                    (None, Some(am), _) => format!("am {}", am.join(" ")),
+                    _ => String::from("no bm, am or dm operation"),
+                };
+                results.push(format!(
+                    "{:<24} failed   {:>11}  {} ({})",
+                    name, "", best_move, expected
+                ));
+            }
+        }
+    }
+
+    println!();
+    for line in results.iter() {
+        println!("{}", line);
+    }
+    println!();
+    println!(
+        "Solved {}/{} in {} ms",
+        solved,
+        entries.len(),
+        start_time.elapsed().as_millis()
+    );
+
+    if solved < threshold {
+        println!("Error - solved fewer than {} positions!", threshold);
+        process::exit(1);
+    }
+}
+
+fn found_mate(result: &SearchResult, position: &Position, mate_in: usize) -> bool {
+    // scores are from white's point of view
+    let mating = match position.move_next {
+        Color::White => result.score >= 30000,
+        Color::Black => result.score <= -30000,
+    };
+
+    // the search can't tell a stalemate from a mate, so a line that ends the game has to
+    // end it in check
+    let mut end = position.clone();
+    for &halfmove in result.moves.iter() {
+        execute_halfmove(&mut end, halfmove);
+    }
+    if gen_legal_moves(&end).is_empty() && !is_in_check(&end) {
+        return false;
+    }
+
+    return mating && mate_plies(position, &result.moves, result.score).div_ceil(2) <= mate_in;
+}
+
+fn parse_epd_line(line: &str) -> Option<EpdEntry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    // board, side to move, castling and en passant fields
+    let mut rest = line;
+    let mut fields = vec![];
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    if fields.iter().any(|field| field.is_empty()) {
+        return None;
+    }
+
+    let mut operations = vec![];
+    let mut operands = vec![];
+    let mut operand = String::new();
+    let mut in_quotes = false;
+
+    // operations are separated by semicolons, and quoted operands may contain spaces
+    for char in rest.chars() {
+        match char {
+            '"' => in_quotes = !in_quotes,
+            ';' | ' ' | '\t' if !in_quotes => {
+                if !operand.is_empty() {
+                    operands.push(std::mem::take(&mut operand));
+                }
+
+                if char == ';' && !operands.is_empty() {
+                    let opcode = operands.remove(0);
+                    operations.push((opcode, std::mem::take(&mut operands)));
+                }
+            }
+            _ => operand.push(char),
+        }
+    }
+
+    if !operand.is_empty() {
+        operands.push(operand);
+    }
+    if !operands.is_empty() {
+        let opcode = operands.remove(0);
+        operations.push((opcode, operands));
+    }
+
+    return Some(EpdEntry {
+        fen: fields.join(" "),
+        operations,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info::SilentInfo;
+    use crate::parse_legal_move;
+    use crate::rules::VARIANT_WIN;
+
+    #[test]
+    fn parses_operations() {
+        let entry = parse_epd_line(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -  bm Bb5 Bc4; id \"Open game; 2\";",
+        )
+        .unwrap();
+
+        assert_eq!(
+            entry.fen,
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -"
+        );
+        assert_eq!(entry.operation("bm").unwrap(), &["Bb5", "Bc4"]);
+        assert_eq!(entry.operation("id").unwrap(), &["Open game; 2"]);
+        assert_eq!(entry.operation("am"), None);
+
+        // the last operation may leave out its semicolon
+        let entry = parse_epd_line("8/8/8/8/8/8/8/k1K4R w - - dm 1").unwrap();
+        assert_eq!(entry.operation("dm").unwrap(), &["1"]);
+        assert!(Position::from_fen(&entry.fen).is_ok());
+    }
+
+    #[test]
+    fn skips_comments_and_short_lines() {
+        assert!(parse_epd_line("").is_none());
+        assert!(parse_epd_line("   ").is_none());
+        assert!(parse_epd_line("# bm e4;").is_none());
+        assert!(parse_epd_line("8/8/8/8/8/8/8/k1K4R w -").is_none());
+    }
+
+    // a search result with this line and score, from a depth that says nothing about it
+    fn line_result(position: &Position, score: i32, line: &[&str]) -> SearchResult {
+        let mut end = position.clone();
+        let mut moves = vec![];
+        for uci in line {
+            let halfmove = parse_legal_move(&end, uci, false).unwrap();
+            execute_halfmove(&mut end, halfmove);
+            moves.push(halfmove);
+        }
+
+        return SearchResult {
+            score,
+            moves,
+            depth: 9,
+            nodes: 0,
+            iterations: vec![],
+        };
+    }
+
+    #[test]
+    fn finds_direct_mates() {
+        let engine = Engine::new();
+        let position = Position::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1").unwrap();
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        let result = engine
+            .searcher()
+            .search(position.clone(), &limits, &SilentInfo);
+
+        assert!(found_mate(&result, &position, 1));
+
+        // a mate for the other side is no solution
+        let mut black_to_move = position.clone();
+        black_to_move.move_next = Color::Black;
+        assert!(!found_mate(&result, &black_to_move, 1));
+
+        // the mate is as far away as the line says, not the depth
+        let position = Position::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let result = line_result(&position, VARIANT_WIN, &["a1a6", "b7a6", "b6b7"]);
+        assert!(found_mate(&result, &position, 2));
+        assert!(!found_mate(&result, &position, 1));
+
+        let result = line_result(&position, VARIANT_WIN, &["a1a6"]);
+        assert!(found_mate(&result, &position, 2));
+        assert!(!found_mate(&result, &position, 1));
+
+        // the search scores this stalemate as a mate in one
+        let position = Position::from_fen("k7/8/2K5/8/8/8/8/1R6 w - - 0 1").unwrap();
+        let result = engine
+            .searcher()
+            .search(position.clone(), &limits, &SilentInfo);
+        assert!(!found_mate(&result, &position, 1));
+        assert!(!found_mate(
+            &line_result(&position, VARIANT_WIN, &["b1b7"]),
+            &position,
+            1
+        ));
+    }
+}
@@ -0,0 +1,580 @@
+// FEN parsing. A fen is parsed into a standalone position and checked before anything
+// uses it, so a malformed or impossible one is reported instead of half building a board
+// or panicking on it.
+
+use crate::{crazyhouse, threecheck};
+use crate::{CastlingRights, Color, Piece, PieceSet, Position, Variant};
+use hashbrown::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    MissingField(&'static str),
+    RankCount(usize),
+    RankLength { rank: usize, length: usize },
+    InvalidPiece(char),
+    InvalidPocket(String),
+    KingCount { color: Color, count: usize },
+    TooManyPieces(Color),
+    PawnOnBackRank(u8),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    CastlingWithoutRook(char),
+    InvalidEnPassant(String),
+    EnPassantWithoutPawn(String),
+    InvalidChecks(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    UnexpectedField(String),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let color_name = |color: &Color| {
+            if *color == Color::White {
+                "white"
+            } else {
+                "black"
+            }
+        };
+
+        return match self {
+            FenError::MissingField(field) => write!(f, "missing {}", field),
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::RankLength { rank, length } => {
+                write!(f, "rank {} covers {} squares instead of 8", rank, length)
+            }
+            FenError::InvalidPiece(char) => write!(f, "invalid piece {}", char),
+            FenError::InvalidPocket(pocket) => write!(f, "invalid pieces in hand {}", pocket),
+            FenError::KingCount { color, count } => {
+                write!(f, "{} has {} kings instead of 1", color_name(color), count)
+            }
+            FenError::TooManyPieces(color) => {
+                write!(
+                    f,
+                    "{} has more pieces than a game can reach",
+                    color_name(color)
+                )
+            }
+            FenError::PawnOnBackRank(square) => {
+                write!(
+                    f,
+                    "pawn on the back rank at {}",
+                    crate::int_to_coord(*square)
+                )
+            }
+            FenError::InvalidSideToMove(token) => {
+                write!(f, "expected w or b to move, found {}", token)
+            }
+            FenError::InvalidCastling(token) => write!(f, "invalid castling rights {}", token),
+            FenError::CastlingWithoutRook(char) => {
+                write!(
+                    f,
+                    "castling right {} without its king and rook in place",
+                    char
+                )
+            }
+            FenError::InvalidEnPassant(token) => write!(f, "invalid en passant square {}", token),
+            FenError::EnPassantWithoutPawn(token) => {
+                write!(
+                    f,
+                    "en passant square {} doesn't follow a double pawn push",
+                    token
+                )
+            }
+            FenError::InvalidChecks(token) => write!(f, "invalid check counters {}", token),
+            FenError::InvalidHalfmoveClock(token) => write!(f, "invalid halfmove clock {}", token),
+            FenError::InvalidFullmoveNumber(token) => {
+                write!(f, "invalid fullmove number {}", token)
+            }
+            FenError::UnexpectedField(token) => write!(f, "unexpected field {}", token),
+        };
+    }
+}
+
+impl Position {
+    pub fn from_fen(fen: &str) -> Result<Position, FenError> {
+        return Position::from_variant_fen(fen, Variant::Standard);
+    }
+
+    // the variant decides which positions are possible, as antichess has no royal king
+    // and crazyhouse drops captured pieces back on the board
+    pub fn from_variant_fen(fen: &str, variant: Variant) -> Result<Position, FenError> {
+        let mut fields = fen.split_whitespace();
+
+        let mut position = Position {
+            board: [None; 64],
+            piece_set: PieceSet {
+                all: HashSet::new(),
+                white: HashSet::new(),
+                black: HashSet::new(),
+                white_king: 4,
+                black_king: 60,
+            },
+            move_next: Color::White,
+            castling_rights: CastlingRights::none(),
+            en_passant_target: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            variant,
+            pockets: [[0; 5]; 2],
+            promoted: 0,
+            checks: [0; 2],
+        };
+
+        parse_board(
+            &mut position,
+            fields.next().ok_or(FenError::MissingField("board"))?,
+        )?;
+        validate_pieces(&position)?;
+
+        position.move_next = match fields.next() {
+            Some("w") => Color::White,
+            Some("b") => Color::Black,
+            Some(token) => return Err(FenError::InvalidSideToMove(token.to_string())),
+            None => return Err(FenError::MissingField("side to move")),
+        };
+
+        // everything after the side to move can be left out, as in epd
+        if let Some(token) = fields.next() {
+            parse_castling_rights(&mut position, token)?;
+        }
+
+        if let Some(token) = fields.next() {
+            parse_en_passant(&mut position, token)?;
+        }
+
+        let mut next_token = fields.next();
+
+        // three-check fens give the checks each side has left after the en passant square
+        if let Some(token) = next_token.filter(|token| token.contains('+')) {
+            position.checks = threecheck::parse_remaining_checks(token)
+                .ok_or(FenError::InvalidChecks(token.to_string()))?;
+            next_token = fields.next();
+        }
+
+        if let Some(token) = next_token {
+            position.halfmove_clock = token
+                .parse::<u16>()
+                .ok()
+                .filter(|&clock| clock <= 100)
+                .ok_or(FenError::InvalidHalfmoveClock(token.to_string()))?;
+        }
+
+        if let Some(token) = fields.next() {
+            position.fullmove_number = token
+                .parse::<u16>()
+                .ok()
+                .filter(|&number| number >= 1)
+                .ok_or(FenError::InvalidFullmoveNumber(token.to_string()))?;
+        }
+
+        // or the checks given so far at the end, as +1+0
+        if let Some(token) = fields.next() {
+            position.checks = threecheck::parse_given_checks(token)
+                .ok_or(FenError::InvalidChecks(token.to_string()))?;
+        }
+
+        if let Some(token) = fields.next() {
+            return Err(FenError::UnexpectedField(token.to_string()));
+        }
+
+        return Ok(position);
+    }
+}
+
+fn parse_board(position: &mut Position, field: &str) -> Result<(), FenError> {
+    // crazyhouse fens list the pieces in hand after the board, as in 4k3/8/8/8/8/8/8/4K3[Qn]
+    let (board, pocket) = match field.split_once('[') {
+        Some((board, pocket)) => (board, pocket.trim_end_matches(']')),
+        None => (field, ""),
+    };
+
+    position.pockets =
+        crazyhouse::parse_pocket(pocket).ok_or(FenError::InvalidPocket(pocket.to_string()))?;
+
+    let ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+
+    for (i, rank) in ranks.iter().enumerate() {
+        let rank_start = (7 - i) * 8;
+        let mut file = 0;
+
+        for char in rank.chars() {
+            if char == '~' {
+                // marks the piece just placed as promoted
+                if file == 0 || file > 8 || position.board[rank_start + file - 1].is_none() {
+                    return Err(FenError::InvalidPiece(char));
+                }
+                position.promoted |= 1 << (rank_start + file - 1);
+                continue;
+            }
+
+            if let Some(digit) = char.to_digit(10).filter(|digit| (1..=8).contains(digit)) {
+                file += digit as usize;
+                continue;
+            }
+
+            let piece = char_to_piece(char).ok_or(FenError::InvalidPiece(char))?;
+            if file < 8 {
+                place_piece(position, (rank_start + file) as u8, piece);
+            }
+            file += 1;
+        }
+
+        if file != 8 {
+            return Err(FenError::RankLength {
+                rank: 8 - i,
+                length: file,
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+fn char_to_piece(char: char) -> Option<Piece> {
+    let color = if char.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+
+    return match char.to_ascii_lowercase() {
+        'p' => Some(Piece::Pawn(color)),
+        'n' => Some(Piece::Knight(color)),
+        'b' => Some(Piece::Bishop(color)),
+        'r' => Some(Piece::Rook(color)),
+        'q' => Some(Piece::Queen(color)),
+        'k' => Some(Piece::King(color)),
+        _ => None,
+    };
+}
+
+fn place_piece(position: &mut Position, square: u8, piece: Piece) {
+    position.board[square as usize] = Some(piece);
+    position.piece_set.add_index(square, piece.get_color());
+
+    match piece {
+        Piece::King(Color::White) => position.piece_set.white_king = square,
+        Piece::King(Color::Black) => position.piece_set.black_king = square,
+        _ => {}
+    }
+}
+
+fn validate_pieces(position: &Position) -> Result<(), FenError> {
+    for color in [Color::White, Color::Black] {
+        let count = |kind: fn(Color) -> Piece| {
+            position
+                .board
+                .iter()
+                .filter(|&&piece| piece == Some(kind(color)))
+                .count()
+        };
+
+        let kings = count(Piece::King);
+        if position.variant != Variant::Antichess && kings != 1 {
+            return Err(FenError::KingCount {
+                color,
+                count: kings,
+            });
+        }
+
+        // crazyhouse drops can put more of one side's pieces on the board than it started with
+        if position.variant != Variant::Crazyhouse {
+            let pawns = count(Piece::Pawn);
+            let promoted = count(Piece::Knight).saturating_sub(2)
+                + count(Piece::Bishop).saturating_sub(2)
+                + count(Piece::Rook).saturating_sub(2)
+                + count(Piece::Queen).saturating_sub(1)
+                + kings.saturating_sub(1);
+
+            if pawns > 8 || promoted > 8 - pawns {
+                return Err(FenError::TooManyPieces(color));
+            }
+        }
+    }
+
+    for square in (0..8).chain(56..64) {
+        if let Some(Piece::Pawn(_)) = position.board[square as usize] {
+            return Err(FenError::PawnOnBackRank(square));
+        }
+    }
+
+    return Ok(());
+}
+
+// KQkq stand for the outermost rook on that side of the king, while shredder-fen and
+// x-fen give the rook's file
+fn parse_castling_rights(position: &mut Position, token: &str) -> Result<(), FenError> {
+    if token == "-" {
+        return Ok(());
+    }
+
+    for char in token.chars() {
+        let color = if char.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let (rank_start, king_square) = if color == Color::White {
+            (0, position.piece_set.white_king)
+        } else {
+            (56, position.piece_set.black_king)
+        };
+        let king_file = king_square % 8;
+
+        if king_square / 8 != rank_start / 8
+            || position.board[king_square as usize] != Some(Piece::King(color))
+        {
+            return Err(FenError::CastlingWithoutRook(char));
+        }
+
+        let is_rook =
+            |file: &u8| position.board[(rank_start + file) as usize] == Some(Piece::Rook(color));
+
+        let rook_file = match char.to_ascii_lowercase() {
+            'k' => (king_file + 1..8).rev().find(is_rook),
+            'q' => (0..king_file).find(is_rook),
+            file @ 'a'..='h' => Some(file as u8 - b'a').filter(is_rook),
+            _ => return Err(FenError::InvalidCastling(token.to_string())),
+        };
+
+        let Some(rook_file) = rook_file else {
+            return Err(FenError::CastlingWithoutRook(char));
+        };
+
+        let rights = position.castling_rights.color_mut(color);
+        if rook_file > king_file {
+            rights.kingside = Some(rook_file);
+        } else {
+            rights.queenside = Some(rook_file);
+        }
+    }
+
+    return Ok(());
+}
+
+// the square has to be right behind a pawn of the side that just moved, which passed
+// over it from its starting square
+fn parse_en_passant(position: &mut Position, token: &str) -> Result<(), FenError> {
+    if token == "-" {
+        return Ok(());
+    }
+
+    let target = crate::coord_to_int(token).ok_or(FenError::InvalidEnPassant(token.to_string()))?;
+    let mover = position.move_next.opposite();
+    let rank = if mover == Color::White { 2 } else { 5 };
+
+    if target / 8 != rank {
+        return Err(FenError::EnPassantWithoutPawn(token.to_string()));
+    }
+
+    let (pawn, start) = if mover == Color::White {
+        (target + 8, target - 8)
+    } else {
+        (target - 8, target + 8)
+    };
+
+    if position.board[pawn as usize] != Some(Piece::Pawn(mover))
+        || position.board[target as usize].is_some()
+        || position.board[start as usize].is_some()
+    {
+        return Err(FenError::EnPassantWithoutPawn(token.to_string()));
+    }
+
+    position.en_passant_target = Some(target);
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(fen: &str) -> FenError {
+        return Position::from_fen(fen).err().unwrap();
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let fens = [
+            (
+                Variant::Standard,
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            ),
+            (
+                Variant::Standard,
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            ),
+            (
+                Variant::Standard,
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq - 37 90",
+            ),
+            (
+                Variant::Crazyhouse,
+                "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQ~KB1R[Pn] b KQkq - 0 3",
+            ),
+            (
+                Variant::ThreeCheck,
+                "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 3+2 0 2",
+            ),
+        ];
+
+        for (variant, fen) in fens {
+            assert_eq!(
+                Position::from_variant_fen(fen, variant).unwrap().to_fen(),
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn reads_other_notations() {
+        // epd style, without the move counters
+        let position = Position::from_fen("8/8/8/4k3/8/8/8/R3K3 b Q -").unwrap();
+        assert_eq!(position.to_fen(), "8/8/8/4k3/8/8/8/R3K3 b Q - 0 1");
+
+        // the checks given, at the end
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2 +0+1";
+        let position = Position::from_variant_fen(fen, Variant::ThreeCheck).unwrap();
+        assert_eq!(position.checks, [0, 1]);
+
+        // shredder-fen and x-fen name the rook files
+        let position =
+            Position::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9")
+                .unwrap();
+        assert_eq!(position.castling_rights.white.kingside, Some(7));
+        assert_eq!(position.castling_rights.white.queenside, Some(5));
+
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/1R2K1RR w Kb - 0 1");
+        assert_eq!(position.err(), Some(FenError::CastlingWithoutRook('b')));
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/1R2K1RR w KB - 0 1").unwrap();
+        assert_eq!(position.castling_rights.white.kingside, Some(7));
+        assert_eq!(position.castling_rights.white.queenside, Some(1));
+    }
+
+    #[test]
+    fn round_trips_chess960_castling() {
+        // shredder-fen names every rook file, x-fen only an inner rook's
+        let fens = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9",
+            ),
+            (
+                "rk2r3/8/8/8/8/8/8/RK2R2R w EAea - 0 1",
+                "rk2r3/8/8/8/8/8/8/RK2R2R w EQkq - 0 1",
+            ),
+            (
+                "4k3/8/8/8/8/8/8/6KR w H - 0 1",
+                "4k3/8/8/8/8/8/8/6KR w K - 0 1",
+            ),
+        ];
+
+        for (shredder_fen, x_fen) in fens {
+            let from_shredder = Position::from_fen(shredder_fen).unwrap();
+            assert_eq!(from_shredder.to_shredder_fen(), shredder_fen);
+            assert_eq!(from_shredder.to_fen(), x_fen);
+
+            let from_x_fen = Position::from_fen(x_fen).unwrap();
+            assert_eq!(from_x_fen.to_shredder_fen(), shredder_fen);
+            assert_eq!(from_x_fen.to_fen(), x_fen);
+            assert_eq!(from_x_fen.castling_rights, from_shredder.castling_rights);
+        }
+    }
+
+    #[test]
+    fn reports_broken_fields() {
+        assert_eq!(error(""), FenError::MissingField("board"));
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3"),
+            FenError::MissingField("side to move")
+        );
+        assert_eq!(error("4k3/8/8/8/8/8/4K3 w - -"), FenError::RankCount(7));
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K4 w - -"),
+            FenError::RankLength { rank: 1, length: 9 }
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4X3 w - -"),
+            FenError::InvalidPiece('X')
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 x - -"),
+            FenError::InvalidSideToMove("x".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K2R w KX -"),
+            FenError::InvalidCastling("KX".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w K -"),
+            FenError::CastlingWithoutRook('K')
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - e9"),
+            FenError::InvalidEnPassant("e9".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - e6"),
+            FenError::EnPassantWithoutPawn("e6".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 101 1"),
+            FenError::InvalidHalfmoveClock("101".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"),
+            FenError::InvalidFullmoveNumber("0".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x"),
+            FenError::InvalidChecks("x".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 0 1 +0+0 x"),
+            FenError::UnexpectedField("x".into())
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/4K3[X] w - -"),
+            FenError::InvalidPocket("X".into())
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        assert_eq!(
+            error("8/8/8/8/8/8/8/4K3 w - -"),
+            FenError::KingCount {
+                color: Color::Black,
+                count: 0
+            }
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/3KK3 w - -"),
+            FenError::KingCount {
+                color: Color::White,
+                count: 2
+            }
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/PPPPPPPP/QQQQKQQQ w - -"),
+            FenError::TooManyPieces(Color::White)
+        );
+        assert_eq!(
+            error("4k3/8/8/8/8/8/8/P3K3 w - -"),
+            FenError::PawnOnBackRank(0)
+        );
+
+        // a lone king is fine in antichess, and extra pieces in crazyhouse
+        assert!(Position::from_variant_fen("8/8/8/8/8/8/8/4K3 w - -", Variant::Antichess).is_ok());
+        assert!(Position::from_variant_fen(
+            "4k3/8/8/8/8/8/PPPPPPPP/QQQQKQQQ[] w - -",
+            Variant::Crazyhouse
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,255 @@
+// Search progress. The search reports what it found through an InfoSink instead of
+// printing it, so the engine can run inside other programs. UciInfo is the sink that
+// writes the uci info and bestmove lines.
+
+use crate::tt::Bound;
+use crate::{execute_halfmove, Color, EngineOptions, HalfMove, Position};
+
+// win rate model parameters, a is the score with even chances to win and b how fast
+// they grow around it. in the usual logistic shape until wdlfit has games to go on:
+// a pawn up is a small edge, and three or four pawns up wins more often than not. fast
+// self-play ends in repetitions whatever the score, which fits to almost all draws
+const WDL_MODEL_A: f64 = 350.0;
+const WDL_MODEL_B: f64 = 100.0;
+
+// from the side to move. mates are counted in moves, negative when being mated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Score {
+    Cp(i32),
+    Mate(i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub seldepth: usize,
+    // ms since the search started
+    pub time: u128,
+    pub nodes: usize,
+    pub hashfull: usize,
+    pub tbhits: usize,
+    pub score: Score,
+    // from the side to move too. a score outside the aspiration window, or from a depth
+    // the search was stopped in, is only a bound
+    pub bound: Bound,
+    // only the part of the line that can actually be played
+    pub pv: Vec<HalfMove>,
+}
+
+// shared with the search threads, though only the main one reports
+pub trait InfoSink: Sync {
+    // after every finished iteration, and once more with the final line
+    fn info(&self, info: &SearchInfo);
+
+    // the move the search settled on, sent last. none when there are no legal moves
+    fn bestmove(&self, _best: Option<HalfMove>, _ponder: Option<HalfMove>) {}
+
+    // the root move being searched, once a search has been running for a while
+    fn currmove(&self, _halfmove: HalfMove, _number: usize) {}
+
+    fn message(&self, _text: &str) {}
+}
+
+impl Score {
+    // scores are kept from white's side during the search, and a mate is as far away
+    // as the plies it takes, counting the mating move
+    pub(crate) fn from_search(score: i32, mate_plies: usize, move_next: Color) -> Score {
+        let score = if move_next == Color::White {
+            score
+        } else {
+            -score
+        };
+
+        let mate_in = mate_plies.div_ceil(2) as i32;
+
+        if score >= 30000 {
+            return Score::Mate(mate_in);
+        } else if score <= -30000 {
+            return Score::Mate(-mate_in);
+        }
+
+        return Score::Cp(score);
+    }
+
+    // the bound of a score from white's side, seen from the side to move
+    pub(crate) fn bound_from_search(bound: Bound, move_next: Color) -> Bound {
+        return match (bound, move_next) {
+            (Bound::Lower, Color::Black) => Bound::Upper,
+            (Bound::Upper, Color::Black) => Bound::Lower,
+            (bound, _) => bound,
+        };
+    }
+
+    // win, draw and loss chances in permille
+    pub fn wdl(&self) -> (u32, u32, u32) {
+        let score = match *self {
+            Score::Mate(moves) if moves > 0 => return (1000, 0, 0),
+            Score::Mate(_) => return (0, 0, 1000),
+            Score::Cp(score) => score,
+        };
+
+        let win = win_rate(score);
+        let loss = win_rate(-score);
+
+        return (win, 1000 - win - loss, loss);
+    }
+}
+
+// logistic fit of game results against the eval of the side to move
+fn win_rate(score: i32) -> u32 {
+    let score = score.clamp(-8000, 8000) as f64;
+    return (1000.0 / (1.0 + ((WDL_MODEL_A - score) / WDL_MODEL_B).exp())).round() as u32;
+}
+
+// for searches whose progress nobody watches
+pub struct SilentInfo;
+
+pub struct UciInfo {
+    // the root, for san moves in the pv
+    position: Position,
+    show_wdl: bool,
+    use_san: bool,
+    chess960: bool,
+}
+
+impl UciInfo {
+    pub fn new(position: &Position, options: &EngineOptions) -> Self {
+        Self {
+            position: position.clone(),
+            show_wdl: options.show_wdl,
+            use_san: options.debug_use_san,
+            chess960: options.chess960,
+        }
+    }
+}
+
+impl InfoSink for UciInfo {
+    fn info(&self, info: &SearchInfo) {
+        print!(
+            "info depth {} seldepth {} time {} nodes {} nps {} hashfull {} tbhits {} ",
+            info.depth,
+            info.seldepth,
+            info.time,
+            info.nodes,
+            info.nodes as u128 * 1000 / info.time.max(1),
+            info.hashfull,
+            info.tbhits
+        );
+
+        match info.score {
+            Score::Cp(score) => print!("score cp {} ", score),
+            Score::Mate(moves) => print!("score mate {} ", moves),
+        }
+
+        match info.bound {
+            Bound::Lower => print!("lowerbound "),
+            Bound::Upper => print!("upperbound "),
+            Bound::Exact => {}
+        }
+
+        if self.show_wdl {
+            let (win, draw, loss) = info.score.wdl();
+            print!("wdl {} {} {} ", win, draw, loss);
+        }
+
+        if info.pv.is_empty() {
+            println!();
+            return;
+        }
+
+        print!("pv ");
+
+        let mut position = self.position.clone();
+        for &halfmove in info.pv.iter() {
+            if self.use_san {
+                print!("{} ", halfmove.move_to_san(&position));
+                execute_halfmove(&mut position, halfmove);
+            } else {
+                print!("{} ", halfmove.move_to_uci(self.chess960));
+            }
+        }
+        println!();
+    }
+
+    fn bestmove(&self, best: Option<HalfMove>, ponder: Option<HalfMove>) {
+        match (best, ponder) {
+            (Some(best), Some(ponder)) => println!(
+                "bestmove {} ponder {}",
+                best.move_to_uci(self.chess960),
+                ponder.move_to_uci(self.chess960)
+            ),
+            (Some(best), None) => println!("bestmove {}", best.move_to_uci(self.chess960)),
+            // the null move, as uci asks for a position without moves
+            (None, _) => println!("bestmove 0000"),
+        }
+    }
+
+    fn currmove(&self, halfmove: HalfMove, number: usize) {
+        println!(
+            "info currmove {} currmovenumber {}",
+            halfmove.move_to_uci(self.chess960),
+            number
+        );
+    }
+
+    fn message(&self, text: &str) {
+        println!("info string {}", text);
+    }
+}
+
+impl InfoSink for SilentInfo {
+    fn info(&self, _info: &SearchInfo) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favours_the_side_ahead() {
+        let (win, draw, loss) = Score::Cp(0).wdl();
+        assert_eq!(win, loss);
+        assert!(draw > 900);
+
+        let (win, _, loss) = Score::Cp(300).wdl();
+        assert!(win > 300 && loss < 10);
+
+        let (win, _, _) = Score::Cp(1000).wdl();
+        assert!(win > 990);
+
+        assert_eq!(Score::Cp(-300).wdl(), {
+            let (win, draw, loss) = Score::Cp(300).wdl();
+            (loss, draw, win)
+        });
+        assert_eq!(Score::Mate(-2).wdl(), (0, 0, 1000));
+    }
+
+    #[test]
+    fn counts_mates_in_moves() {
+        assert_eq!(Score::from_search(50000, 1, Color::White), Score::Mate(1));
+        assert_eq!(Score::from_search(50000, 3, Color::White), Score::Mate(2));
+        assert_eq!(Score::from_search(50000, 2, Color::Black), Score::Mate(-1));
+        assert_eq!(Score::from_search(-50000, 3, Color::Black), Score::Mate(2));
+        assert_eq!(Score::from_search(-120, 7, Color::Black), Score::Cp(120));
+    }
+
+    #[test]
+    fn turns_bounds_with_the_score() {
+        assert_eq!(
+            Score::bound_from_search(Bound::Lower, Color::White),
+            Bound::Lower
+        );
+        assert_eq!(
+            Score::bound_from_search(Bound::Lower, Color::Black),
+            Bound::Upper
+        );
+        assert_eq!(
+            Score::bound_from_search(Bound::Upper, Color::Black),
+            Bound::Lower
+        );
+        assert_eq!(
+            Score::bound_from_search(Bound::Exact, Color::Black),
+            Bound::Exact
+        );
+    }
+}
@@ -0,0 +1,426 @@
+// A small JSON reader and writer for the analysis server. It covers what requests and
+// replies need, objects keep their key order and numbers are read as f64.
+
+use std::fmt;
+
+// deeper arrays and objects are refused, the parser recurses once per level
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            index: 0,
+            depth: 0,
+        };
+
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.index < parser.chars.len() {
+            return Err(format!("unexpected text at {}", parser.index));
+        }
+
+        return Ok(value);
+    }
+
+    // none for a missing key, or when this isn't an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        let Json::Object(entries) = self else {
+            return None;
+        };
+
+        return entries
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value);
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        };
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        return match self {
+            Json::Bool(bool) => Some(*bool),
+            _ => None,
+        };
+    }
+
+    // only whole, non negative numbers
+    pub fn as_u64(&self) -> Option<u64> {
+        return match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => Some(*number as u64),
+            _ => None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        return match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        };
+    }
+}
+
+impl From<&str> for Json {
+    fn from(string: &str) -> Json {
+        return Json::String(string.to_string());
+    }
+}
+
+impl From<String> for Json {
+    fn from(string: String) -> Json {
+        return Json::String(string);
+    }
+}
+
+impl From<bool> for Json {
+    fn from(bool: bool) -> Json {
+        return Json::Bool(bool);
+    }
+}
+
+impl From<i32> for Json {
+    fn from(number: i32) -> Json {
+        return Json::Number(number as f64);
+    }
+}
+
+impl From<usize> for Json {
+    fn from(number: usize) -> Json {
+        return Json::Number(number as f64);
+    }
+}
+
+impl From<u64> for Json {
+    fn from(number: u64) -> Json {
+        return Json::Number(number as f64);
+    }
+}
+
+impl From<u128> for Json {
+    fn from(number: u128) -> Json {
+        return Json::Number(number as f64);
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(bool) => write!(f, "{}", bool),
+            Json::Number(number) if number.is_finite() => write!(f, "{}", number),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(string) => write_string(f, string),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        };
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for char in string.chars() {
+        match char {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            char if (char as u32) < 0x20 => write!(f, "\\u{:04x}", char as u32)?,
+            char => write!(f, "{}", char)?,
+        }
+    }
+
+    return write!(f, "\"");
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    // arrays and objects open around the current value
+    depth: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.index < self.chars.len() && self.chars[self.index].is_whitespace() {
+            self.index += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        return self.chars.get(self.index).copied();
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(format!("expected {} at {}", expected, self.index));
+        }
+
+        self.index += 1;
+        return Ok(());
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        return match self.peek() {
+            Some('{') => self.nested(Parser::object),
+            Some('[') => self.nested(Parser::array),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('n') => self.literal("null", Json::Null),
+            Some(char) if char == '-' || char.is_ascii_digit() => self.number(),
+            Some(char) => Err(format!("unexpected {} at {}", char, self.index)),
+            None => Err(String::from("unexpected end of input")),
+        };
+    }
+
+    fn nested(&mut self, parse: fn(&mut Parser) -> Result<Json, String>) -> Result<Json, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!(
+                "nested deeper than {} at {}",
+                MAX_DEPTH, self.index
+            ));
+        }
+
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+
+        return value;
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.index + word.len();
+        if end > self.chars.len() || self.chars[self.index..end].iter().collect::<String>() != word
+        {
+            return Err(format!("invalid literal at {}", self.index));
+        }
+
+        self.index = end;
+        return Ok(value);
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.index;
+        while self.index < self.chars.len()
+            && matches!(
+                self.chars[self.index],
+                '-' | '+' | '.' | 'e' | 'E' | '0'..='9'
+            )
+        {
+            self.index += 1;
+        }
+
+        let text: String = self.chars[start..self.index].iter().collect();
+        return text
+            .parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid number {}", text));
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            let Some(&char) = self.chars.get(self.index) else {
+                return Err(String::from("unterminated string"));
+            };
+            self.index += 1;
+
+            match char {
+                '"' => return Ok(string),
+                '\\' => {
+                    let Some(&escape) = self.chars.get(self.index) else {
+                        return Err(String::from("unterminated string"));
+                    };
+                    self.index += 1;
+
+                    string.push(match escape {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.unicode_escape()?,
+                        _ => return Err(format!("invalid escape \\{}", escape)),
+                    });
+                }
+                char => string.push(char),
+            }
+        }
+    }
+
+    // surrogate pairs aren't joined, anything outside the basic plane becomes U+FFFD
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let end = self.index + 4;
+        if end > self.chars.len() {
+            return Err(String::from("unterminated string"));
+        }
+
+        let hex: String = self.chars[self.index..end].iter().collect();
+        self.index = end;
+
+        let code =
+            u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape \\u{}", hex))?;
+        return Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = vec![];
+
+        if self.peek() == Some(']') {
+            self.index += 1;
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some(']') => {
+                    self.index += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(format!("expected , or ] at {}", self.index)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut entries = vec![];
+
+        if self.peek() == Some('}') {
+            self.index += 1;
+            return Ok(Json::Object(entries));
+        }
+
+        loop {
+            if self.peek() != Some('"') {
+                return Err(format!("expected a key at {}", self.index));
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some('}') => {
+                    self.index += 1;
+                    return Ok(Json::Object(entries));
+                }
+                _ => return Err(format!("expected , or }} at {}", self.index)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let json =
+            Json::parse(r#"{"fen": "8/8", "moves": ["e2e4", "e7e5"], "limits": {"depth": 6}}"#)
+                .unwrap();
+
+        assert_eq!(json.get("fen").and_then(Json::as_str), Some("8/8"));
+        assert_eq!(
+            json.get("moves")
+                .and_then(Json::as_array)
+                .map(<[Json]>::len),
+            Some(2)
+        );
+        assert_eq!(
+            json.get("limits")
+                .and_then(|limits| limits.get("depth"))
+                .and_then(Json::as_u64),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn round_trips_escapes() {
+        let json = Json::from("a \"quoted\"\n\\ \u{1}");
+        let text = json.to_string();
+
+        assert_eq!(text, r#""a \"quoted\"\n\\ \u0001""#);
+        assert_eq!(Json::parse(&text).unwrap(), json);
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        for text in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "tru",
+            "\"open",
+            "1 2",
+            "{1: 2}",
+        ] {
+            assert!(Json::parse(text).is_err(), "{} parsed", text);
+        }
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Json::parse(&"[".repeat(200000)).is_err());
+        assert!(Json::parse(&"{\"a\":".repeat(200000)).is_err());
+    }
+
+    #[test]
+    fn reads_whole_numbers_only() {
+        assert_eq!(Json::parse("12").unwrap().as_u64(), Some(12));
+        assert_eq!(Json::parse("1.5").unwrap().as_u64(), None);
+        assert_eq!(Json::parse("-3").unwrap().as_u64(), None);
+    }
+}
@@ -0,0 +1,97 @@
+// King of the Hill. Besides checkmate, a side also wins as soon as its king reaches one
+// of the four center squares.
+
+use crate::rules::{king_square, mover_king_safe, win_for, Rules};
+use crate::{Color, Position};
+
+const CENTER: [u8; 4] = [27, 28, 35, 36];
+
+// per step the king is closer to the center
+const KING_CENTER_BONUS: i32 = 10;
+
+pub struct KingOfTheHill;
+
+impl Rules for KingOfTheHill {
+    fn game_result(&self, position: &Position) -> Option<i32> {
+        let mover = position.move_next.opposite();
+
+        if CENTER.contains(&king_square(position, mover)) && mover_king_safe(position) {
+            return Some(win_for(mover));
+        }
+
+        // only possible when set up that way
+        if CENTER.contains(&king_square(position, position.move_next)) {
+            return Some(win_for(position.move_next));
+        }
+
+        return None;
+    }
+
+    fn eval(&self, position: &Position) -> i32 {
+        let mut eval = 0;
+
+        for color in [Color::White, Color::Black] {
+            let king = king_square(position, color);
+            let distance = CENTER
+                .iter()
+                .map(|&center| {
+                    (king % 8)
+                        .abs_diff(center % 8)
+                        .max((king / 8).abs_diff(center / 8))
+                })
+                .min()
+                .unwrap() as i32;
+
+            if color == Color::White {
+                eval += (3 - distance) * KING_CENTER_BONUS;
+            } else {
+                eval -= (3 - distance) * KING_CENTER_BONUS;
+            }
+        }
+
+        return eval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::VARIANT_WIN;
+    use crate::{gen_legal_moves, Variant};
+
+    fn koth(fen: &str) -> Position {
+        return Position::from_variant_fen(fen, Variant::KingOfTheHill).unwrap();
+    }
+
+    #[test]
+    fn wins_on_the_hill() {
+        let position = koth("8/8/8/8/4K3/8/8/k7 b - - 0 1");
+        assert_eq!(KingOfTheHill.game_result(&position), Some(VARIANT_WIN));
+        assert!(gen_legal_moves(&position).is_empty());
+
+        let position = koth("8/8/8/4k3/8/8/8/K7 w - - 0 1");
+        assert_eq!(KingOfTheHill.game_result(&position), Some(-VARIANT_WIN));
+
+        let position = koth("8/8/8/8/8/2K5/8/k7 w - - 0 1");
+        assert_eq!(KingOfTheHill.game_result(&position), None);
+    }
+
+    #[test]
+    fn ignores_a_king_left_in_check() {
+        let position = koth("8/8/8/3rK3/8/8/8/k7 b - - 0 1");
+        assert_eq!(KingOfTheHill.game_result(&position), None);
+    }
+
+    #[test]
+    fn rewards_the_way_to_the_centre() {
+        assert_eq!(KingOfTheHill.eval(&Position::startpos()), 0);
+        assert_eq!(
+            KingOfTheHill.eval(&koth("8/8/8/8/8/2K5/8/k7 w - - 0 1")),
+            20
+        );
+        assert_eq!(
+            KingOfTheHill.eval(&koth("8/8/2k5/8/8/8/8/K7 w - - 0 1")),
+            -20
+        );
+    }
+}
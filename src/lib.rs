@@ -0,0 +1,3875 @@
+#![allow(clippy::needless_return)]
+#![allow(clippy::too_many_arguments)]
+#![allow(clippy::unnecessary_unwrap)]
+#![allow(clippy::unnecessary_sort_by)]
+#![allow(clippy::manual_is_multiple_of)]
+
+// The engine as a library: positions and fen, move generation, evaluation and the
+// search, plus the tools built on them. The uci front end in main.rs is one user of it.
+
+pub mod adjudication;
+pub mod antichess;
+pub mod atomic;
+pub mod bench;
+pub mod crazyhouse;
+pub mod epd;
+pub mod fen;
+pub mod info;
+pub mod json;
+pub mod kingofthehill;
+pub mod makebook;
+pub mod mate;
+pub mod perft;
+pub mod pgn;
+pub mod play;
+pub mod polyglot;
+pub mod rules;
+pub mod serve;
+pub mod syzygy;
+pub mod threecheck;
+pub mod tournament;
+pub mod tt;
+pub mod wdlfit;
+pub mod xboard;
+
+use hashbrown::{HashMap, HashSet};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, thread};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Color {
+    Black,
+    White,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Piece {
+    Pawn(Color),
+    Knight(Color),
+    Bishop(Color),
+    Rook(Color),
+    Queen(Color),
+    King(Color),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Variant {
+    Standard,
+    Crazyhouse,
+    Atomic,
+    KingOfTheHill,
+    ThreeCheck,
+    Antichess,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HalfmoveFlag {
+    KnightPromotion,
+    BishopPromotion,
+    RookPromotion,
+    QueenPromotion,
+    // antichess only
+    KingPromotion,
+    Castle,
+    EnPassant,
+    DoublePawnMove,
+    PawnDrop,
+    KnightDrop,
+    BishopDrop,
+    RookDrop,
+    QueenDrop,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub struct HalfMove {
+    pub from: u8,
+    pub to: u8,
+    pub flag: Option<HalfmoveFlag>,
+    pub is_capture: bool,
+}
+
+// files of the rooks that can still castle, which only differ from the corners in chess960
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorCastlingRights {
+    pub kingside: Option<u8>,
+    pub queenside: Option<u8>,
+}
+
+#[derive(Clone)]
+pub struct PieceSet {
+    pub all: HashSet<u8>,
+    pub white: HashSet<u8>,
+    pub black: HashSet<u8>,
+    pub white_king: u8,
+    pub black_king: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CastlingRights {
+    pub black: ColorCastlingRights,
+    pub white: ColorCastlingRights,
+}
+
+#[derive(Clone)]
+pub struct Position {
+    pub board: [Option<Piece>; 64],
+    pub piece_set: PieceSet,
+    pub move_next: Color,
+    pub castling_rights: CastlingRights,
+    pub en_passant_target: Option<u8>,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+    pub variant: Variant,
+    // crazyhouse pieces in hand, pawn to queen for white then black
+    pub pockets: [[u8; 5]; 2],
+    // squares of crazyhouse pieces that were promoted, they go back to the pocket as pawns
+    pub promoted: u64,
+    // three-check counters, checks given by white then black
+    pub checks: [u8; 2],
+}
+
+#[derive(Clone)]
+pub struct PositionTree {
+    nodes: Vec<Vec<PositionTreeNode>>,
+    position: Position,
+    depth: usize,
+    leaf_size: usize,
+}
+
+#[derive(Clone)]
+struct PositionTreeNode {
+    // parent, top_parent, and halfmove for root don't matter.
+    // using option here would be inefficient
+    parent: usize,
+    children: Option<(usize, usize)>,
+    halfmove: HalfMove,
+    score: Option<i32>,
+}
+
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match *self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+}
+
+impl Variant {
+    pub fn rules(&self) -> &'static dyn rules::Rules {
+        return match self {
+            Variant::Standard => &rules::Standard,
+            Variant::Crazyhouse => &crazyhouse::Crazyhouse,
+            Variant::Atomic => &atomic::Atomic,
+            Variant::KingOfTheHill => &kingofthehill::KingOfTheHill,
+            Variant::ThreeCheck => &threecheck::ThreeCheck,
+            Variant::Antichess => &antichess::Antichess,
+        };
+    }
+
+    // names as used by UCI_Variant
+    pub fn from_name(name: &str) -> Option<Variant> {
+        return match name {
+            "chess" => Some(Variant::Standard),
+            "crazyhouse" => Some(Variant::Crazyhouse),
+            "atomic" => Some(Variant::Atomic),
+            "kingofthehill" => Some(Variant::KingOfTheHill),
+            "3check" => Some(Variant::ThreeCheck),
+            "antichess" => Some(Variant::Antichess),
+            _ => None,
+        };
+    }
+}
+
+impl CastlingRights {
+    pub fn none() -> Self {
+        Self {
+            black: ColorCastlingRights {
+                kingside: None,
+                queenside: None,
+            },
+            white: ColorCastlingRights {
+                kingside: None,
+                queenside: None,
+            },
+        }
+    }
+
+    pub fn color(&self, color: Color) -> &ColorCastlingRights {
+        return match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        };
+    }
+
+    pub fn color_mut(&mut self, color: Color) -> &mut ColorCastlingRights {
+        return match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        };
+    }
+
+    // a move from or to a castling rook's square means that rook can't castle anymore
+    fn remove_rook_square(&mut self, square: u8) {
+        let color = match square / 8 {
+            0 => Color::White,
+            7 => Color::Black,
+            _ => return,
+        };
+        let rights = self.color_mut(color);
+
+        if rights.kingside == Some(square % 8) {
+            rights.kingside = None;
+        }
+        if rights.queenside == Some(square % 8) {
+            rights.queenside = None;
+        }
+    }
+}
+
+impl ColorCastlingRights {
+    fn clear(&mut self) {
+        self.kingside = None;
+        self.queenside = None;
+    }
+}
+
+impl Piece {
+    pub fn get_color(&self) -> Color {
+        match self {
+            Piece::Pawn(color)
+            | Piece::Knight(color)
+            | Piece::Bishop(color)
+            | Piece::Rook(color)
+            | Piece::Queen(color)
+            | Piece::King(color) => *color,
+        }
+    }
+
+    fn get_cp_val(&self) -> u16 {
+        match self {
+            Piece::Pawn(_) => 100,
+            Piece::Knight(_) => 320,
+            Piece::Bishop(_) => 290,
+            Piece::Rook(_) => 490,
+            Piece::Queen(_) => 900,
+            Piece::King(_) => 60000,
+        }
+    }
+}
+
+impl fmt::Debug for HalfMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.flag.is_none() {
+            return write!(f, "[{} {}]", int_to_coord(self.from), int_to_coord(self.to));
+        } else {
+            return write!(
+                f,
+                "[{:?} {} {}]",
+                self.flag.as_ref().unwrap(),
+                int_to_coord(self.from),
+                int_to_coord(self.to)
+            );
+        }
+    }
+}
+
+impl HalfMove {
+    pub fn move_to_coords(&self) -> String {
+        let promotion_str = match self.flag {
+            Some(HalfmoveFlag::QueenPromotion) => "q",
+            Some(HalfmoveFlag::KingPromotion) => "k",
+            Some(HalfmoveFlag::RookPromotion) => "r",
+            Some(HalfmoveFlag::KnightPromotion) => "n",
+            Some(HalfmoveFlag::BishopPromotion) => "b",
+            _ => "",
+        };
+
+        if let Some(piece) = self.drop_char() {
+            return format!("{}@{}", piece, int_to_coord(self.to));
+        }
+
+        // castles are stored as king takes rook, but written as the king's move
+        if self.flag == Some(HalfmoveFlag::Castle) {
+            return format!(
+                "{}{}",
+                int_to_coord(self.from),
+                int_to_coord(self.castle_king_square())
+            );
+        }
+
+        return format!(
+            "{}{}{}",
+            int_to_coord(self.from),
+            int_to_coord(self.to),
+            promotion_str
+        );
+    }
+
+    // chess960 guis expect castles as king takes rook, since the king's move alone can
+    // be ambiguous there
+    pub fn move_to_uci(&self, chess960: bool) -> String {
+        if chess960 && self.flag == Some(HalfmoveFlag::Castle) {
+            return format!("{}{}", int_to_coord(self.from), int_to_coord(self.to));
+        }
+
+        return self.move_to_coords();
+    }
+
+    fn is_kingside_castle(&self) -> bool {
+        return self.to > self.from;
+    }
+
+    // where the king and rook end up, the same squares as in standard chess
+    fn castle_king_square(&self) -> u8 {
+        let rank_start = self.from - self.from % 8;
+        return rank_start + if self.is_kingside_castle() { 6 } else { 2 };
+    }
+
+    fn castle_rook_square(&self) -> u8 {
+        let rank_start = self.from - self.from % 8;
+        return rank_start + if self.is_kingside_castle() { 5 } else { 3 };
+    }
+
+    pub fn promotion_char(&self) -> Option<char> {
+        return match self.flag {
+            Some(HalfmoveFlag::KnightPromotion) => Some('N'),
+            Some(HalfmoveFlag::BishopPromotion) => Some('B'),
+            Some(HalfmoveFlag::RookPromotion) => Some('R'),
+            Some(HalfmoveFlag::QueenPromotion) => Some('Q'),
+            Some(HalfmoveFlag::KingPromotion) => Some('K'),
+            _ => None,
+        };
+    }
+
+    // crazyhouse drops start and end on the square the piece is dropped on
+    pub fn drop_char(&self) -> Option<char> {
+        return match self.flag {
+            Some(HalfmoveFlag::PawnDrop) => Some('P'),
+            Some(HalfmoveFlag::KnightDrop) => Some('N'),
+            Some(HalfmoveFlag::BishopDrop) => Some('B'),
+            Some(HalfmoveFlag::RookDrop) => Some('R'),
+            Some(HalfmoveFlag::QueenDrop) => Some('Q'),
+            _ => None,
+        };
+    }
+
+    pub fn move_to_san(&self, position: &Position) -> String {
+        let mut san = String::new();
+
+        if let Some(piece) = self.drop_char() {
+            san += &format!("{}@{}", piece, int_to_coord(self.to));
+        } else if self.flag == Some(HalfmoveFlag::Castle) {
+            if self.is_kingside_castle() {
+                san += "O-O";
+            } else {
+                san += "O-O-O";
+            }
+        } else {
+            let piece = position.board[self.from as usize].unwrap();
+            let is_capture = self.flag == Some(HalfmoveFlag::EnPassant)
+                || position.board[self.to as usize].is_some();
+
+            if let Piece::Pawn(_) = piece {
+                if is_capture {
+                    san.push((b'a' + self.from % 8) as char);
+                }
+            } else {
+                san.push(piece_to_char(Some(piece), false).to_ascii_uppercase());
+
+                // other pieces of the same type that can reach the same square
+                let others: Vec<u8> = gen_legal_moves(position)
+                    .iter()
+                    .filter(|other| {
+                        other.to == self.to
+                            && other.from != self.from
+                            && other.flag != Some(HalfmoveFlag::Castle)
+                            && position.board[other.from as usize] == Some(piece)
+                    })
+                    .map(|other| other.from)
+                    .collect();
+
+                if !others.is_empty() {
+                    if others.iter().all(|&from| from % 8 != self.from % 8) {
+                        san.push((b'a' + self.from % 8) as char);
+                    } else if others.iter().all(|&from| from / 8 != self.from / 8) {
+                        san.push((b'1' + self.from / 8) as char);
+                    } else {
+                        san += &int_to_coord(self.from);
+                    }
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+
+            san += &int_to_coord(self.to);
+
+            if let Some(promotion) = self.promotion_char() {
+                san.push('=');
+                san.push(promotion);
+            }
+
+            if self.flag == Some(HalfmoveFlag::EnPassant) {
+                san += " e.p.";
+            }
+        }
+
+        let mut new_pos = position.clone();
+        execute_halfmove(&mut new_pos, *self);
+
+        // antichess has no check
+        if new_pos.variant != Variant::Antichess && is_in_check(&new_pos) {
+            if gen_legal_moves(&new_pos).is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+
+        return san;
+    }
+
+    pub fn from_san(position: &Position, san: &str) -> Option<HalfMove> {
+        let san = san
+            .trim_end_matches(['+', '#', '!', '?'])
+            .trim_end_matches("e.p.")
+            .trim_end();
+        let legal_moves = gen_legal_moves(position);
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let kingside = san.len() == 3;
+
+            return legal_moves.into_iter().find(|halfmove| {
+                halfmove.flag == Some(HalfmoveFlag::Castle)
+                    && halfmove.is_kingside_castle() == kingside
+            });
+        }
+
+        // drops are written the same as in uci, with pawn drops sometimes as @e4
+        if let Some((piece, square)) = san.split_once('@') {
+            let piece = piece.chars().next().unwrap_or('P');
+
+            return legal_moves.into_iter().find(|halfmove| {
+                halfmove.drop_char() == Some(piece) && int_to_coord(halfmove.to) == square
+            });
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|&c| c != 'x' && c != '-').collect();
+
+        // promotion, written as e8=Q or e8Q
+        let mut promotion = None;
+        if let Some(&last) = chars.last() {
+            if "NBRQK".contains(last) && chars.len() > 2 {
+                promotion = Some(last);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+
+        let piece = match chars.first() {
+            Some(&c) if "NBRQK".contains(c) => {
+                chars.remove(0);
+                c
+            }
+            _ => 'P',
+        };
+
+        if chars.len() < 2 {
+            return None;
+        }
+
+        let to_file = chars[chars.len() - 2];
+        let to_rank = chars[chars.len() - 1];
+        let to = coord_to_int(&format!("{}{}", to_file, to_rank))?;
+        let disambiguation = &chars[..chars.len() - 2];
+
+        let mut matching = legal_moves.into_iter().filter(|halfmove| {
+            let from_matches = disambiguation.iter().all(|&c| {
+                if c.is_ascii_lowercase() {
+                    halfmove.from % 8 == c as u8 - b'a'
+                } else {
+                    c.to_digit(10) == Some((halfmove.from / 8) as u32 + 1)
+                }
+            });
+
+            let moved_piece = position.board[halfmove.from as usize];
+
+            halfmove.to == to
+                && halfmove.flag != Some(HalfmoveFlag::Castle)
+                && piece_to_char(moved_piece, false).to_ascii_uppercase() == piece
+                && halfmove.promotion_char() == promotion
+                && from_matches
+        });
+
+        let halfmove = matching.next();
+
+        // ambiguous moves are rejected
+        if matching.next().is_some() {
+            return None;
+        }
+
+        return halfmove;
+    }
+}
+
+impl fmt::Debug for PieceSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut all_string = String::from("All:");
+        let mut sorted_all: Vec<u8> = self.all.iter().cloned().collect();
+        sorted_all.sort_unstable();
+
+        for i in sorted_all {
+            all_string += " ";
+            all_string += &int_to_coord(i);
+        }
+
+        let mut white_string = String::from("White:");
+        let mut sorted_white: Vec<u8> = self.white.iter().cloned().collect();
+        sorted_white.sort_unstable();
+
+        for i in sorted_white {
+            white_string += " ";
+            white_string += &int_to_coord(i);
+        }
+
+        let mut black_string = String::from("Black:");
+        let mut sorted_black: Vec<u8> = self.black.iter().cloned().collect();
+        sorted_black.sort_unstable();
+
+        for i in sorted_black {
+            black_string += " ";
+            black_string += &int_to_coord(i);
+        }
+
+        return write!(f, "{}\n{}\n{}", all_string, white_string, black_string);
+    }
+}
+
+impl PieceSet {
+    fn remove_index(&mut self, index: u8, color: Color) {
+        self.all.remove(&index);
+
+        if color == Color::Black {
+            self.black.remove(&index);
+        } else {
+            self.white.remove(&index);
+        }
+    }
+
+    fn add_index(&mut self, index: u8, color: Color) {
+        self.all.insert(index);
+
+        if color == Color::Black {
+            self.black.insert(index);
+        } else {
+            self.white.insert(index);
+        }
+    }
+
+    fn add_index_or_color_swap(&mut self, index: u8, color: Color) {
+        self.all.insert(index);
+
+        if color == Color::Black {
+            self.black.insert(index);
+            self.white.remove(&index);
+        } else {
+            self.white.insert(index);
+            self.black.remove(&index);
+        }
+    }
+}
+
+impl PositionTree {
+    pub fn from_pos(position: Position) -> Self {
+        Self {
+            position,
+            nodes: vec![vec![PositionTreeNode::root_node()]],
+            depth: 0,
+            leaf_size: 0,
+        }
+    }
+
+    pub fn print_tree(&self) {
+        for i in 0..self.nodes.len() {
+            println!();
+            println!("Depth: {}", i);
+            for j in 0..self.nodes[i].len() {
+                self.nodes[i][j].print_node();
+                if j != self.nodes[i].len() - 1 {
+                    print!("| ");
+                }
+            }
+            println!();
+        }
+        println!();
+    }
+
+    fn gen_children(&mut self, depth: usize, index: usize) {
+        let mut position = self.position.clone();
+
+        let mut trace = vec![];
+        let mut cur_depth = depth;
+        let mut cur_index = index;
+        while cur_depth > 0 {
+            trace.push(cur_index);
+            cur_index = self.nodes[cur_depth][cur_index].parent;
+
+            cur_depth -= 1;
+        }
+        trace.reverse();
+
+        cur_depth = 1;
+        for item in trace {
+            execute_halfmove(&mut position, self.nodes[cur_depth][item].halfmove);
+            cur_depth += 1;
+        }
+
+        let moves = gen_possible(&mut position);
+
+        self.leaf_size += moves.len();
+
+        if !moves.is_empty() {
+            if self.nodes.len() <= depth + 1 {
+                self.nodes.push(vec![]);
+            }
+
+            let orig_len = self.nodes[depth + 1].len();
+            for halfmove in moves.iter() {
+                let child_node = PositionTreeNode {
+                    parent: index,
+                    halfmove: halfmove.to_owned(),
+                    children: None,
+                    score: None,
+                };
+                self.nodes[depth + 1].push(child_node);
+            }
+
+            let child_range = Some((orig_len, orig_len + moves.len() - 1));
+            self.nodes[depth][index].children = child_range;
+        }
+    }
+
+    fn restrict_root(&mut self, moves: &[HalfMove]) {
+        if moves.is_empty() {
+            return;
+        }
+
+        self.nodes.truncate(1);
+        self.nodes[0][0].children = Some((0, moves.len() - 1));
+        self.nodes.push(
+            moves
+                .iter()
+                .map(|&halfmove| PositionTreeNode {
+                    parent: 0,
+                    halfmove,
+                    children: None,
+                    score: None,
+                })
+                .collect(),
+        );
+        self.leaf_size += moves.len();
+    }
+
+    pub fn increase_depth(&mut self) -> usize {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+
+        for i in 0..self.nodes[self.depth].len() {
+            if self.nodes[self.depth][i].children.is_some() {
+                continue;
+            }
+
+            self.gen_children(self.depth, i);
+        }
+        self.depth += 1;
+
+        return self.nodes[self.depth].len();
+    }
+}
+
+impl PositionTreeNode {
+    fn root_node() -> Self {
+        Self {
+            parent: 0,
+            halfmove: HalfMove {
+                from: 63,
+                to: 63,
+                flag: None,
+                is_capture: false,
+            },
+            children: None,
+            score: None,
+        }
+    }
+
+    fn print_node(&self) {
+        if self.halfmove.to == 63 && self.halfmove.from == 63 {
+            print!("root");
+        } else {
+            print!("{}", self.halfmove.move_to_coords());
+        }
+    }
+}
+
+impl Position {
+    pub fn startpos() -> Self {
+        let mut position = Position {
+            board: [None; 64],
+            piece_set: PieceSet {
+                all: HashSet::new(),
+                white: HashSet::new(),
+                black: HashSet::new(),
+                white_king: 4,
+                black_king: 60,
+            },
+            move_next: Color::White,
+            castling_rights: CastlingRights {
+                black: ColorCastlingRights {
+                    kingside: Some(7),
+                    queenside: Some(0),
+                },
+                white: ColorCastlingRights {
+                    kingside: Some(7),
+                    queenside: Some(0),
+                },
+            },
+            en_passant_target: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            variant: Variant::Standard,
+            pockets: [[0; 5]; 2],
+            promoted: 0,
+            checks: [0; 2],
+        };
+
+        let back_rank: [fn(Color) -> Piece; 8] = [
+            Piece::Rook,
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Queen,
+            Piece::King,
+            Piece::Bishop,
+            Piece::Knight,
+            Piece::Rook,
+        ];
+
+        for (file, piece) in back_rank.iter().enumerate() {
+            position.board[file] = Some(piece(Color::White));
+            position.board[file + 8] = Some(Piece::Pawn(Color::White));
+            position.board[file + 48] = Some(Piece::Pawn(Color::Black));
+            position.board[file + 56] = Some(piece(Color::Black));
+
+            position.piece_set.add_index(file as u8, Color::White);
+            position.piece_set.add_index(file as u8 + 8, Color::White);
+            position.piece_set.add_index(file as u8 + 48, Color::Black);
+            position.piece_set.add_index(file as u8 + 56, Color::Black);
+        }
+
+        return position;
+    }
+
+    pub fn gen_hash(&self) -> u64 {
+        let mut hash: u64 = 0;
+
+        #[rustfmt::skip]
+        let psrn_table: [u64; 781] = [6050961064690644123, 15385182941806993281, 1474049585344358660, 6851573923483025534, 13899087919403525125, 8758650992845187116, 1831239503027593786, 13701660087018851169, 18335348291191493899, 4402234053541100678, 14757096522167036102, 13009140431848805653, 1292898825854068034, 4884307846020727494, 13857947210706460393, 1626896879833203751, 6038445616195308722, 6720134536466369422, 4497292822882533224, 12369361321546040904, 14712685727521284085, 1608341193440387084, 4094586736089739280, 2072304564850959527, 4091162237664628960, 15417717071469061328, 158710210446366970, 10118476861800698006, 16261210225467785938, 3509118041234889229, 6369150832245265647, 16079384263440389010, 11115231651891558388, 4646006308786422360, 18110725773482173731, 5657782342379456300, 11143381484293096337, 7487773842973491479, 6751517840915511657, 14929942954797253082, 1901957234508141725, 8921907195207315801, 17463714160121970869, 12245751322195944246, 10654386101703818407, 3931494334593277793, 17115885933089799525, 8502883217534375488, 745914388038295655, 1034741315093060365, 9433678509952610578, 1098536606267845662, 13213316387606432785, 3350954517876542623, 11207000871705408100, 10414442641064136232, 16749912713695375096, 16740481193746264268, 15559897978749864387, 10170635327641382168, 13139853202089369670, 14022649397309221013, 11247166396668734960, 14500993388554649383, 10234231535682188861, 16082651711303738385, 13240344989764749555, 15761415548747030129, 13200626097523845685, 10158384463413159211, 12082793007871671521, 7053088165737182306, 17910572772996987755, 634551525556320577, 8715072720248632882, 16645249778365519939, 17071269256303802149, 13000434989816980991, 3266080034350421129, 10387188012931609076, 12909971265520579520, 2232707469466001278, 12247075673661908260, 2073603714481317363, 901131989421222986, 17687777256174267121, 10628670673870316880, 10335258412280339222, 13252625444758210862, 13244768822050161111, 11902193789785886843, 4557300638221084616, 15723110200581411395, 2002686390970716135, 3679706203300853541, 8465679685848505392, 15629865532713611859, 2252635975746926934, 13176514338201280970, 18323437376244292447, 3052078822704129486, 14668753997257336776, 7484590864270466728, 9116309183190979995, 17775487882875822414, 773122596006458714, 17641850471318846102, 15107524460097819202, 7418208085589646254, 8561007928848469504, 15315171626020440806, 5431303807153869368, 18338711474901845704, 15830389477933775901, 943683194046629764, 12756255220308303970, 15818202731076003553, 17099604802677736889, 4050058657376309133, 17788592446092713641, 12125532480504028469, 7346811925904984991, 16743490842944433249, 12915895388335722275, 13798990182043546430, 10651826920167390121, 17969822964227303393, 4544407742359086458, 10681790818208387649, 14722634122293894088, 7546947474351786735, 3105849400590956960, 8113431633459910815, 16846295435437074212, 2884719721752435755, 11434748994118687031, 8592015634217955360, 12804867188244779916, 11198362185301661234, 11893160967421070986, 5713328646749870157, 3376500401699912934, 12396827523520345466, 16163415384865807273, 10631825877706701086, 18362438055956926458, 7091289048407828922, 2601597000512142188, 5934351981512336591, 18009871113071078878, 5067636467652884776, 13664982911664380293, 11250428350774470275, 15195462104258779713, 3761708893439855811, 5714505373559617613, 16070201332416855208, 7836116975836822007, 6610470649036680618, 18340614937879377979, 16747532071404513809, 13866875191998180171, 2046326353399532111, 14152787502496138315, 17862055635526226878, 3935530809429155555, 14407604056361705041, 17819032531253250211, 7012195161138792524, 843324294862535766, 11284107948253343080, 1749165026438999140, 17365438740212629834, 529057808325496683, 11364771066596107837, 11856258599114527383, 4316973369925240629, 5243288441161619140, 894022035255586177, 16853695020805006493, 10797222682704016790, 5858313985552783408, 10237723180844500384, 15304820458373535844, 17850530461622689681, 14894060435840074976, 14427026045903430902, 104617213228060690, 7640074872228573677, 7573980051921992697, 10305090662346373726, 18307325185753646832, 3253083594076551494, 12756449958142110556, 7986408859512743752, 4976782687715554697, 6758736852197655040, 1033181489679567150, 14155585553909016816, 4249394446353065408, 17942940693848032142, 3535312454936521939, 13154155077310235819, 6615990194370558678, 11838970440518365616, 17082754182448501336, 8146609427596499162, 2225872567139137754, 5812928537890751298, 7002225902229134612, 15122223306994340390, 8811643324484140341, 4240177335615464473, 1263622195699005784, 4937788903724975379, 8710761994859176931, 11579355844267439659, 14762877258348145194, 9340761068251229970, 2965544404013391364, 7474830457601152485, 17004451485192980313, 836167104639626113, 16192472709886055895, 5567772969564176462, 14711296339676478873, 9064257581222141987, 289450938860923833, 9437036928624368577, 17319187955177794104, 14705260853599714953, 2938102596797146997, 13053614869271975353, 7811262463056009475, 10471781887007966218, 6318402700082491738, 12859742873462550346, 13581778329009260002, 2191427603160772933, 2912587536796309376, 12190681911391377435, 2121662344245551616, 11940356828758808627, 8579633679480549070, 17748750722896289810, 9922686718031707817, 9890729722693482208, 1738413465528204104, 12106772477101032553, 10343326210733605168, 10521792142915609879, 9133206837523597081, 14228057140258989546, 16629749403701501368, 8057453397540486664, 14771587299335891728, 12542639368862350092, 17033018684229091182, 14299417385609581513, 299895395448337771, 18261141907208659512, 58435901761234140, 1029815525973352126, 16667980257426781041, 10364293774554972990, 14118057326965178932, 13217797211731137055, 7331073934442150546, 13516155712980895236, 3849197493611392794, 6311397283325561707, 5734118818395547438, 4867368830777807010, 9287369375107932908, 2926380459256882904, 18359136274642055492, 6157115134594143556, 15083371154181254693, 14506803485078401988, 10100223926074614734, 17009608990384185248, 13503764453345526380, 8209605655417046357, 10908528342113814552, 5270672473694595866, 5227971298844608744, 2079841133548231047, 9716184702400726114, 16198418592916683571, 3228342983974782177, 14635980218870688079, 13550371517618278327, 12669594339150634112, 11591207933534184271, 9564796019405425199, 2501218974170272794, 1327476418968706882, 3168866119130897463, 8425176289155694011, 4253645703623642439, 4012572788319560961, 14748287885898588380, 15913389759861721802, 9004133984019784852, 5915021421986852130, 5629928874286919288, 4221326977905064881, 2510275727066081252, 8126081520560038169, 5696364608254310546, 10956502371156347231, 3256132070556573989, 16347019016654603140, 5002654120378261107, 2093733417751210425, 18015440295385245672, 5699852561200492883, 10706954589778002309, 3296275905849577026, 2965778812108887194, 4418827907814509781, 11190035921018846823, 4212926398119039131, 2172920485357587036, 5417674759529146084, 12559822789700806847, 6420030248204950146, 11556884813285663168, 17078599768159079822, 9457541948057297374, 5294206209553005609, 10417300360929566980, 16196327365681227323, 6395469077661940900, 4708532786143680622, 10654194123371921563, 9651553495607851035, 15014301726845382732, 12035066491922951630, 12561246240444678516, 11978111492276933879, 990166153752483250, 17569815533005884963, 10194498563663234464, 10928768676372136124, 14796581717184468331, 9723175147088108129, 5810018124754208806, 15176267457803663891, 10020041885928112913, 18310674336842914861, 6397752648784716519, 17225786258546997877, 1789197968025353863, 10403293684791961098, 18144238680550519661, 4576865157808586296, 12574838769490753335, 7897053966609216911, 17669716723430272262, 16887123826806941351, 14567216391692586257, 14148853342514078053, 15543561537766863720, 13179732114149938262, 15548110259070525182, 8241184539042406975, 12886167617719501434, 2668442484504456534, 5528737578750256550, 8045724552511222249, 14233549524182091382, 14454773653496152899, 4803382709611342105, 14104305995325012156, 9233416359162608765, 10896765243687396087, 3888622613535254020, 5204363573751428905, 15550077342098247025, 14057215280926617785, 6429067651734432800, 12145175219210066021, 3871151583257216929, 14382153126479391325, 3503136348951471139, 17074632318198699960, 18277337232990677676, 2438793295253393259, 14188276604425453834, 17190081648445444068, 14901372647638775549, 10575384267303610410, 12463714381430437920, 5296503864634704402, 1085506994095541018, 17711931477255281454, 10029194223911757044, 10755199144959386844, 8671868823094321814, 5983006676130798566, 10792592475280434339, 722608211743985546, 14482132779275271146, 9415512828898525574, 16956058083295549808, 9209857238564496465, 12683605268868586743, 2955776406012433258, 16029235202218082952, 6579160785242750161, 504295306752149147, 9341624862273318372, 15375265198034115277, 6994898638369110070, 309797721354564726, 12429410516424851772, 5192024237253378865, 3912399787570959755, 12541234326966170226, 18441632327004496392, 7346203468976882923, 17593945557702212252, 15367442556011108555, 18217099153608305021, 10157165767144106959, 14018728927678812016, 18099686645005791370, 15136980239802015388, 7047521305623726125, 7575245649510417331, 14278619717007843644, 556011385191822492, 11381450268477688895, 14606319637689027024, 7080222843433955438, 5535489633773271511, 12090789406220893065, 14588818283718185151, 15370484225886308308, 12506301389557425466, 14865276370451418685, 8307888451349003948, 2861458479835086804, 5979069397180909905, 3140261739536988441, 5512756738929686408, 62084764907834261, 15807114778163996394, 2514213451484910157, 12101977943332277088, 5754338443349951926, 11202526598411612289, 1941284846376634320, 13676463015082195127, 13512152708144120784, 15967285827171943566, 1414500093148047241, 12815445217859919773, 11408657165942473469, 13896534001351748553, 12170732773640396882, 13528711356234590625, 13396280905236298091, 526652414431385131, 18204997071569618430, 4672075988794117180, 10712277614075303886, 10462100441247111006, 15579071806953301277, 15286269530449908638, 5479544935618236438, 7078561675539836269, 14897271087535510231, 6663607476483075550, 10975108262709842260, 5164218779845057244, 3026027211361889997, 10372550805396296371, 5511181984466209111, 4615344310383006121, 14765022018300614504, 7941633078349736364, 2336972229937914156, 15572100879945226778, 4252302396980455049, 12550177359955319593, 1459460872050639652, 17262569865062661057, 1539903875688234572, 17611947439799518453, 2703027075660991994, 11637679138689401581, 3064316072640710608, 1680909683069808723, 3607511184654591057, 4108173343691407894, 17379562958700858326, 302916588307766784, 3080575744190946064, 14618492782227969892, 7410866293301888883, 15107711256805849837, 4154183358968633778, 228080548283573131, 4117428293408729535, 20393270934047095, 6924832010164006882, 9829266407182768870, 1479756068945379597, 13132187458871599966, 8408723953761582692, 7925131402231319271, 4163854595303398243, 11230101227039602264, 16993193842701891622, 15444679853316663011, 3781919890769373844, 11182705188793031493, 5892311539960805112, 17158673965144059034, 16226450487359544767, 3857937074244810267, 10290970525402511515, 4090612527962514610, 15705494108227347854, 11713886306567047904, 11839618259637189525, 8015231900599896429, 14318494365807990907, 17066719705795494095, 1191101778471427856, 18366858155298147659, 9909682530008047655, 18103868884984506862, 18272414527621650028, 8396690449634257845, 541665888372703491, 8880466152303936336, 7116327037981094726, 14787688634394995663, 10394250631058185299, 10941901494326388747, 2555388952390999332, 3758236094703160891, 16576737194033338957, 14366906953111661451, 1903270876738280544, 10324021488998625612, 18353689077818956100, 8991522840099717154, 2421074737169331519, 9169793787044604812, 18260962835765091438, 4114111187649682384, 12816926656461667356, 17481819214090174809, 10131753959629909294, 12546401621311663568, 7179244263615447903, 3726159482382699804, 4915138607684722647, 17168907241619308384, 12339912791745187348, 1707583925986994553, 14011204057319936567, 10794690787627844528, 10695852063656574836, 3197783774491593781, 7298933884713059525, 6328633030694775205, 10766434409434719553, 10091956128572215514, 3455431069320366557, 392899140943852740, 4786988958218928946, 17290118779266618583, 9569754117035606215, 9608745232397807979, 5317990318280560256, 1174821456301900773, 9629429049860584332, 16050528676160605532, 8553649108826978033, 9401175273538018431, 16154633128515230798, 1905181735190354887, 14357904420278363879, 8896250678213174483, 4757364172887264470, 534375847936343030, 4168770809732413905, 5319475466728698669, 16027717470825227410, 16290862895133357951, 7585575570172258495, 7376450908955157397, 105693702558868570, 5867124974882586974, 10358598132073172602, 12687742061092614126, 10033659482067303133, 6046794178657639025, 5682077375508511604, 16085410403491061574, 2221968166217602761, 14054121017115688694, 9999403329710406488, 14173309310608438308, 2213865887714781468, 10095989436830741386, 6831589881469508820, 2210779362904169838, 17870372339043947522, 926838333319636069, 14155028843993162273, 3789607254882896617, 11944486143104258955, 7877247516668015598, 13403475833651114537, 6804136318190869572, 15941814127752619716, 2870163589488692723, 3380428415866679688, 17869614886487731048, 12478570456242254441, 15953546942867752514, 14008141154075371973, 3261688501716887849, 8115949271760655919, 4123026970301930621, 6389926672597484019, 1434651534731442627, 17520271334962967152, 16176207172243961455, 12191313098201911592, 11829326229021738049, 3441526450024664451, 14672768068246754822, 6091054180467864913, 17310674220118407423, 10133704405879849808, 5245870133028354084, 5699111173180951384, 424903395538216595, 7043252863064238904, 15030900898582281980, 6047080229948242541, 6673848437141978636, 7619119509346272531, 1176862265596481243, 1432562585694455670, 14207256064924225720, 17177014215747555751, 12404913789792397349, 17142808236991882077, 12206497933940300112, 17239638110422550477, 9541260587948853653, 5382239951718353022, 12461479961770469628, 5378179547293175807, 17788513785887631264, 2769065464121114364, 6698553467183667369, 12881128031963500700, 15757831429553553394, 13546128785342036562, 12785217889406033884, 5788314480727360675, 2793556930718848067, 2569518059303078779, 9235865686928466768, 8559980265714462943, 1268367044108754402, 10691882615180890276, 15531362923319305157, 3713025523581636531, 17821743846441991997, 4052839474685653212, 1964950709534779841, 3077048493964259228, 4680455791302938987, 14635728993302465994, 16761081135007356997, 14169913184783073434, 15297891832366291834, 2318207228039675382, 8504436602692556662, 8742349526436801363, 6126332799630915405, 5684354393344322870, 786183764801356732, 2343749936100637379, 720083360215038549, 5698685623915037082, 5915927667934393073, 9653509432946646798, 7079450852067684930, 2873528879681144878, 5558725876601302241, 7122421005083450743, 9720837126712108722, 7042772586139178077, 9659765980907602557, 617947098950154900, 3427189771032661006, 17611098782518188137, 11842008121454083047, 12090422274619625801, 13027146231092701682, 7150380630802500542, 76383145629883242, 18080576578711996702, 16356958352804286010, 14114746357020113352, 7921345840959732225, 17062772333595287544, 1260272922934697060, 5106096742451363382, 16075734719455612351, 10285794434717851630, 13399089060204000538, 8989588679388842206, 17101036744433399257, 1870648155382229838, 4497126874072672417, 3938922443378817162, 2927351814943280270, 3808821898427060335, 13872502583271862467, 5070605930349356387, 11972956721874186286, 8204290494894192656, 7230281892289845417, 18275285539786715214, 2404658561333529903, 16200640225189980110, 1810535742598390686, 749579642403451083, 9381688544530888032, 2833403535692394632, 4291075055163480910, 13897310516919681668, 18124882455222455711, 4399037140000442103, 16790244923650890342, 11248815251785723945, 8950265283055727281, 14064316622141232227, 3486167182041002958, 3686193929606109177, 2507146866769039965, 17198954785903697242, 17081810208716216052, 3983765481446896246, 7931770154753963032, 11893182668119123647, 12793958946298266810, 8401299987260453643, 2010613517693662606, 1665411773551417479, 11537634155796626245, 17933021902018037060, 15450537488547765217, 5339631745645945879, 12343722746092515604, 1624170935175840137, 6367934948056314691, 9093462352226564605, 5970002736843134976, 16103184750985353063, 15981753300871893582, 7557999814210827344, 5975213922167227474, 9964613871441776749, 9541798040899160189, 9138840133348875391, 1714696712793392765, 4519285853735943465, 442643889793963538];
+
+        for i in 0..64 {
+            match self.board[i] {
+                Some(Piece::Pawn(Color::White)) => {
+                    hash = hash.wrapping_add(psrn_table[i]);
+                }
+                Some(Piece::Pawn(Color::Black)) => {
+                    hash = hash.wrapping_add(psrn_table[i + 64]);
+                }
+                Some(Piece::Knight(Color::White)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 2)]);
+                }
+                Some(Piece::Knight(Color::Black)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 3)]);
+                }
+                Some(Piece::Bishop(Color::White)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 4)]);
+                }
+                Some(Piece::Bishop(Color::Black)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 5)]);
+                }
+                Some(Piece::Rook(Color::White)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 6)]);
+                }
+                Some(Piece::Rook(Color::Black)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 7)]);
+                }
+                Some(Piece::Queen(Color::White)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 8)]);
+                }
+                Some(Piece::Queen(Color::Black)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 9)]);
+                }
+                Some(Piece::King(Color::White)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 10)]);
+                }
+                Some(Piece::King(Color::Black)) => {
+                    hash = hash.wrapping_add(psrn_table[i + (64 * 11)]);
+                }
+                None => {}
+            }
+        }
+
+        if self.move_next == Color::Black {
+            hash = hash.wrapping_add(768);
+        }
+        if self.castling_rights.white.kingside.is_some() {
+            hash = hash.wrapping_add(769);
+        }
+        if self.castling_rights.white.queenside.is_some() {
+            hash = hash.wrapping_add(770);
+        }
+        if self.castling_rights.black.kingside.is_some() {
+            hash = hash.wrapping_add(771);
+        }
+        if self.castling_rights.black.queenside.is_some() {
+            hash = hash.wrapping_add(772);
+        }
+
+        if self.en_passant_target.is_some() {
+            hash = hash.wrapping_add(773 + (self.en_passant_target.unwrap() % 8) as u64);
+        }
+
+        // pieces in hand use the keys of pawns on their own back rank, which can't exist
+        for kind in 0..5 {
+            hash = hash.wrapping_add(psrn_table[kind].wrapping_mul(self.pockets[0][kind] as u64));
+            hash = hash.wrapping_add(
+                psrn_table[64 + 56 + kind].wrapping_mul(self.pockets[1][kind] as u64),
+            );
+        }
+        hash = hash.wrapping_add(psrn_table[5].wrapping_mul(self.checks[0] as u64));
+        hash = hash.wrapping_add(psrn_table[64 + 56 + 5].wrapping_mul(self.checks[1] as u64));
+
+        return hash;
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        let mut index: u8 = 64;
+        let mut blank_count: u8;
+
+        for i in 0..8 {
+            index -= 8;
+            blank_count = 0;
+
+            if self.board[index as usize].is_none() {
+                blank_count += 1;
+            } else {
+                if blank_count != 0 {
+                    fen += &format!("{}", blank_count);
+                    blank_count = 0;
+                }
+                fen += &format!("{}", piece_to_char(self.board[index as usize], false));
+                fen += self.promoted_marker(index);
+            }
+
+            for _ in 0..7 {
+                index += 1;
+                if self.board[index as usize].is_none() {
+                    blank_count += 1;
+                } else {
+                    if blank_count != 0 {
+                        fen += &format!("{}", blank_count);
+                        blank_count = 0;
+                    }
+                    fen += &format!("{}", piece_to_char(self.board[index as usize], false));
+                    fen += self.promoted_marker(index);
+                }
+            }
+
+            if blank_count != 0 {
+                fen += &format!("{}", blank_count);
+            }
+
+            if i != 7 {
+                fen += "/";
+            }
+            index -= 7;
+        }
+
+        if self.variant == Variant::Crazyhouse {
+            fen += &crazyhouse::pocket_string(self);
+        }
+
+        if self.move_next == Color::Black {
+            fen += " b";
+        } else {
+            fen += " w";
+        }
+
+        fen += " ";
+        fen += &self.castling_field(false);
+        fen += " ";
+
+        if self.en_passant_target.is_none() {
+            fen += "-";
+        } else {
+            fen += &int_to_coord(self.en_passant_target.unwrap()).to_string();
+        }
+
+        if self.variant == Variant::ThreeCheck {
+            fen += " ";
+            fen += &threecheck::checks_string(self);
+        }
+
+        fen += &format!(" {} {}", self.halfmove_clock, self.fullmove_number);
+
+        return fen;
+    }
+
+    // crazyhouse fens mark promoted pieces with a ~ after them
+    fn promoted_marker(&self, index: u8) -> &'static str {
+        if self.variant == Variant::Crazyhouse && self.promoted & (1 << index) != 0 {
+            return "~";
+        }
+
+        return "";
+    }
+
+    // shredder-fen always names the rook files, x-fen only when KQkq would be ambiguous,
+    // so it stays the same as a normal fen for standard chess
+    pub fn to_shredder_fen(&self) -> String {
+        let fen = self.to_fen();
+        let mut fields: Vec<&str> = fen.split(' ').collect();
+        let castling = self.castling_field(true);
+        fields[2] = &castling;
+
+        return fields.join(" ");
+    }
+
+    fn castling_field(&self, shredder: bool) -> String {
+        let mut field = String::new();
+
+        for color in [Color::White, Color::Black] {
+            let rights = self.castling_rights.color(color);
+
+            for (rook_file, kingside) in [(rights.kingside, true), (rights.queenside, false)] {
+                let Some(rook_file) = rook_file else {
+                    continue;
+                };
+
+                let letter = if shredder || !self.is_outer_rook(color, rook_file, kingside) {
+                    (b'a' + rook_file) as char
+                } else if kingside {
+                    'k'
+                } else {
+                    'q'
+                };
+
+                if color == Color::White {
+                    field.push(letter.to_ascii_uppercase());
+                } else {
+                    field.push(letter);
+                }
+            }
+        }
+
+        if field.is_empty() {
+            field.push('-');
+        }
+
+        return field;
+    }
+
+    // whether there is no other rook of the color further out on that side of the king
+    fn is_outer_rook(&self, color: Color, rook_file: u8, kingside: bool) -> bool {
+        let rank_start = if color == Color::White { 0 } else { 56 };
+        let outer_files = if kingside {
+            rook_file + 1..8
+        } else {
+            0..rook_file
+        };
+
+        return outer_files
+            .into_iter()
+            .all(|file| self.board[(rank_start + file) as usize] != Some(Piece::Rook(color)));
+    }
+}
+
+impl fmt::Debug for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut to_print = String::new();
+        to_print += "\n";
+
+        let mut index: usize = 72;
+
+        let mut column_num: u8 = 8;
+        let horiz_space = "   ";
+
+        for _i in 0..8 {
+            index -= 16;
+            to_print += &format!("{} {}", column_num, horiz_space);
+            column_num -= 1;
+            for _j in 0..8 {
+                let piece_char = piece_to_char(self.board[index], false);
+
+                to_print += &format!("{}{}", piece_char, horiz_space);
+                index += 1;
+            }
+            to_print += "\n";
+        }
+        to_print += &format!(
+            "\n  {}A{}B{}C{}D{}E{}F{}G{}H\n",
+            horiz_space,
+            horiz_space,
+            horiz_space,
+            horiz_space,
+            horiz_space,
+            horiz_space,
+            horiz_space,
+            horiz_space
+        );
+        return write!(f, "{}", to_print);
+    }
+}
+
+pub struct EngineOptions {
+    pub multi_pv: u8,
+    pub debug_indexes: bool,
+    pub debug_sets_display: bool,
+    pub debug_use_symbols: bool,
+    pub debug_use_san: bool,
+    pub syzygy_path: String,
+    pub syzygy_probe_limit: u8,
+    pub own_book: bool,
+    pub book_file: String,
+    pub book_depth: u16,
+    pub book_best_move: bool,
+    pub threads: usize,
+    pub hash_size: usize,
+    pub show_wdl: bool,
+    pub chess960: bool,
+    pub variant: Variant,
+}
+
+// everything the engine keeps between searches. a front end owns one and hands a
+// searcher to each search, so the search itself never needs the engine
+pub struct Engine {
+    pub position: Position,
+    pub options: EngineOptions,
+    pub tt: Arc<tt::TranspositionTable>,
+    pub repetition_map: HashMap<u64, u8>,
+    pub tablebase: Arc<syzygy::Tablebase>,
+    pub book: Option<Arc<polyglot::Book>>,
+    // stops the running search
+    pub stop: StopHandle,
+    pub ponder_hit: Arc<AtomicBool>,
+}
+
+// stops a search from another thread. the flag stays set until reset, so a search
+// started after a stop ends at once
+#[derive(Clone, Default)]
+pub struct StopHandle {
+    flag: Arc<AtomicBool>,
+}
+
+// a search ends at whichever limit comes first. without any it runs until stopped
+#[derive(Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub nodes: Option<usize>,
+    // ms
+    pub movetime: Option<u64>,
+    // wtime, btime, winc and binc in ms
+    pub clock: Option<[u64; 4]>,
+    pub moves_to_go: Option<u64>,
+    // only these root moves are searched, all of them when empty
+    pub search_moves: Vec<HalfMove>,
+    // look for a mate in this many moves first
+    pub mate: Option<usize>,
+    // ignore the clock
+    pub infinite: bool,
+    // searching the expected reply on the opponent's time. the clock only starts at a
+    // ponderhit, and the search doesn't end on its own before one or a stop
+    pub ponder: bool,
+}
+
+// what a search needs from the engine, taken once when it starts
+#[derive(Clone)]
+pub struct Searcher {
+    tt: Arc<tt::TranspositionTable>,
+    tablebase: Arc<syzygy::Tablebase>,
+    probe_limit: usize,
+    repetition_map: HashMap<u64, u8>,
+    stop: StopHandle,
+    ponder_hit: Arc<AtomicBool>,
+    threads: usize,
+}
+
+// everything a search thread reads, with the limits of this search
+#[derive(Clone)]
+struct SearchContext<'a> {
+    tt: Arc<tt::TranspositionTable>,
+    tablebase: Arc<syzygy::Tablebase>,
+    probe_limit: usize,
+    repetition_map: HashMap<u64, u8>,
+    // set by the stop command
+    stop: Arc<AtomicBool>,
+    // set by the main thread when it is done, so the helpers finish with it
+    finished: Arc<AtomicBool>,
+    // the clock is only set once a ponder search gets its ponderhit
+    time_stop: Cell<Option<Instant>>,
+    time_budget: Option<Duration>,
+    // set by the ponderhit command, watched while pondering
+    ponder_hit: Arc<AtomicBool>,
+    pondering: Cell<bool>,
+    node_stop: Option<usize>,
+    // nodes visited since the search started, and whether a limit was hit at the last check
+    visited: Cell<usize>,
+    limit_reached: Cell<bool>,
+    // deepest ply reached, quiescence included
+    seldepth: Cell<usize>,
+    start_time: Instant,
+    // only some root moves are searched, so the root score isn't the position's
+    restricted_root: bool,
+    // only the main thread reports, helpers have no sink
+    sink: Option<&'a dyn info::InfoSink>,
+}
+
+pub struct SearchResult {
+    pub score: i32,
+    pub moves: Vec<HalfMove>,
+    pub depth: usize,
+    pub nodes: usize,
+    // elapsed ms and best move after each finished iteration
+    pub iterations: Vec<(u128, HalfMove)>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            position: Position::startpos(),
+            options: EngineOptions {
+                multi_pv: 1,
+                debug_indexes: false,
+                debug_sets_display: false,
+                debug_use_symbols: false,
+                debug_use_san: false,
+                syzygy_path: String::from("<empty>"),
+                syzygy_probe_limit: 7,
+                own_book: false,
+                book_file: String::from("<empty>"),
+                book_depth: 20,
+                book_best_move: false,
+                threads: 1,
+                hash_size: 16,
+                show_wdl: false,
+                chess960: false,
+                variant: Variant::Standard,
+            },
+            tt: Arc::new(tt::TranspositionTable::new(16)),
+            repetition_map: HashMap::new(),
+            tablebase: Arc::new(syzygy::Tablebase::empty()),
+            book: None,
+            stop: StopHandle::default(),
+            ponder_hit: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // starts a new game from position, forgetting the repetitions of the old one
+    pub fn set_position(&mut self, position: Position) {
+        self.repetition_map = HashMap::new();
+        self.repetition_map.insert(position.gen_hash(), 1);
+        self.position = position;
+    }
+
+    pub fn play(&mut self, halfmove: HalfMove) {
+        execute_halfmove(&mut self.position, halfmove);
+        *self
+            .repetition_map
+            .entry(self.position.gen_hash())
+            .or_insert(0) += 1;
+    }
+
+    pub fn searcher(&self) -> Searcher {
+        return Searcher {
+            tt: Arc::clone(&self.tt),
+            tablebase: Arc::clone(&self.tablebase),
+            probe_limit: self.options.syzygy_probe_limit as usize,
+            repetition_map: self.repetition_map.clone(),
+            stop: self.stop.clone(),
+            ponder_hit: Arc::clone(&self.ponder_hit),
+            threads: self.options.threads,
+        };
+    }
+
+    pub fn book_move(&self, position: &Position) -> Option<HalfMove> {
+        // polyglot books only cover standard chess
+        if !self.options.own_book
+            || position.fullmove_number > self.options.book_depth
+            || position.variant != Variant::Standard
+        {
+            return None;
+        }
+
+        return self
+            .book
+            .as_ref()?
+            .pick_move(position, self.options.book_best_move);
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        return self.flag.load(Ordering::Relaxed);
+    }
+}
+
+impl SearchLimits {
+    // nothing but a stop ends the search. a mate search ends on its own, once the mate is
+    // proven or refuted
+    pub fn is_unbounded(&self) -> bool {
+        return self.infinite || (self.mate.is_none() && self.has_no_limits());
+    }
+
+    fn has_no_limits(&self) -> bool {
+        return self.depth.is_none()
+            && self.nodes.is_none()
+            && self.movetime.is_none()
+            && self.clock.is_none();
+    }
+
+    // how long this search may take once its clock runs, none if it has no time limit
+    fn time_budget(&self, color: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+
+        if let Some(movetime) = self.movetime {
+            return Some(Duration::from_millis(movetime));
+        }
+
+        let times = self.clock?;
+        let (time, increment) = if color == Color::White {
+            (times[0], times[2])
+        } else {
+            (times[1], times[3])
+        };
+        let moves_to_go = self.moves_to_go.unwrap_or(30);
+
+        // spread the clock over the remaining moves, keeping a margin for move overhead
+        let budget = (time / moves_to_go.max(1) + increment * 3 / 4).min(time.saturating_sub(50));
+
+        return Some(Duration::from_millis(budget.max(1)));
+    }
+}
+
+// a go without any limits searches this many nodes
+pub const DEFAULT_NODES: usize = 500000;
+
+// limits that need the clock are only checked once every this many nodes
+const LIMIT_CHECK_INTERVAL: usize = 1024;
+
+const CURRMOVE_DELAY: Duration = Duration::from_millis(1000);
+
+// a pv carried on from the hash table stops here, or at a repetition
+const MAX_PV_LENGTH: usize = 64;
+
+// once the score has settled, each depth is first searched only this far around the
+// last one. the shallow depths still swing too much for the window to pay off
+const ASPIRATION_DEPTH: usize = 4;
+const ASPIRATION_WINDOW: i32 = 50;
+const FULL_WINDOW: (i32, i32) = (i32::MIN + 1, i32::MAX);
+
+impl<'a> SearchContext<'a> {
+    fn new(
+        searcher: &Searcher,
+        time_budget: Option<Duration>,
+        pondering: bool,
+        node_stop: Option<usize>,
+        sink: &'a dyn info::InfoSink,
+    ) -> Self {
+        let time_stop = if pondering {
+            None
+        } else {
+            time_budget.map(|budget| Instant::now() + budget)
+        };
+
+        Self {
+            tt: Arc::clone(&searcher.tt),
+            tablebase: Arc::clone(&searcher.tablebase),
+            probe_limit: searcher.probe_limit,
+            repetition_map: searcher.repetition_map.clone(),
+            stop: Arc::clone(&searcher.stop.flag),
+            finished: Arc::new(AtomicBool::new(false)),
+            time_stop: Cell::new(time_stop),
+            time_budget,
+            ponder_hit: Arc::clone(&searcher.ponder_hit),
+            pondering: Cell::new(pondering),
+            node_stop,
+            visited: Cell::new(0),
+            limit_reached: Cell::new(false),
+            seldepth: Cell::new(0),
+            start_time: Instant::now(),
+            restricted_root: false,
+            sink: Some(sink),
+        }
+    }
+
+    // helpers have no limits of their own, they run until the main thread finishes
+    fn helper(&self) -> SearchContext<'static> {
+        return SearchContext {
+            tt: Arc::clone(&self.tt),
+            tablebase: Arc::clone(&self.tablebase),
+            probe_limit: self.probe_limit,
+            repetition_map: self.repetition_map.clone(),
+            stop: Arc::clone(&self.stop),
+            finished: Arc::clone(&self.finished),
+            time_stop: Cell::new(None),
+            time_budget: None,
+            ponder_hit: Arc::clone(&self.ponder_hit),
+            pondering: Cell::new(false),
+            node_stop: None,
+            visited: Cell::new(0),
+            limit_reached: Cell::new(false),
+            seldepth: Cell::new(0),
+            start_time: self.start_time,
+            restricted_root: self.restricted_root,
+            sink: None,
+        };
+    }
+
+    fn should_stop(&self, nodes: usize) -> bool {
+        if self.stop.load(Ordering::Relaxed) || self.finished.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        let visited = self.visited.get() + 1;
+        self.visited.set(visited);
+
+        if visited % LIMIT_CHECK_INTERVAL == 0 {
+            self.limit_reached
+                .set(self.out_of_time() || self.node_stop.is_some_and(|limit| limit < nodes));
+        }
+
+        return self.limit_reached.get();
+    }
+
+    // a ponderhit turns the ponder search into a normal one, with its clock starting now
+    fn is_pondering(&self) -> bool {
+        if self.pondering.get() && self.ponder_hit.load(Ordering::Relaxed) {
+            self.pondering.set(false);
+            self.time_stop
+                .set(self.time_budget.map(|budget| Instant::now() + budget));
+        }
+
+        return self.pondering.get();
+    }
+
+    // whether should_stop has cut the search short, without counting a node
+    fn is_interrupted(&self) -> bool {
+        return self.limit_reached.get()
+            || self.stop.load(Ordering::Relaxed)
+            || self.finished.load(Ordering::Relaxed);
+    }
+
+    fn out_of_time(&self) -> bool {
+        return !self.is_pondering()
+            && self
+                .time_stop
+                .get()
+                .is_some_and(|time| time <= Instant::now());
+    }
+
+    // uci asks for currmove only once a search has been running for a while
+    fn report_currmove(&self, halfmove: HalfMove, number: usize) {
+        if let Some(sink) = self.sink {
+            if self.start_time.elapsed() >= CURRMOVE_DELAY {
+                sink.currmove(halfmove, number);
+            }
+        }
+    }
+
+    // the search's line as far as it is legal. a hash hit returns just its best move, so
+    // the line goes on with the best moves stored for the positions after it
+    fn pv(&self, moves: &[HalfMove], position: &Position) -> Vec<HalfMove> {
+        let mut pv = legal_pv(moves, position);
+
+        let mut end = position.clone();
+        for &halfmove in pv.iter() {
+            execute_halfmove(&mut end, halfmove);
+        }
+
+        let mut seen = HashSet::new();
+        while pv.len() < MAX_PV_LENGTH && seen.insert(end.gen_hash()) {
+            let Some(halfmove) = self.tt.best_move(end.gen_hash()) else {
+                break;
+            };
+            if !gen_legal_moves(&end).contains(&halfmove) {
+                break;
+            }
+
+            execute_halfmove(&mut end, halfmove);
+            pv.push(halfmove);
+        }
+
+        return pv;
+    }
+
+    fn report(
+        &self,
+        position: &Position,
+        depth: usize,
+        nodes: usize,
+        score: i32,
+        bound: tt::Bound,
+        moves: &[HalfMove],
+    ) {
+        let Some(sink) = self.sink else {
+            return;
+        };
+
+        let pv = self.pv(moves, position);
+        let mate_plies = mate_plies(position, &pv, score);
+
+        sink.info(&info::SearchInfo {
+            depth,
+            seldepth: self.seldepth.get().max(depth),
+            time: self.start_time.elapsed().as_millis(),
+            nodes,
+            hashfull: self.tt.hashfull(),
+            tbhits: self.tablebase.hits(),
+            score: info::Score::from_search(score, mate_plies, position.move_next),
+            bound: info::Score::bound_from_search(bound, position.move_next),
+            pv,
+        });
+    }
+}
+
+// how many plies away the mate behind a mate score is. mate scores don't count the
+// distance, so this plays out the pv. a pv cut short ends before the mate, so give the
+// nearest one past its end. the winner makes the last move, an odd ply for the side to
+// move
+pub(crate) fn mate_plies(position: &Position, pv: &[HalfMove], score: i32) -> usize {
+    let mut end = position.clone();
+    for &halfmove in pv.iter() {
+        execute_halfmove(&mut end, halfmove);
+    }
+
+    let mut mate_plies = pv.len();
+    if !gen_legal_moves(&end).is_empty() {
+        let mover_wins = (score > 0) == (position.move_next == Color::White);
+
+        mate_plies += 1;
+        if (mate_plies % 2 == 1) != mover_wins {
+            mate_plies += 1;
+        }
+    }
+
+    return mate_plies;
+}
+
+// the most squares one move can change: an atomic capture empties the square the
+// capturer left, the one it captured on and the eight around that
+const MAX_CHANGED_SQUARES: usize = 10;
+
+// what a move changed, for undo_halfmove. the search itself is copy-make and clones the
+// position before each move instead, which allocates the three piece sets every time
+pub struct Undo {
+    // the squares the move changed, with what stood on them before
+    changed: [(u8, Option<Piece>); MAX_CHANGED_SQUARES],
+    changed_count: usize,
+    move_next: Color,
+    castling_rights: CastlingRights,
+    en_passant_target: Option<u8>,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    kings: [u8; 2],
+    pockets: [[u8; 5]; 2],
+    promoted: u64,
+    checks: [u8; 2],
+}
+
+pub fn execute_halfmove(position: &mut Position, to_exec: HalfMove) {
+    position.variant.rules().execute_halfmove(position, to_exec);
+}
+
+// make/unmake for callers that keep a single position. the hash isn't stored in the
+// position, so there is none to restore
+pub fn do_halfmove(position: &mut Position, to_exec: HalfMove) -> Undo {
+    let board = position.board;
+    let mut undo = Undo {
+        changed: [(0, None); MAX_CHANGED_SQUARES],
+        changed_count: 0,
+        move_next: position.move_next,
+        castling_rights: position.castling_rights.clone(),
+        en_passant_target: position.en_passant_target,
+        halfmove_clock: position.halfmove_clock,
+        fullmove_number: position.fullmove_number,
+        kings: [position.piece_set.white_king, position.piece_set.black_king],
+        pockets: position.pockets,
+        promoted: position.promoted,
+        checks: position.checks,
+    };
+
+    execute_halfmove(position, to_exec);
+
+    for (square, &before) in board.iter().enumerate() {
+        if position.board[square] != before {
+            undo.changed[undo.changed_count] = (square as u8, before);
+            undo.changed_count += 1;
+        }
+    }
+
+    return undo;
+}
+
+pub fn undo_halfmove(position: &mut Position, undo: Undo) {
+    for &(square, piece) in undo.changed[..undo.changed_count].iter() {
+        if let Some(current) = position.board[square as usize] {
+            position.piece_set.remove_index(square, current.get_color());
+        }
+        if let Some(piece) = piece {
+            position.piece_set.add_index(square, piece.get_color());
+        }
+        position.board[square as usize] = piece;
+    }
+
+    position.move_next = undo.move_next;
+    position.castling_rights = undo.castling_rights;
+    position.en_passant_target = undo.en_passant_target;
+    position.halfmove_clock = undo.halfmove_clock;
+    position.fullmove_number = undo.fullmove_number;
+    [position.piece_set.white_king, position.piece_set.black_king] = undo.kings;
+    position.pockets = undo.pockets;
+    position.promoted = undo.promoted;
+    position.checks = undo.checks;
+}
+
+// a move by the standard rules, which the variants build on
+pub fn make_halfmove(position: &mut Position, to_exec: HalfMove) {
+    // no legality checks, assumes that to_exec is legal
+
+    if to_exec.from == 0 && to_exec.to == 0 && to_exec.flag.is_none() {
+        return;
+    }
+
+    position.halfmove_clock += 1;
+
+    let color = match position.board[to_exec.from as usize] {
+        Some(piece) => piece.get_color(),
+        // drops come from the pocket of the side to move
+        None => position.move_next,
+    };
+
+    let piece = match to_exec.flag {
+        Some(HalfmoveFlag::KnightPromotion) | Some(HalfmoveFlag::KnightDrop) => {
+            Piece::Knight(color)
+        }
+        Some(HalfmoveFlag::BishopPromotion) | Some(HalfmoveFlag::BishopDrop) => {
+            Piece::Bishop(color)
+        }
+        Some(HalfmoveFlag::RookPromotion) | Some(HalfmoveFlag::RookDrop) => Piece::Rook(color),
+        Some(HalfmoveFlag::QueenPromotion) | Some(HalfmoveFlag::QueenDrop) => Piece::Queen(color),
+        Some(HalfmoveFlag::KingPromotion) => Piece::King(color),
+        Some(HalfmoveFlag::PawnDrop) => Piece::Pawn(color),
+        _ => position.board[to_exec.from as usize].unwrap(),
+    };
+
+    if to_exec.flag != Some(HalfmoveFlag::Castle) {
+        if position.board[to_exec.to as usize].is_some()
+            || position.board[to_exec.from as usize] == Some(Piece::Pawn(position.move_next))
+        {
+            position.halfmove_clock = 0;
+        }
+
+        // moving or capturing a rook on its starting square removes that castling right
+        position.castling_rights.remove_rook_square(to_exec.from);
+        position.castling_rights.remove_rook_square(to_exec.to);
+
+        position.board[to_exec.to as usize] = Some(piece);
+        position
+            .piece_set
+            .add_index_or_color_swap(to_exec.to, color);
+
+        if piece == Piece::King(color) {
+            position.castling_rights.color_mut(color).clear();
+
+            if color == Color::White {
+                position.piece_set.white_king = to_exec.to;
+            } else {
+                position.piece_set.black_king = to_exec.to;
+            }
+        }
+
+        // a drop starts on the square it lands on
+        if to_exec.from != to_exec.to {
+            position.board[to_exec.from as usize] = None;
+            position.piece_set.remove_index(to_exec.from, color);
+        }
+    } else {
+        let king_square = to_exec.castle_king_square();
+        let rook_square = to_exec.castle_rook_square();
+
+        position.board[to_exec.to as usize] = None;
+        position.piece_set.remove_index(to_exec.to, color);
+
+        position.board[king_square as usize] = Some(Piece::King(color));
+        position.piece_set.add_index(king_square, color);
+        position.board[rook_square as usize] = Some(Piece::Rook(color));
+        position.piece_set.add_index(rook_square, color);
+
+        // in chess960 the king may not move at all, or the rook may land where it stood
+        if to_exec.from != king_square && to_exec.from != rook_square {
+            position.board[to_exec.from as usize] = None;
+            position.piece_set.remove_index(to_exec.from, color);
+        }
+
+        if color == Color::White {
+            position.piece_set.white_king = king_square;
+        } else {
+            position.piece_set.black_king = king_square;
+        }
+
+        position.castling_rights.color_mut(color).clear();
+    }
+
+    if to_exec.flag == Some(HalfmoveFlag::EnPassant) {
+        let mut target = position.en_passant_target.unwrap();
+
+        if (target / 8) == 5 {
+            target -= 8;
+        } else {
+            target += 8;
+        }
+
+        position.board[target as usize] = None;
+        position.piece_set.remove_index(target, color.opposite());
+    }
+
+    if to_exec.flag == Some(HalfmoveFlag::DoublePawnMove) {
+        let middle_space: u8 = if to_exec.from > to_exec.to {
+            to_exec.from - 8
+        } else {
+            to_exec.from + 8
+        };
+
+        position.en_passant_target = Some(middle_space);
+    } else {
+        position.en_passant_target = None;
+    }
+
+    if position.move_next == Color::Black {
+        position.fullmove_number += 1;
+        position.move_next = Color::White;
+    } else {
+        position.move_next = Color::Black;
+    }
+}
+
+pub fn string_to_halfmove(position: &Position, move_string: &str) -> Option<HalfMove> {
+    // crazyhouse drops, written as P@e4
+    if let Some((piece, square)) = move_string.split_once('@') {
+        let flag = match piece {
+            "P" => HalfmoveFlag::PawnDrop,
+            "N" => HalfmoveFlag::KnightDrop,
+            "B" => HalfmoveFlag::BishopDrop,
+            "R" => HalfmoveFlag::RookDrop,
+            "Q" => HalfmoveFlag::QueenDrop,
+            _ => return None,
+        };
+        let square = coord_to_int(square)?;
+
+        return Some(HalfMove {
+            from: square,
+            to: square,
+            flag: Some(flag),
+            is_capture: false,
+        });
+    }
+
+    let coord1_str: String = move_string.chars().take(2).collect();
+    let coord1 = coord_to_int(&coord1_str)?;
+
+    let coord2_str: String = move_string.chars().skip(2).take(2).collect();
+    let mut coord2 = coord_to_int(&coord2_str)?;
+
+    let board = &position.board;
+
+    let mut flag = None;
+    if board[coord1 as usize] == Some(Piece::Pawn(position.move_next))
+        && position.en_passant_target == Some(coord2)
+    {
+        flag = Some(HalfmoveFlag::EnPassant);
+    } else if board[coord1 as usize] == Some(Piece::King(position.move_next))
+        && coord1 / 8 == coord2 / 8
+    {
+        let rights = position.castling_rights.color(position.move_next);
+        let rank_start = coord1 - coord1 % 8;
+
+        for (rook_file, king_file) in [(rights.kingside, 6), (rights.queenside, 2)] {
+            let Some(rook_file) = rook_file else {
+                continue;
+            };
+            let rook_square = rank_start + rook_file;
+
+            // king takes rook, or the king's destination as long as that can't be a
+            // normal king move
+            if coord2 == rook_square
+                || (coord2 == rank_start + king_file && coord1.abs_diff(coord2) > 1)
+            {
+                coord2 = rook_square;
+                flag = Some(HalfmoveFlag::Castle);
+                break;
+            }
+        }
+    }
+
+    if move_string.len() > 4 {
+        flag = match move_string.chars().nth(4) {
+            Some('n') => Some(HalfmoveFlag::KnightPromotion),
+            Some('b') => Some(HalfmoveFlag::BishopPromotion),
+            Some('r') => Some(HalfmoveFlag::RookPromotion),
+            Some('q') => Some(HalfmoveFlag::QueenPromotion),
+            Some('k') => Some(HalfmoveFlag::KingPromotion),
+            _ => None,
+        };
+    }
+
+    return Some(HalfMove {
+        from: coord1,
+        to: coord2,
+        flag,
+        is_capture: false,
+    });
+}
+
+// a legal move in coordinate notation or san. anything else, malformed or not, is none
+pub fn parse_legal_move(
+    position: &Position,
+    move_string: &str,
+    chess960: bool,
+) -> Option<HalfMove> {
+    let legal_moves = gen_legal_moves(position);
+
+    return legal_moves
+        .iter()
+        .copied()
+        .find(|halfmove| halfmove.move_to_uci(chess960) == move_string)
+        .or_else(|| {
+            HalfMove::from_san(position, move_string)
+                .filter(|halfmove| legal_moves.contains(halfmove))
+        });
+}
+
+// a square such as e4, or none for anything else
+pub fn coord_to_int(coord: &str) -> Option<u8> {
+    let mut chars = coord.chars();
+    let (Some(file), Some(rank), None) = (chars.next(), chars.next(), chars.next()) else {
+        return None;
+    };
+
+    if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+
+    return Some((rank as u8 - b'1') * 8 + (file as u8 - b'a'));
+}
+
+pub fn int_to_coord(num: u8) -> String {
+    let file = (num % 8) + b'a';
+
+    let rank = (num / 8 + 1).to_string();
+
+    let coord = (file as char).to_string() + &rank;
+
+    return coord;
+}
+
+pub fn piece_to_char(piece: Option<Piece>, use_symbols: bool) -> char {
+    if use_symbols {
+        match piece {
+            Some(Piece::Pawn(Color::White)) => return '♙',
+            Some(Piece::Knight(Color::White)) => return '♘',
+            Some(Piece::Bishop(Color::White)) => return '♗',
+            Some(Piece::Rook(Color::White)) => return '♖',
+            Some(Piece::Queen(Color::White)) => return '♕',
+            Some(Piece::King(Color::White)) => return '♔',
+            Some(Piece::Pawn(Color::Black)) => return '♟',
+            Some(Piece::Knight(Color::Black)) => return '♞',
+            Some(Piece::Bishop(Color::Black)) => return '♝',
+            Some(Piece::Rook(Color::Black)) => return '♜',
+            Some(Piece::Queen(Color::Black)) => return '♛',
+            Some(Piece::King(Color::Black)) => return '♚',
+            _ => {}
+        }
+        return '⚊';
+    } else {
+        match piece {
+            Some(Piece::Pawn(Color::White)) => return 'P',
+            Some(Piece::Knight(Color::White)) => return 'N',
+            Some(Piece::Bishop(Color::White)) => return 'B',
+            Some(Piece::Rook(Color::White)) => return 'R',
+            Some(Piece::Queen(Color::White)) => return 'Q',
+            Some(Piece::King(Color::White)) => return 'K',
+            Some(Piece::Pawn(Color::Black)) => return 'p',
+            Some(Piece::Knight(Color::Black)) => return 'n',
+            Some(Piece::Bishop(Color::Black)) => return 'b',
+            Some(Piece::Rook(Color::Black)) => return 'r',
+            Some(Piece::Queen(Color::Black)) => return 'q',
+            Some(Piece::King(Color::Black)) => return 'k',
+            _ => {}
+        }
+        return '-';
+    }
+}
+
+impl Searcher {
+    pub fn stop_handle(&self) -> StopHandle {
+        return self.stop.clone();
+    }
+
+    // iterative deepening until one of the limits or a stop. every finished iteration
+    // and the final result go to sink, followed by the best move
+    pub fn search(
+        &self,
+        position: Position,
+        limits: &SearchLimits,
+        sink: &dyn info::InfoSink,
+    ) -> SearchResult {
+        // checkmate, stalemate or a game a variant rule ended, so there is no move to give
+        if gen_legal_moves(&position).is_empty() {
+            let rules = position.variant.rules();
+            let score = rules
+                .game_result(&position)
+                .or_else(|| rules.no_moves_result(&position))
+                .unwrap_or(if is_in_check(&position) {
+                    rules::win_for(position.move_next.opposite())
+                } else {
+                    0
+                });
+
+            SearchContext::new(self, None, false, None, sink).report(
+                &position,
+                0,
+                0,
+                score,
+                tt::Bound::Exact,
+                &[],
+            );
+            sink.bestmove(None, None);
+
+            return SearchResult {
+                score,
+                moves: vec![],
+                depth: 0,
+                nodes: 0,
+                iterations: vec![],
+            };
+        }
+
+        let mut node_stop = limits.nodes;
+        let mut depth_stop = limits.depth;
+        let mut time_budget = limits.time_budget(position.move_next);
+
+        // with a time or node limit the mate search gets half of it, and the normal search
+        // what is left when no mate was found. without one the mate search runs until the
+        // mate is proven or refuted
+        if let Some(mate_in) = limits.mate {
+            let bounded = time_budget.is_some() || node_stop.is_some();
+            let mate_start = Instant::now();
+            let mate_limits = mate::MateLimits {
+                time_budget: time_budget.map(|budget| budget / 2),
+                node_stop: node_stop.map(|nodes| nodes / 2),
+                ponder: limits.ponder,
+            };
+
+            if let Some(result) = mate::go_mate(
+                &position,
+                mate_in,
+                &limits.search_moves,
+                &mate_limits,
+                self,
+                sink,
+            ) {
+                return result;
+            }
+
+            // a ponder search's clock starts at the ponderhit, which may have come during
+            // the mate search
+            time_budget = time_budget.map(|budget| {
+                if limits.ponder {
+                    budget - budget / 2
+                } else {
+                    budget.saturating_sub(mate_start.elapsed())
+                }
+            });
+            node_stop = node_stop.map(|nodes| nodes - nodes / 2);
+
+            // and then picks a move the way a bare go does
+            if !bounded && !limits.infinite && limits.has_no_limits() {
+                node_stop = Some(DEFAULT_NODES);
+            }
+        }
+
+        let mut tree = PositionTree::from_pos(position);
+        let mut moves;
+        let mut score;
+        let mut depth = 0;
+        let mut bound = tt::Bound::Exact;
+        let mut window = FULL_WINDOW;
+        let mut prev_score = 0;
+        let mut prev_moves = vec![];
+        let mut iterations = vec![];
+
+        let start_time = Instant::now();
+
+        let mut context = SearchContext::new(self, time_budget, limits.ponder, node_stop, sink);
+        let tablebase = Arc::clone(&context.tablebase);
+        tablebase.reset_hits();
+
+        // only search the requested moves, and of those the ones that keep the tablebase result
+        let mut root_moves = limits.search_moves.clone();
+        if tablebase.can_probe(&tree.position, context.probe_limit) {
+            if let Some(tablebase_moves) = tablebase.filter_root_moves(&tree.position) {
+                if root_moves.is_empty() {
+                    root_moves = tablebase_moves;
+                } else if root_moves.iter().any(|m| tablebase_moves.contains(m)) {
+                    root_moves.retain(|m| tablebase_moves.contains(m));
+                }
+            }
+        }
+        tree.restrict_root(&root_moves);
+        context.restricted_root = !root_moves.is_empty();
+
+        if let Some(ref mut depth) = depth_stop {
+            if *depth <= 1 {
+                *depth += 1;
+            }
+        }
+
+        // lazy smp: helpers search the same root on their own trees and only share
+        // results through the transposition table
+        let helper_nodes = Arc::new(AtomicUsize::new(0));
+        let helpers: Vec<_> = (1..self.threads)
+            .map(|thread_index| {
+                let tree = tree.clone();
+                let context = context.helper();
+                let helper_nodes = Arc::clone(&helper_nodes);
+
+                thread::spawn(move || helper_search(tree, thread_index, &context, &helper_nodes))
+            })
+            .collect();
+
+        loop {
+            let is_maximizing = tree.position.move_next == Color::White;
+            let start_pos = tree.position.clone();
+
+            let (alpha, beta) = window;
+
+            (score, moves) = minimax(
+                &mut tree,
+                start_pos,
+                0,
+                0,
+                is_maximizing,
+                alpha,
+                beta,
+                depth,
+                &context,
+            );
+
+            // an iteration cut short has only searched some of the root moves
+            let interrupted = context.is_interrupted();
+
+            // outside the window the score only says which side of it the real one is on.
+            // report that and search the same depth again with the full window
+            if window != FULL_WINDOW && !interrupted && (score <= alpha || score >= beta) {
+                let fail_bound = if score <= alpha {
+                    tt::Bound::Upper
+                } else {
+                    tt::Bound::Lower
+                };
+                context.report(
+                    &tree.position,
+                    depth + 1,
+                    tree.leaf_size,
+                    score,
+                    fail_bound,
+                    &moves,
+                );
+
+                window = FULL_WINDOW;
+                continue;
+            }
+
+            depth += 1;
+            tree.depth += 1;
+
+            if score.abs() >= 30000
+                || (node_stop.is_some() && node_stop.unwrap() <= tree.leaf_size)
+                || context.out_of_time()
+                || (depth_stop.is_some() && depth_stop.unwrap() <= depth)
+                || context.stop.load(Ordering::Relaxed)
+            {
+                if score.abs() == i32::MAX || moves.is_empty() {
+                    score = prev_score;
+                    moves = prev_moves.clone();
+                } else if interrupted {
+                    // the best of the root moves searched so far, and the side to move can
+                    // only do better
+                    bound = if is_maximizing {
+                        tt::Bound::Lower
+                    } else {
+                        tt::Bound::Upper
+                    };
+                }
+                break;
+            } else {
+                prev_score = score;
+                prev_moves = moves.clone();
+                iterations.push((start_time.elapsed().as_millis(), moves[0]));
+
+                // uci reccomendation is to only show info after 1000 ms but I like seeing work
+                if start_time.elapsed().as_millis() > 0 {
+                    context.report(
+                        &tree.position,
+                        depth,
+                        tree.leaf_size,
+                        score,
+                        tt::Bound::Exact,
+                        &moves,
+                    );
+                }
+
+                window = if depth >= ASPIRATION_DEPTH {
+                    (score - ASPIRATION_WINDOW, score + ASPIRATION_WINDOW)
+                } else {
+                    FULL_WINDOW
+                };
+            }
+        }
+
+        context.finished.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            let _ = helper.join();
+        }
+
+        // a ponder search that ended early holds its move until the gui decides
+        while context.is_pondering() && !context.stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        let nodes = tree.leaf_size + helper_nodes.load(Ordering::Relaxed);
+
+        // a search stopped during the captures only first iteration can come back with a
+        // move that leaves the king in check
+        let legal_moves = if root_moves.is_empty() {
+            gen_legal_moves(&tree.position)
+        } else {
+            root_moves
+        };
+        if moves.first().is_none_or(|m| !legal_moves.contains(m)) {
+            moves = vec![legal_moves[0]];
+        }
+        moves = context.pv(&moves, &tree.position);
+
+        context.report(&tree.position, depth, nodes, score, bound, &moves);
+        sink.bestmove(Some(moves[0]), moves.get(1).copied());
+
+        iterations.push((start_time.elapsed().as_millis(), moves[0]));
+
+        if depth <= 2 {
+            depth = 3;
+        }
+
+        return SearchResult {
+            score,
+            moves,
+            depth,
+            nodes,
+            iterations,
+        };
+    }
+}
+
+// a lazy smp helper thread. depths are staggered between helpers so they don't all
+// search the same iteration, and nothing is reported
+fn helper_search(
+    mut tree: PositionTree,
+    thread_index: usize,
+    context: &SearchContext,
+    nodes: &AtomicUsize,
+) {
+    let mut depth = 1 + thread_index % 3;
+
+    while !context.should_stop(tree.leaf_size) {
+        let is_maximizing = tree.position.move_next == Color::White;
+        let start_pos = tree.position.clone();
+
+        let (score, _) = minimax(
+            &mut tree,
+            start_pos,
+            0,
+            0,
+            is_maximizing,
+            i32::MIN + 1,
+            i32::MAX,
+            depth,
+            context,
+        );
+
+        if score.abs() >= 30000 {
+            break;
+        }
+
+        depth += 1;
+    }
+
+    // wait for the main thread so the loop above isn't restarted on a finished mate
+    while !context.finished.load(Ordering::Relaxed) && !context.stop.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    nodes.fetch_add(tree.leaf_size, Ordering::Relaxed);
+}
+
+// search paths repeat their last move and mates end with the king being taken, so
+// only keep the part of the line that can actually be played
+fn legal_pv(moves: &[HalfMove], position: &Position) -> Vec<HalfMove> {
+    let mut position = position.clone();
+    let mut pv = vec![];
+
+    for &halfmove in moves {
+        if !gen_legal_moves(&position).contains(&halfmove) {
+            break;
+        }
+
+        execute_halfmove(&mut position, halfmove);
+        pv.push(halfmove);
+    }
+
+    return pv;
+}
+
+fn minimax(
+    tree: &mut PositionTree,
+    position: Position,
+    node_depth: usize,
+    node_index: usize,
+    is_maximizing: bool,
+    mut alpha: i32,
+    mut beta: i32,
+    depth: usize,
+    context: &SearchContext,
+) -> (i32, Vec<HalfMove>) {
+    if node_depth > context.seldepth.get() {
+        context.seldepth.set(node_depth);
+    }
+
+    let use_tt = depth > 0 && !(node_depth == 0 && context.restricted_root);
+
+    if use_tt {
+        if let Some((score, bound, best_move)) = context.tt.probe(position.gen_hash(), depth) {
+            // zobrist cache hit, unless the stored score is only a bound that says nothing
+            // about this window
+            let usable = match bound {
+                tt::Bound::Exact => true,
+                tt::Bound::Lower => score >= beta,
+                tt::Bound::Upper => score <= alpha,
+            };
+
+            if usable {
+                tree.nodes[node_depth][node_index].score = Some(score);
+                return (score, best_move.into_iter().collect());
+            }
+        }
+    }
+
+    if context.should_stop(tree.leaf_size) {
+        let eval = if is_maximizing {
+            i32::MIN + 1
+        } else {
+            i32::MAX
+        };
+        tree.nodes[node_depth][node_index].score = Some(eval);
+        return (eval, vec![tree.nodes[node_depth][node_index].halfmove]);
+    }
+
+    if node_depth > 0 {
+        if let Some(eval) = position.variant.rules().game_result(&position) {
+            tree.nodes[node_depth][node_index].score = Some(eval);
+            return (eval, vec![tree.nodes[node_depth][node_index].halfmove]);
+        }
+    }
+
+    if node_depth > 0 && context.tablebase.can_probe(&position, context.probe_limit) {
+        if let Some(wdl) = context.tablebase.probe_wdl(&position) {
+            let mut eval = syzygy::wdl_to_score(wdl, node_depth);
+            if position.move_next == Color::Black {
+                eval = -eval;
+            }
+
+            tree.nodes[node_depth][node_index].score = Some(eval);
+            return (eval, vec![tree.nodes[node_depth][node_index].halfmove]);
+        }
+    }
+
+    if tree.nodes[node_depth][node_index].children.is_none() {
+        tree.gen_children(node_depth, node_index);
+    }
+
+    // a side without moves can win or lose by a variant rule, instead of the static eval
+    if tree.nodes[node_depth][node_index].children.is_none() {
+        if let Some(eval) = position.variant.rules().no_moves_result(&position) {
+            tree.nodes[node_depth][node_index].score = Some(eval);
+            return (eval, vec![tree.nodes[node_depth][node_index].halfmove]);
+        }
+    }
+
+    let mut eval_exists = false;
+    let mut to_search: Vec<(usize, Option<i32>)> = Vec::new();
+    if tree.nodes[node_depth][node_index].children.is_some() {
+        let children = tree.nodes[node_depth][node_index].children.unwrap();
+        for i in children.0..children.1 + 1 {
+            if depth > 0
+                || position.board[tree.nodes[node_depth + 1][i].halfmove.to as usize].is_some()
+            {
+                to_search.push((i, tree.nodes[node_depth + 1][i].score));
+                if tree.nodes[node_depth + 1][i].score.is_some() {
+                    eval_exists = true
+                }
+            }
+        }
+    }
+
+    if to_search.is_empty() {
+        let eval = position_eval(&position, &context.repetition_map);
+        tree.nodes[node_depth][node_index].score = Some(eval);
+        return (eval, vec![tree.nodes[node_depth][node_index].halfmove]);
+    }
+
+    if eval_exists {
+        let (mut to_search_with_score, to_search_without_score): (Vec<_>, Vec<_>) = to_search
+            .clone()
+            .into_iter()
+            .partition(|&(_, score)| score.is_some());
+        if is_maximizing {
+            to_search_with_score.sort_by(|a, b| b.1.cmp(&a.1));
+        } else {
+            to_search_with_score.sort_by(|a, b| a.1.cmp(&b.1));
+        }
+        let mut to_search = to_search_with_score;
+        to_search.extend(to_search_without_score);
+    }
+
+    // scores are white's, so for both sides a score at or below alpha is at most that and
+    // one at or above beta at least that
+    let (alpha_start, beta_start) = (alpha, beta);
+
+    let mut best_score = if is_maximizing {
+        i32::MIN + 1
+    } else {
+        i32::MAX
+    };
+    let mut best_path = Vec::new();
+    for i in 0..to_search.len() {
+        let mut new_pos = position.clone();
+
+        let halfmove = tree.nodes[node_depth + 1][to_search[i].0].halfmove;
+        execute_halfmove(&mut new_pos, halfmove);
+
+        // no more computations if found mate
+        if is_maximizing && alpha >= 30000 {
+            return (alpha, best_path);
+        } else if !is_maximizing && beta <= -30000 {
+            return (beta, best_path);
+        }
+
+        if node_depth == 0 {
+            context.report_currmove(halfmove, i + 1);
+        }
+
+        let (child_score, mut child_path) = minimax(
+            tree,
+            new_pos,
+            node_depth + 1,
+            to_search[i].0,
+            !is_maximizing,
+            alpha,
+            beta,
+            if depth > 0 { depth - 1 } else { 0 },
+            context,
+        );
+
+        if is_maximizing {
+            if child_score > best_score {
+                best_score = child_score;
+                child_path.insert(0, halfmove);
+                best_path = child_path.clone();
+            }
+            alpha = alpha.max(best_score);
+        } else {
+            if child_score < best_score {
+                best_score = child_score;
+                child_path.insert(0, halfmove);
+                best_path = child_path.clone();
+            }
+            beta = beta.min(best_score);
+        }
+
+        if beta <= alpha {
+            break;
+        }
+
+        // note: no need to early return if finished loop anyways, so check for all but last iter
+        if i < to_search.len() - 1 && context.should_stop(tree.leaf_size) {
+            // note: won't be sorted if early return.
+            // also won't store in zobrist, which is intentional, as current is not fully searched
+            tree.nodes[node_depth][node_index].score = Some(best_score);
+            return (best_score, best_path);
+        }
+    }
+
+    // a stop during the last child leaves its sentinel score in best_score, which the
+    // next search would take for a mate
+    if use_tt && !context.is_interrupted() {
+        let bound = if best_score <= alpha_start {
+            tt::Bound::Upper
+        } else if best_score >= beta_start {
+            tt::Bound::Lower
+        } else {
+            tt::Bound::Exact
+        };
+
+        context.tt.store(
+            position.gen_hash(),
+            depth,
+            best_score,
+            bound,
+            best_path.first().copied(),
+        );
+    }
+
+    tree.nodes[node_depth][node_index].score = Some(best_score);
+    return (best_score, best_path);
+}
+
+pub fn position_eval(position: &Position, repetition_map: &HashMap<u64, u8>) -> i32 {
+    let mut eval = 0;
+
+    // 50-move rule
+    if position.halfmove_clock >= 50 {
+        return 0;
+    }
+
+    // threefold repetition
+    let hash = position.gen_hash();
+    if let Some(&count) = repetition_map.get(&hash) {
+        if count >= 2 {
+            return 0;
+        }
+    }
+
+    let rules = position.variant.rules();
+
+    for &i in position.piece_set.white.iter() {
+        eval += rules.piece_value(position.board[i as usize].unwrap(), i);
+    }
+    for &i in position.piece_set.black.iter() {
+        eval -= rules.piece_value(position.board[i as usize].unwrap(), i);
+    }
+
+    eval += rules.eval(position);
+
+    return eval;
+}
+
+pub fn get_piece_value(piece: Piece, index: u8) -> i32 {
+    let mut value;
+    let pawn_table = [
+        0, 0, 0, 0, 0, 0, 0, 0, 30, 30, 30, 40, 40, 30, 30, 30, 20, 20, 20, 30, 30, 30, 20, 20, 10,
+        10, 15, 25, 25, 15, 10, 10, 5, 5, 5, 20, 20, 5, 5, 5, 5, 0, 0, 5, 5, 0, 0, 5, 5, 5, 5, -10,
+        -10, 5, 5, 5, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let knight_table = [
+        -5, -5, -5, -5, -5, -5, -5, -5, -5, 0, 0, 10, 10, 0, 0, -5, -5, 5, 10, 10, 10, 10, 5, -5,
+        -5, 5, 10, 15, 15, 10, 5, -5, -5, 5, 10, 15, 15, 10, 5, -5, -5, 5, 10, 10, 10, 10, 5, -5,
+        -5, 0, 0, 5, 5, 0, 0, -5, -5, -10, -5, -5, -5, -5, -10, -5,
+    ];
+    let bishop_table = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10, 0, 0, 0, 0,
+        10, 0, 5, 0, 10, 0, 0, 10, 0, 5, 0, 10, 0, 10, 10, 0, 10, 0, 0, 10, 0, 10, 10, 0, 10, 0, 0,
+        0, -10, 0, 0, -10, 0, 0,
+    ];
+    let rook_table = [
+        10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 10, 10, 0, 0,
+        0, 0, 0, 0, 10, 10, 5, 0, 0,
+    ];
+    let queen_table = [
+        -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0,
+        -10, -5, 0, 5, 5, 5, 5, 0, -5, -5, 0, 5, 5, 5, 5, 0, -5, -10, 5, 5, 5, 5, 5, 0, -10, -10,
+        0, 5, 0, 0, 0, 0, -10, -20, -10, -10, 0, 0, -10, -10, -20,
+    ];
+    let king_table = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, -5, -5, -5, 0, 0, 0, 0, 10,
+        -5, -5, -5, 10, 0,
+    ];
+
+    let pos = if piece.get_color() == Color::White {
+        (63 - index) as usize
+    } else {
+        index as usize
+    };
+
+    value = piece.get_cp_val() as i32;
+
+    match piece {
+        Piece::Pawn(_) => {
+            value += pawn_table[pos];
+        }
+        Piece::Bishop(_) => {
+            value += bishop_table[pos];
+        }
+        Piece::Knight(_) => {
+            value += knight_table[pos];
+        }
+        Piece::Rook(_) => {
+            value += rook_table[pos];
+        }
+        Piece::Queen(_) => {
+            value += queen_table[pos];
+        }
+        Piece::King(_) => {
+            value += king_table[pos];
+        }
+    }
+
+    return value;
+}
+
+fn gen_possible(position: &mut Position) -> Vec<HalfMove> {
+    let moves = position.variant.rules().gen_possible(position);
+
+    return moves;
+}
+
+pub fn gen_legal_moves(position: &Position) -> Vec<HalfMove> {
+    let rules = position.variant.rules();
+
+    // no moves once a variant rule ended the game
+    if rules.game_result(position).is_some() {
+        return vec![];
+    }
+
+    let mut moves = rules.gen_possible(position);
+    moves.retain(|&halfmove| {
+        let mut new_pos = position.clone();
+        rules.execute_halfmove(&mut new_pos, halfmove);
+
+        return rules.is_legal(position, &new_pos, halfmove);
+    });
+
+    return moves;
+}
+
+pub fn is_in_check(position: &Position) -> bool {
+    let king_pos = if position.move_next == Color::White {
+        position.piece_set.white_king
+    } else {
+        position.piece_set.black_king
+    };
+
+    return is_piece_attacked(king_pos, position.move_next, position);
+}
+
+pub fn is_piece_attacked(index: u8, piece_color: Color, position: &Position) -> bool {
+    let opp_color = piece_color.opposite();
+
+    let mut dir_offset = -8;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset < 0 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Rook(opp_color)
+                || (piece == Piece::King(opp_color) && offset == -8)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = 8;
+    offset = dir_offset;
+
+    loop {
+        if index as i8 + offset > 63 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Rook(opp_color)
+                || (piece == Piece::King(opp_color) && offset == 8)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = 1;
+    offset = dir_offset;
+
+    loop {
+        if (index as i8 + offset) % 8 == 0 || index as i8 + offset > 63 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Rook(opp_color)
+                || (piece == Piece::King(opp_color) && offset == 1)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = -1;
+    offset = dir_offset;
+
+    loop {
+        if (index as i8 + offset) % 8 == 7 || index as i8 + offset < 0 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Rook(opp_color)
+                || (piece == Piece::King(opp_color) && offset == -1)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = 9;
+    offset = dir_offset;
+
+    loop {
+        if index as i8 + offset > 63 || (index as i8 + offset) % 8 == 0 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Bishop(opp_color)
+                || (piece == Piece::King(opp_color) && offset == 9)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = 7;
+    offset = dir_offset;
+
+    loop {
+        if index as i8 + offset > 63 || (index as i8 + offset) % 8 == 7 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Bishop(opp_color)
+                || (piece == Piece::King(opp_color) && offset == 7)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = -9;
+    offset = dir_offset;
+
+    loop {
+        if index as i8 + offset < 0 || (index as i8 + offset) % 8 == 7 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Bishop(opp_color)
+                || (piece == Piece::King(opp_color) && offset == -9)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    dir_offset = -7;
+    offset = dir_offset;
+
+    loop {
+        if index as i8 + offset < 0 || (index as i8 + offset) % 8 == 0 {
+            break;
+        }
+
+        if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+            if piece == Piece::Queen(opp_color)
+                || piece == Piece::Bishop(opp_color)
+                || (piece == Piece::King(opp_color) && offset == -7)
+            {
+                return true;
+            }
+
+            break;
+        }
+
+        offset += dir_offset;
+    }
+
+    // knight checks
+    // up 2
+    if (index / 8) <= 5 {
+        // right 1
+        if (index % 8) <= 6
+            && position.board[(index as i8 + 17) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+
+        // left 1
+        if (index % 8) >= 1
+            && position.board[(index as i8 + 15) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+    }
+
+    // right 2
+    if (index % 8) <= 5 {
+        // up 1
+        if (index / 8) <= 6
+            && position.board[(index as i8 + 10) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+
+        // down 1
+        if (index / 8) >= 1
+            && position.board[(index as i8 - 6) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+    }
+
+    // down 2
+    if (index / 8) >= 2 {
+        // right 1
+        if (index % 8) <= 6
+            && position.board[(index as i8 - 15) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+
+        // left 1
+        if (index % 8) >= 1
+            && position.board[(index as i8 - 17) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+    }
+
+    // left 2
+    if (index % 8) >= 2 {
+        // up 1
+        if (index / 8) <= 6
+            && position.board[(index as i8 + 6) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+
+        // down 1
+        if (index / 8) >= 1
+            && position.board[(index as i8 - 10) as usize] == Some(Piece::Knight(opp_color))
+        {
+            return true;
+        }
+    }
+
+    // pawn checks (not counting en-passant)
+    if opp_color == Color::White && index > 7 {
+        if index % 8 > 0
+            && position.board[(index as i8 - 9) as usize] == Some(Piece::Pawn(opp_color))
+        {
+            return true;
+        }
+
+        if index % 8 < 7
+            && position.board[(index as i8 - 7) as usize] == Some(Piece::Pawn(opp_color))
+        {
+            return true;
+        }
+    }
+
+    if opp_color == Color::Black && index < 56 {
+        if index % 8 > 0
+            && position.board[(index as i8 + 7) as usize] == Some(Piece::Pawn(opp_color))
+        {
+            return true;
+        }
+
+        if index % 8 < 7
+            && position.board[(index as i8 + 9) as usize] == Some(Piece::Pawn(opp_color))
+        {
+            return true;
+        }
+    }
+
+    // todo: implement en-passant check so fn can be generalized for universal use including pawns
+
+    return false;
+}
+
+pub fn gen_pseudolegal_moves(position: &Position) -> Vec<HalfMove> {
+    let color = position.move_next;
+
+    let piece_set: HashSet<u8> = if color == Color::Black {
+        position.piece_set.black.clone()
+    } else {
+        position.piece_set.white.clone()
+    };
+
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    for i in piece_set {
+        // gen pseudolegal moves for each piece at index i
+        // add each move to moves vector
+        let result = gen_piece_pseudolegal_moves(i, position);
+        moves.extend(result);
+
+        // likely no need to gen new threads here, will likely be suboptimal due to thread overhead.
+        // if no need for threads, we can pass moves as an address instead and return nothing
+        // todo: test thread implementation performance
+        // Our tree will exponentially grow so fast itd be pointless to do it here.
+
+        // just a thought, if we make the eval properly, do we even need to check for legality?
+    }
+
+    moves.extend(gen_castles(position));
+
+    return moves;
+}
+
+// castles as king takes rook. the king and rook end up on the same squares as in
+// standard chess, and every square either of them crosses has to be empty apart from
+// the two of them
+fn gen_castles(position: &Position) -> Vec<HalfMove> {
+    let color = position.move_next;
+    let rights = position.castling_rights.color(color);
+    let king_square = if color == Color::White {
+        position.piece_set.white_king
+    } else {
+        position.piece_set.black_king
+    };
+    let rank_start = if color == Color::White { 0 } else { 56 };
+
+    let mut moves = vec![];
+
+    if king_square / 8 != rank_start / 8
+        || position.board[king_square as usize] != Some(Piece::King(color))
+    {
+        return moves;
+    }
+
+    for rook_file in [rights.kingside, rights.queenside].into_iter().flatten() {
+        let castle = HalfMove {
+            from: king_square,
+            to: rank_start + rook_file,
+            flag: Some(HalfmoveFlag::Castle),
+            is_capture: false,
+        };
+
+        if position.board[castle.to as usize] != Some(Piece::Rook(color)) {
+            continue;
+        }
+
+        let king_path = squares_between(king_square, castle.castle_king_square());
+        let rook_path = squares_between(castle.to, castle.castle_rook_square());
+
+        let is_blocked = king_path.clone().chain(rook_path).any(|square| {
+            square != king_square
+                && square != castle.to
+                && position.board[square as usize].is_some()
+        });
+
+        // the destination is left to the legality check like any other king move
+        let crosses_attack = king_path
+            .filter(|&square| square != king_square)
+            .any(|square| is_piece_attacked(square, color, position));
+
+        if !is_blocked && !crosses_attack {
+            moves.push(castle);
+        }
+    }
+
+    return moves;
+}
+
+// both ends included, on the same rank
+fn squares_between(a: u8, b: u8) -> std::ops::RangeInclusive<u8> {
+    return a.min(b)..=a.max(b);
+}
+
+// the up to eight squares around a square
+fn adjacent_squares(square: u8) -> impl Iterator<Item = u8> {
+    let (file, rank) = ((square % 8) as i8, (square / 8) as i8);
+
+    return (-1..=1)
+        .flat_map(move |df| (-1..=1).map(move |dr| (file + df, rank + dr)))
+        .filter(move |&(f, r)| (f, r) != (file, rank) && (0..8).contains(&f) && (0..8).contains(&r))
+        .map(|(f, r)| (r * 8 + f) as u8);
+}
+
+fn gen_piece_pseudolegal_moves(piece_index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves;
+
+    match position.board[piece_index as usize] {
+        Some(Piece::Pawn(Color::White)) => {
+            moves = gen_white_pawn_moves(piece_index, position);
+        }
+        Some(Piece::Pawn(Color::Black)) => {
+            moves = gen_black_pawn_moves(piece_index, position);
+        }
+        Some(Piece::Knight(_)) => {
+            moves = gen_knight_moves(piece_index, position);
+        }
+        Some(Piece::Rook(_)) => {
+            moves = gen_rook_moves(piece_index, position);
+        }
+        Some(Piece::Bishop(_)) => {
+            moves = gen_bishop_moves(piece_index, position);
+        }
+        Some(Piece::Queen(_)) => {
+            moves = gen_queen_moves(piece_index, position);
+        }
+        Some(Piece::King(_)) => {
+            moves = gen_normal_king_moves(piece_index, position);
+        }
+        None => panic!("Error, index contained in piece_set has no piece on board!"),
+    }
+
+    for halfmove in moves.iter_mut() {
+        if position.board[halfmove.to as usize].is_none()
+            && halfmove.flag != Some(HalfmoveFlag::EnPassant)
+        {
+            halfmove.is_capture = true;
+        }
+    }
+
+    return moves;
+}
+
+fn gen_normal_king_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    gen_halfmove_with_check(7, index, position, &mut moves);
+    gen_halfmove_with_check(8, index, position, &mut moves);
+    gen_halfmove_with_check(9, index, position, &mut moves);
+    gen_halfmove_with_check(1, index, position, &mut moves);
+    gen_halfmove_with_check(-7, index, position, &mut moves);
+    gen_halfmove_with_check(-8, index, position, &mut moves);
+    gen_halfmove_with_check(-9, index, position, &mut moves);
+    gen_halfmove_with_check(-1, index, position, &mut moves);
+
+    return moves;
+}
+
+fn gen_halfmove_with_check(offset: i8, index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    if index as i8 + offset > 63 || index as i8 + offset < 0 {
+        return;
+    }
+
+    // rightward bound check
+    if (offset % 8 == 1 || offset % 8 == -7) && index % 8 == 7 {
+        return;
+    }
+
+    // leftward bound check
+    if (offset % 8 == 7 || offset % 8 == -1) && index % 8 == 0 {
+        return;
+    }
+
+    gen_halfmove(offset, index, position, moves);
+}
+
+fn gen_queen_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    gen_down_left(index, position, &mut moves);
+    gen_down_right(index, position, &mut moves);
+    gen_up_left(index, position, &mut moves);
+    gen_up_right(index, position, &mut moves);
+    gen_downwards(index, position, &mut moves);
+    gen_right(index, position, &mut moves);
+    gen_upwards(index, position, &mut moves);
+    gen_left(index, position, &mut moves);
+
+    return moves;
+}
+
+fn gen_knight_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    // total of 8 move combinations
+
+    // first, check bounds for length 2, then check bounds for length 1
+
+    // up 2
+    if (index / 8) <= 5 {
+        // right 1
+        if (index % 8) <= 6 {
+            gen_halfmove(17, index, position, &mut moves);
+        }
+
+        // left 1
+        if (index % 8) >= 1 {
+            gen_halfmove(15, index, position, &mut moves);
+        }
+    }
+
+    // right 2
+    if (index % 8) <= 5 {
+        // up 1
+        if (index / 8) <= 6 {
+            gen_halfmove(10, index, position, &mut moves);
+        }
+
+        // down 1
+        if (index / 8) >= 1 {
+            gen_halfmove(-6, index, position, &mut moves);
+        }
+    }
+
+    // down 2
+    if (index / 8) >= 2 {
+        // right 1
+        if (index % 8) <= 6 {
+            gen_halfmove(-15, index, position, &mut moves);
+        }
+
+        // left 1
+        if (index % 8) >= 1 {
+            gen_halfmove(-17, index, position, &mut moves);
+        }
+    }
+
+    // left 2
+    if (index % 8) >= 2 {
+        // up 1
+        if (index / 8) <= 6 {
+            gen_halfmove(6, index, position, &mut moves);
+        }
+
+        // down 1
+        if (index / 8) >= 1 {
+            gen_halfmove(-10, index, position, &mut moves);
+        }
+    }
+
+    return moves;
+}
+
+fn gen_upwards(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = 8;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset > 63 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_downwards(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = -8;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset < 0 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_right(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = 1;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if (index as i8 + offset) % 8 == 0 || index as i8 + offset > 63 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_left(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = -1;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if (index as i8 + offset) % 8 == 7 || index as i8 + offset < 0 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_up_right(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = 9;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset > 63 || (index as i8 + offset) % 8 == 0 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_up_left(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = 7;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset > 63 || (index as i8 + offset) % 8 == 7 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_down_right(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = -7;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset < 0 || (index as i8 + offset) % 8 == 0 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_down_left(index: u8, position: &Position, moves: &mut Vec<HalfMove>) {
+    let dir_offset = -9;
+    let mut offset: i8 = dir_offset;
+
+    loop {
+        if index as i8 + offset < 0 || (index as i8 + offset) % 8 == 7 {
+            break;
+        }
+
+        if !gen_halfmove(offset, index, position, moves) {
+            break;
+        }
+
+        offset += dir_offset;
+    }
+}
+
+fn gen_bishop_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    gen_down_left(index, position, &mut moves);
+    gen_down_right(index, position, &mut moves);
+    gen_up_left(index, position, &mut moves);
+    gen_up_right(index, position, &mut moves);
+
+    return moves;
+}
+
+fn gen_rook_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    gen_downwards(index, position, &mut moves);
+    gen_right(index, position, &mut moves);
+    gen_upwards(index, position, &mut moves);
+    gen_left(index, position, &mut moves);
+
+    return moves;
+}
+
+fn gen_halfmove(offset: i8, index: u8, position: &Position, moves: &mut Vec<HalfMove>) -> bool {
+    let mut to_return = true;
+
+    if let Some(piece) = position.board[(index as i8 + offset) as usize] {
+        if piece.get_color() == position.move_next {
+            return false;
+        }
+        to_return = false;
+    }
+
+    moves.push(HalfMove {
+        from: index,
+        to: (index as i8 + offset) as u8,
+        flag: None,
+        is_capture: false,
+    });
+
+    return to_return;
+}
+
+fn gen_white_pawn_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    let board = position.board;
+    let piece = board[index as usize].unwrap();
+    let color = piece.get_color();
+
+    // straight move
+    if board[(index + 8) as usize].is_none() {
+        // nothing in the way
+        if (index / 8) != 6 {
+            moves.push(HalfMove {
+                from: index,
+                to: (index + 8),
+                flag: None,
+                is_capture: false,
+            });
+            if (index / 8 == 1) && board[(index + 16) as usize].is_none() {
+                moves.push(HalfMove {
+                    from: index,
+                    to: (index + 16),
+                    flag: Some(HalfmoveFlag::DoublePawnMove),
+                    is_capture: false,
+                });
+            }
+        } else {
+            // promotion
+            moves.push(HalfMove {
+                from: index,
+                to: (index + 8),
+                flag: Some(HalfmoveFlag::KnightPromotion),
+                is_capture: false,
+            });
+            moves.push(HalfMove {
+                from: index,
+                to: (index + 8),
+                flag: Some(HalfmoveFlag::BishopPromotion),
+                is_capture: false,
+            });
+            moves.push(HalfMove {
+                from: index,
+                to: (index + 8),
+                flag: Some(HalfmoveFlag::RookPromotion),
+                is_capture: false,
+            });
+            moves.push(HalfMove {
+                from: index,
+                to: (index + 8),
+                flag: Some(HalfmoveFlag::QueenPromotion),
+                is_capture: false,
+            });
+        }
+    }
+
+    // captures
+    let should_promote = (index / 8) == 6;
+
+    if (index % 8) != 0 {
+        // left capture
+        if let Some(target) = board[(index + 7) as usize] {
+            if target.get_color() != color {
+                if should_promote {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 7),
+                        flag: Some(HalfmoveFlag::KnightPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 7),
+                        flag: Some(HalfmoveFlag::BishopPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 7),
+                        flag: Some(HalfmoveFlag::RookPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 7),
+                        flag: Some(HalfmoveFlag::QueenPromotion),
+                        is_capture: false,
+                    });
+                } else {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 7),
+                        flag: None,
+                        is_capture: false,
+                    });
+                }
+            }
+        } else if let Some(target) = position.en_passant_target {
+            // en passant
+            if index + 7 == target {
+                moves.push(HalfMove {
+                    from: index,
+                    to: (index + 7),
+                    flag: Some(HalfmoveFlag::EnPassant),
+                    is_capture: false,
+                });
+            }
+        }
+    }
+    if (index % 8) != 7 {
+        // right capture
+        if let Some(target) = board[(index + 9) as usize] {
+            if target.get_color() != color {
+                if should_promote {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 9),
+                        flag: Some(HalfmoveFlag::KnightPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 9),
+                        flag: Some(HalfmoveFlag::BishopPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 9),
+                        flag: Some(HalfmoveFlag::RookPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 9),
+                        flag: Some(HalfmoveFlag::QueenPromotion),
+                        is_capture: false,
+                    });
+                } else {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index + 9),
+                        flag: None,
+                        is_capture: false,
+                    });
+                }
+            }
+        } else if let Some(target) = position.en_passant_target {
+            // en passant
+            if index + 9 == target {
+                moves.push(HalfMove {
+                    from: index,
+                    to: (index + 9),
+                    flag: Some(HalfmoveFlag::EnPassant),
+                    is_capture: false,
+                });
+            }
+        }
+    }
+
+    return moves;
+}
+
+fn gen_black_pawn_moves(index: u8, position: &Position) -> Vec<HalfMove> {
+    let mut moves: Vec<HalfMove> = Vec::new();
+
+    let board = position.board;
+    let piece = board[index as usize].unwrap();
+    let color = piece.get_color();
+
+    // straight move
+    if board[(index - 8) as usize].is_none() {
+        // nothing in the way
+        if (index / 8) != 1 {
+            moves.push(HalfMove {
+                from: index,
+                to: (index - 8),
+                flag: None,
+                is_capture: false,
+            });
+            if (index / 8 == 6) && board[(index - 16) as usize].is_none() {
+                moves.push(HalfMove {
+                    from: index,
+                    to: (index - 16),
+                    flag: Some(HalfmoveFlag::DoublePawnMove),
+                    is_capture: false,
+                });
+            }
+        } else {
+            // promotion
+            moves.push(HalfMove {
+                from: index,
+                to: (index - 8),
+                flag: Some(HalfmoveFlag::KnightPromotion),
+                is_capture: false,
+            });
+            moves.push(HalfMove {
+                from: index,
+                to: (index - 8),
+                flag: Some(HalfmoveFlag::BishopPromotion),
+                is_capture: false,
+            });
+            moves.push(HalfMove {
+                from: index,
+                to: (index - 8),
+                flag: Some(HalfmoveFlag::RookPromotion),
+                is_capture: false,
+            });
+            moves.push(HalfMove {
+                from: index,
+                to: (index - 8),
+                flag: Some(HalfmoveFlag::QueenPromotion),
+                is_capture: false,
+            });
+        }
+    }
+
+    // captures (left/right orientation with white as bottom)
+    let should_promote = (index / 8) == 1;
+
+    if (index % 8) != 0 {
+        // left capture
+        if let Some(target) = board[(index - 9) as usize] {
+            if target.get_color() != color {
+                if should_promote {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 9),
+                        flag: Some(HalfmoveFlag::KnightPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 9),
+                        flag: Some(HalfmoveFlag::BishopPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 9),
+                        flag: Some(HalfmoveFlag::RookPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 9),
+                        flag: Some(HalfmoveFlag::QueenPromotion),
+                        is_capture: false,
+                    });
+                } else {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 9),
+                        flag: None,
+                        is_capture: false,
+                    });
+                }
+            }
+        } else if let Some(target) = position.en_passant_target {
+            // en passant
+            if index - 9 == target {
+                moves.push(HalfMove {
+                    from: index,
+                    to: (index - 9),
+                    flag: Some(HalfmoveFlag::EnPassant),
+                    is_capture: false,
+                });
+            }
+        }
+    }
+    if (index % 8) != 7 {
+        // right capture
+        if let Some(target) = board[(index - 7) as usize] {
+            if target.get_color() != color {
+                if should_promote {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 7),
+                        flag: Some(HalfmoveFlag::KnightPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 7),
+                        flag: Some(HalfmoveFlag::BishopPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 7),
+                        flag: Some(HalfmoveFlag::RookPromotion),
+                        is_capture: false,
+                    });
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 7),
+                        flag: Some(HalfmoveFlag::QueenPromotion),
+                        is_capture: false,
+                    });
+                } else {
+                    moves.push(HalfMove {
+                        from: index,
+                        to: (index - 7),
+                        flag: None,
+                        is_capture: false,
+                    });
+                }
+            }
+        } else if let Some(target) = position.en_passant_target {
+            // en passant
+            if index - 7 == target {
+                moves.push(HalfMove {
+                    from: index,
+                    to: (index - 7),
+                    flag: Some(HalfmoveFlag::EnPassant),
+                    is_capture: false,
+                });
+            }
+        }
+    }
+
+    return moves;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(fen: &str, limits: SearchLimits) -> SearchResult {
+        let engine = Engine::new();
+        let position = Position::from_fen(fen).unwrap();
+
+        return engine
+            .searcher()
+            .search(position, &limits, &info::SilentInfo);
+    }
+
+    fn depth(depth: usize) -> SearchLimits {
+        return SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        };
+    }
+
+    #[test]
+    fn rejects_garbled_coordinates() {
+        assert_eq!(coord_to_int("a1"), Some(0));
+        assert_eq!(coord_to_int("h8"), Some(63));
+        for coord in ["", "e", "e9", "i1", "e44", "é1"] {
+            assert_eq!(coord_to_int(coord), None);
+        }
+
+        let position = Position::startpos();
+        for move_string in ["", "xx", "e2", "e2e9", "P@z9", "K@e4"] {
+            assert!(string_to_halfmove(&position, move_string).is_none());
+        }
+        assert!(string_to_halfmove(&position, "e2e4").is_some());
+    }
+
+    fn san_of(fen: &str, uci: &str) -> String {
+        let position = Position::from_fen(fen).unwrap();
+        return parse_legal_move(&position, uci, false)
+            .unwrap()
+            .move_to_san(&position);
+    }
+
+    fn from_san_of(fen: &str, san: &str) -> Option<String> {
+        let position = Position::from_fen(fen).unwrap();
+        return HalfMove::from_san(&position, san).map(|halfmove| halfmove.move_to_uci(false));
+    }
+
+    #[test]
+    fn writes_san() {
+        // by file, by rank, and by square when neither is enough
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        let rooks = "R7/7k/8/8/8/8/8/R3K3 w - - 0 1";
+        let queens = "8/7k/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san_of(knights, "b1d2"), "Nbd2");
+        assert_eq!(san_of(knights, "f1d2"), "Nfd2");
+        assert_eq!(san_of(rooks, "a1a4"), "R1a4");
+        assert_eq!(san_of(rooks, "a8a4"), "R8a4");
+        assert_eq!(san_of(queens, "a1b2"), "Qa1b2");
+        assert_eq!(san_of(queens, "a3b2"), "Q3b2");
+        assert_eq!(san_of(queens, "c1b2"), "Qcb2");
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san_of(en_passant, "e5d6"), "exd6 e.p.");
+
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san_of(castling, "e1g1"), "O-O");
+        assert_eq!(san_of(castling, "e1c1"), "O-O-O");
+
+        assert_eq!(san_of("7k/4P3/8/8/8/8/8/K7 w - - 0 1", "e7e8q"), "e8=Q+");
+        assert_eq!(san_of("7k/4P3/8/8/8/8/8/K7 w - - 0 1", "e7e8n"), "e8=N");
+        assert_eq!(san_of("k7/4P3/1K6/8/8/8/8/8 w - - 0 1", "e7e8q"), "e8=Q#");
+        assert_eq!(san_of("k7/4P3/1K6/8/8/8/8/8 w - - 0 1", "e7e8r"), "e8=R#");
+    }
+
+    #[test]
+    fn reads_san() {
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        let rooks = "R7/7k/8/8/8/8/8/R3K3 w - - 0 1";
+        let queens = "8/7k/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(from_san_of(knights, "Nbd2"), Some(String::from("b1d2")));
+        assert_eq!(from_san_of(rooks, "R8a4"), Some(String::from("a8a4")));
+        assert_eq!(from_san_of(queens, "Qa1b2"), Some(String::from("a1b2")));
+        assert_eq!(from_san_of(queens, "Qcb2"), Some(String::from("c1b2")));
+
+        // ambiguous
+        assert_eq!(from_san_of(knights, "Nd2"), None);
+        assert_eq!(from_san_of(rooks, "Ra4"), None);
+        assert_eq!(from_san_of(queens, "Qb2"), None);
+        assert_eq!(from_san_of(queens, "Qab2"), None);
+        assert_eq!(from_san_of(queens, "Q1b2"), None);
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(from_san_of(en_passant, "exd6"), Some(String::from("e5d6")));
+        assert_eq!(
+            from_san_of(en_passant, "exd6 e.p."),
+            Some(String::from("e5d6"))
+        );
+
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(from_san_of(castling, "O-O"), Some(String::from("e1g1")));
+        assert_eq!(from_san_of(castling, "0-0-0"), Some(String::from("e1c1")));
+
+        let promotion = "k7/4P3/1K6/8/8/8/8/8 w - - 0 1";
+        assert_eq!(from_san_of(promotion, "e8=Q#"), Some(String::from("e7e8q")));
+        assert_eq!(from_san_of(promotion, "e8N"), Some(String::from("e7e8n")));
+        assert_eq!(from_san_of(promotion, "e8"), None);
+
+        // illegal or garbled
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        for san in [
+            "", "e5", "Nf6", "Ke2", "Qh5", "O-O", "Bb5", "Zz9", "e9", "N",
+        ] {
+            assert_eq!(from_san_of(start, san), None, "{}", san);
+        }
+
+        // every legal move reads back from its own san
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            queens,
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            for halfmove in gen_legal_moves(&position) {
+                let san = halfmove.move_to_san(&position);
+                assert_eq!(
+                    HalfMove::from_san(&position, &san),
+                    Some(halfmove),
+                    "{}",
+                    san
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn castles_as_king_takes_rook_in_chess960() {
+        // b1c1 is also a plain king move, so only b1a1 says which one is meant
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        let castles: Vec<HalfMove> = gen_legal_moves(&position)
+            .into_iter()
+            .filter(|halfmove| halfmove.flag == Some(HalfmoveFlag::Castle))
+            .collect();
+        let uci = |chess960| {
+            let mut moves: Vec<String> = castles
+                .iter()
+                .map(|castle| castle.move_to_uci(chess960))
+                .collect();
+            moves.sort();
+            return moves;
+        };
+        assert_eq!(uci(true), ["b1a1", "b1h1"]);
+        assert_eq!(uci(false), ["b1c1", "b1g1"]);
+
+        let queenside = parse_legal_move(&position, "b1a1", true).unwrap();
+        assert_eq!(queenside.flag, Some(HalfmoveFlag::Castle));
+        assert_ne!(parse_legal_move(&position, "b1c1", true), Some(queenside));
+        assert_eq!(parse_legal_move(&position, "O-O-O", true), Some(queenside));
+
+        let mut castled = position.clone();
+        execute_halfmove(&mut castled, queenside);
+        assert_eq!(castled.to_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+
+        // the king can already stand on its castled square
+        let position = Position::from_fen("4k3/8/8/8/8/8/8/6KR w H - 0 1").unwrap();
+        let kingside = parse_legal_move(&position, "g1h1", true).unwrap();
+        assert_eq!(kingside.flag, Some(HalfmoveFlag::Castle));
+        assert_eq!(kingside.move_to_uci(true), "g1h1");
+
+        let mut castled = position.clone();
+        execute_halfmove(&mut castled, kingside);
+        assert_eq!(castled.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn undoes_every_move() {
+        let fens = [
+            (
+                Variant::Standard,
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            ),
+            (
+                Variant::Standard,
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1",
+            ),
+            (
+                Variant::Standard,
+                "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            ),
+            (
+                Variant::Standard,
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            ),
+            (
+                Variant::Atomic,
+                "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1",
+            ),
+            (
+                Variant::Crazyhouse,
+                "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQ~KB1R[Pn] b KQkq - 0 3",
+            ),
+            (
+                Variant::ThreeCheck,
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 1+1 0 1",
+            ),
+        ];
+
+        for (variant, fen) in fens {
+            let mut position = Position::from_variant_fen(fen, variant).unwrap();
+            let original = position.clone();
+
+            for halfmove in gen_legal_moves(&position) {
+                let mut made = position.clone();
+                execute_halfmove(&mut made, halfmove);
+
+                let undo = do_halfmove(&mut position, halfmove);
+                assert_eq!(position.gen_hash(), made.gen_hash());
+                assert_eq!(position.piece_set.all, made.piece_set.all);
+
+                undo_halfmove(&mut position, undo);
+                assert_eq!(position.to_fen(), original.to_fen());
+                assert_eq!(position.gen_hash(), original.gen_hash());
+                assert_eq!(position.promoted, original.promoted);
+                assert_eq!(position.piece_set.white, original.piece_set.white);
+                assert_eq!(position.piece_set.black, original.piece_set.black);
+                assert_eq!(position.piece_set.all, original.piece_set.all);
+                assert_eq!(position.piece_set.white_king, original.piece_set.white_king);
+                assert_eq!(position.piece_set.black_king, original.piece_set.black_king);
+            }
+        }
+    }
+
+    #[test]
+    fn finds_no_move_when_mated() {
+        let result = search("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", depth(2));
+
+        assert!(result.moves.is_empty());
+        assert!(result.score >= 30000);
+    }
+
+    #[test]
+    fn finds_no_move_in_stalemate() {
+        let result = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", depth(2));
+
+        assert!(result.moves.is_empty());
+        assert_eq!(result.score, 0);
+    }
+
+    #[test]
+    fn finds_a_mate_in_one() {
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", depth(3));
+
+        assert_eq!(result.moves[0].move_to_uci(false), "a1a8");
+        assert!(result.score >= 30000);
+    }
+
+    #[test]
+    fn ponders_until_ponderhit() {
+        let engine = Engine::new();
+        let searcher = engine.searcher();
+        let limits = SearchLimits {
+            movetime: Some(100),
+            ponder: true,
+            ..Default::default()
+        };
+
+        let search = thread::spawn(move || {
+            searcher.search(Position::startpos(), &limits, &info::SilentInfo)
+        });
+
+        // the movetime only counts from the ponderhit
+        thread::sleep(Duration::from_millis(400));
+        assert!(!search.is_finished());
+
+        let hit = Instant::now();
+        engine.ponder_hit.store(true, Ordering::Relaxed);
+        let result = search.join().unwrap();
+
+        assert!(hit.elapsed() < Duration::from_millis(1000));
+        assert!(!result.moves.is_empty());
+    }
+
+    #[test]
+    fn holds_a_finished_ponder_search_until_stopped() {
+        let engine = Engine::new();
+        let searcher = engine.searcher();
+        let limits = SearchLimits {
+            depth: Some(2),
+            ponder: true,
+            ..Default::default()
+        };
+
+        let search = thread::spawn(move || {
+            searcher.search(Position::startpos(), &limits, &info::SilentInfo)
+        });
+
+        thread::sleep(Duration::from_millis(300));
+        assert!(!search.is_finished());
+
+        engine.stop.stop();
+        assert!(!search.join().unwrap().moves.is_empty());
+    }
+
+    #[test]
+    fn rebuilds_the_pv_from_the_hash_table() {
+        let engine = Engine::new();
+        let limits = depth(4);
+
+        let first = engine
+            .searcher()
+            .search(Position::startpos(), &limits, &info::SilentInfo);
+        // answered from the hash table at the root this time
+        let second = engine
+            .searcher()
+            .search(Position::startpos(), &limits, &info::SilentInfo);
+
+        assert!(first.moves.len() >= 3);
+        assert!(second.moves.len() >= 3);
+        assert_eq!(legal_pv(&second.moves, &Position::startpos()), second.moves);
+    }
+
+    #[test]
+    fn keeps_to_search_moves() {
+        let position = Position::startpos();
+        let search_moves = vec![
+            parse_legal_move(&position, "a2a3", false).unwrap(),
+            parse_legal_move(&position, "h2h3", false).unwrap(),
+        ];
+        let result = search(
+            &position.to_fen(),
+            SearchLimits {
+                depth: Some(3),
+                search_moves: search_moves.clone(),
+                ..Default::default()
+            },
+        );
+
+        assert!(search_moves.contains(&result.moves[0]));
+    }
+}
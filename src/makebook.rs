@@ -0,0 +1,354 @@
+// Builds a Polyglot opening book from PGN game collections.
+//
+// usage: endgame2 makebook <output.bin> <games.pgn>... [maxply N] [mingames N]
+//        [minscore N] [minelo N]
+
+use crate::pgn::{read_pgn_file, Game};
+use crate::polyglot::{encode_move, polyglot_key};
+use crate::{execute_halfmove, Color, Position};
+use hashbrown::HashMap;
+use std::fs;
+
+struct MakebookOptions {
+    output: String,
+    inputs: Vec<String>,
+    max_ply: usize,
+    min_games: u32,
+    // percentage of points scored by the side playing the move
+    min_score: f64,
+    min_elo: u32,
+}
+
+#[derive(Default)]
+struct MoveStats {
+    games: u32,
+    wins: u32,
+    draws: u32,
+}
+
+pub fn makebook_command(args: &[String]) {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Error - {}", e);
+            println!(
+                "usage: makebook <output.bin> <games.pgn>... [maxply N] [mingames N] [minscore N] [minelo N]"
+            );
+            return;
+        }
+    };
+
+    let mut stats: HashMap<(u64, u16), MoveStats> = HashMap::new();
+    let mut game_count = 0;
+    let mut skipped_count = 0;
+
+    for input in options.inputs.iter() {
+        let games = match read_pgn_file(input) {
+            Ok(games) => games,
+            Err(e) => {
+                println!("Error - {}", e);
+                continue;
+            }
+        };
+
+        for game in games.iter() {
+            match game {
+                Ok(game) if add_game(game, &options, &mut stats) => game_count += 1,
+                _ => skipped_count += 1,
+            }
+        }
+    }
+
+    let entries = build_entries(&stats, &options);
+
+    if let Err(e) = write_book(&options.output, &entries) {
+        println!("Error - could not write {}: {}", options.output, e);
+        return;
+    }
+
+    println!(
+        "Games: {} (skipped {})\nPositions: {}\nEntries: {}\nWritten to {}",
+        game_count,
+        skipped_count,
+        stats.len(),
+        entries.len(),
+        options.output
+    );
+}
+
+// entries must already be sorted by key
+fn write_book(path: &str, entries: &[(u64, u16, u16)]) -> std::io::Result<()> {
+    let mut bytes = Vec::with_capacity(entries.len() * 16);
+    for (key, halfmove, weight) in entries.iter() {
+        bytes.extend_from_slice(&key.to_be_bytes());
+        bytes.extend_from_slice(&halfmove.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&0u32.to_be_bytes());
+    }
+
+    return fs::write(path, bytes);
+}
+
+fn parse_options(args: &[String]) -> Result<MakebookOptions, String> {
+    let mut options = MakebookOptions {
+        output: String::new(),
+        inputs: vec![],
+        max_ply: 24,
+        min_games: 1,
+        min_score: 0.0,
+        min_elo: 0,
+    };
+
+    let mut tokens = args.iter();
+
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "maxply" | "mingames" | "minscore" | "minelo" => {
+                let value = tokens
+                    .next()
+                    .ok_or(format!("missing value for {}", token))?;
+
+                let parsed = value
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid value for {}: {}", token, value))?;
+
+                match token.as_str() {
+                    "maxply" => options.max_ply = parsed as usize,
+                    "mingames" => options.min_games = parsed,
+                    "minscore" => options.min_score = parsed as f64,
+                    _ => options.min_elo = parsed,
+                }
+            }
+            _ => {
+                if options.output.is_empty() {
+                    options.output = token.clone();
+                } else {
+                    options.inputs.push(token.clone());
+                }
+            }
+        }
+    }
+
+    if options.output.is_empty() {
+        return Err(String::from("no output file given"));
+    }
+    if options.inputs.is_empty() {
+        return Err(String::from("no PGN files given"));
+    }
+
+    return Ok(options);
+}
+
+// returns false if the game could not be used
+fn add_game(
+    game: &Game,
+    options: &MakebookOptions,
+    stats: &mut HashMap<(u64, u16), MoveStats>,
+) -> bool {
+    // books are built from the standard starting position only
+    if game.tag("FEN").is_some() {
+        return false;
+    }
+    let mut position = Position::startpos();
+
+    let winner = match game.result() {
+        "1-0" => Some(Color::White),
+        "0-1" => Some(Color::Black),
+        "1/2-1/2" => None,
+        _ => return false,
+    };
+
+    let elo = |tag: &str| {
+        game.tag(tag)
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(0)
+    };
+    let white_elo = elo("WhiteElo");
+    let black_elo = elo("BlackElo");
+
+    for halfmove in game.mainline().into_iter().take(options.max_ply) {
+        let mover = position.move_next;
+        let mover_elo = if mover == Color::White {
+            white_elo
+        } else {
+            black_elo
+        };
+
+        if mover_elo >= options.min_elo {
+            let entry = stats
+                .entry((polyglot_key(&position), encode_move(halfmove)))
+                .or_default();
+
+            entry.games += 1;
+            match winner {
+                Some(color) if color == mover => entry.wins += 1,
+                None => entry.draws += 1,
+                _ => {}
+            }
+        }
+
+        execute_halfmove(&mut position, halfmove);
+    }
+
+    return true;
+}
+
+fn build_entries(
+    stats: &HashMap<(u64, u16), MoveStats>,
+    options: &MakebookOptions,
+) -> Vec<(u64, u16, u16)> {
+    let mut by_key: HashMap<u64, Vec<(u16, u32)>> = HashMap::new();
+
+    for (&(key, halfmove), move_stats) in stats.iter() {
+        let score = 100.0 * (move_stats.wins as f64 + move_stats.draws as f64 / 2.0)
+            / move_stats.games as f64;
+
+        if move_stats.games < options.min_games || score < options.min_score {
+            continue;
+        }
+
+        // polyglot weights count half points
+        let weight = 2 * move_stats.wins + move_stats.draws;
+        if weight == 0 {
+            continue;
+        }
+
+        by_key.entry(key).or_default().push((halfmove, weight));
+    }
+
+    let mut entries = vec![];
+
+    for (key, moves) in by_key.into_iter() {
+        let max_weight = moves.iter().map(|&(_, weight)| weight).max().unwrap_or(0);
+
+        for (halfmove, weight) in moves {
+            // scale down so the heaviest move still fits in 16 bits
+            let scaled = if max_weight > u16::MAX as u32 {
+                ((weight as u64 * u16::MAX as u64) / max_weight as u64).max(1) as u16
+            } else {
+                weight as u16
+            };
+
+            entries.push((key, halfmove, scaled));
+        }
+    }
+
+    entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)));
+
+    return entries;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_legal_move;
+    use crate::pgn::parse_pgn;
+    use crate::polyglot::{decode_move, Book};
+
+    const GAMES: &str = "[WhiteElo \"2000\"]\n[BlackElo \"1500\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n\
+                         [WhiteElo \"2000\"]\n[BlackElo \"2000\"]\n[Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2\n\n\
+                         [WhiteElo \"1500\"]\n[BlackElo \"1500\"]\n[Result \"1/2-1/2\"]\n\n1. d4 d5 1/2-1/2\n";
+
+    // the book entries for the test games, with makebook arguments after the file names
+    fn book_entries(args: &[&str]) -> Vec<(u64, u16, u16)> {
+        let args: Vec<String> = ["book.bin", "games.pgn"]
+            .iter()
+            .chain(args)
+            .map(|arg| arg.to_string())
+            .collect();
+        let options = parse_options(&args).unwrap();
+
+        let mut stats = HashMap::new();
+        for game in parse_pgn(GAMES) {
+            assert!(add_game(&game.unwrap(), &options, &mut stats));
+        }
+
+        return build_entries(&stats, &options);
+    }
+
+    fn weight_of(entries: &[(u64, u16, u16)], moves: &[&str], uci: &str) -> Option<u16> {
+        let mut position = Position::startpos();
+        for uci in moves {
+            let halfmove = parse_legal_move(&position, uci, false).unwrap();
+            execute_halfmove(&mut position, halfmove);
+        }
+        let halfmove = encode_move(parse_legal_move(&position, uci, false).unwrap());
+
+        return entries
+            .iter()
+            .find(|&&(key, entry_move, _)| key == polyglot_key(&position) && entry_move == halfmove)
+            .map(|&(_, _, weight)| weight);
+    }
+
+    #[test]
+    fn writes_a_readable_book() {
+        let entries = book_entries(&[]);
+        let path =
+            std::env::temp_dir().join(format!("endgame2-makebook-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        write_book(path, &entries).unwrap();
+        let book = Book::open(path).unwrap();
+        let bytes = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        // e5 lost its only game, so there is nothing to play it for
+        assert_eq!(book.entry_count(), 5);
+
+        let keys: Vec<u64> = bytes
+            .chunks_exact(16)
+            .map(|chunk| u64::from_be_bytes(chunk[0..8].try_into().unwrap()))
+            .collect();
+        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // heaviest move first
+        let position = Position::startpos();
+        let moves: Vec<(String, u16)> = book
+            .entries_for(polyglot_key(&position))
+            .iter()
+            .map(|entry| {
+                let halfmove = decode_move(&position, entry.halfmove).unwrap();
+                (halfmove.move_to_uci(false), entry.weight)
+            })
+            .collect();
+        assert_eq!(
+            moves,
+            [(String::from("e2e4"), 3), (String::from("d2d4"), 1)]
+        );
+    }
+
+    #[test]
+    fn weighs_moves_by_half_points() {
+        let entries = book_entries(&[]);
+
+        // a win and a draw, a draw, a win
+        assert_eq!(weight_of(&entries, &[], "e2e4"), Some(3));
+        assert_eq!(weight_of(&entries, &["e2e4"], "c7c5"), Some(1));
+        assert_eq!(weight_of(&entries, &["e2e4", "e7e5"], "g1f3"), Some(2));
+        assert_eq!(weight_of(&entries, &["e2e4"], "e7e5"), None);
+    }
+
+    #[test]
+    fn filters_moves() {
+        // only e4 was played twice
+        let entries = book_entries(&["mingames", "2"]);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(weight_of(&entries, &[], "e2e4"), Some(3));
+
+        // the draws score 50%
+        let entries = book_entries(&["minscore", "60"]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(weight_of(&entries, &["e2e4", "e7e5"], "g1f3"), Some(2));
+
+        // only the moves of the 2000 players
+        let entries = book_entries(&["minelo", "1800"]);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(weight_of(&entries, &[], "d2d4"), None);
+        assert_eq!(weight_of(&entries, &["d2d4"], "d7d5"), None);
+
+        // white's first moves only
+        let entries = book_entries(&["maxply", "1"]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(weight_of(&entries, &[], "d2d4"), Some(1));
+    }
+}
@@ -0,0 +1,441 @@
+// Mate search for go mate N. Mates are searched by distance, one move at a time, so
+// the first one proven is also the shortest. Each distance is tried first with only
+// checking moves for the attacker, which finds most forced mates quickly, and then
+// with every move so quiet mating moves aren't missed.
+//
+// Every distance repeats the work of the shorter ones, so a table keeps what was
+// learned about each position: how many moves it was shown to hold out against,
+// and the move that mated from it or escaped. Attacking moves are tried with the
+// fewest replies first, as those take the fewest lines to prove, the same measure
+// proof-number search starts from.
+//
+// The search keeps to the time and node limits of the go command the way the normal
+// search does, checking the clock every so many nodes and stopping as soon as they run
+// out, so the search that follows a failed mate search still gets to move in time.
+
+use crate::info::{InfoSink, Score, SearchInfo};
+use crate::rules::win_for;
+use crate::tt::Bound;
+use crate::{
+    execute_halfmove, gen_legal_moves, is_in_check, HalfMove, Position, SearchResult, Searcher,
+    LIMIT_CHECK_INTERVAL,
+};
+use hashbrown::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// about 100 MB of entries, the table starts over when full
+const MAX_ENTRIES: usize = 1 << 22;
+
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    // no mate within this many moves, with every move and with checks only
+    no_mate_within: [usize; 2],
+    // the move that mated from here, or the reply that escaped
+    best: Option<HalfMove>,
+}
+
+struct MateSearch {
+    stop: Arc<AtomicBool>,
+    // the clock is only set once a ponder search gets its ponderhit
+    time_stop: Option<Instant>,
+    time_budget: Option<Duration>,
+    ponder_hit: Arc<AtomicBool>,
+    pondering: bool,
+    node_stop: Option<usize>,
+    // whether a limit was hit at the last check
+    limit_reached: bool,
+    nodes: usize,
+    table: HashMap<u64, Entry>,
+}
+
+// what the mate search may spend of a go command's limits
+pub struct MateLimits {
+    pub time_budget: Option<Duration>,
+    pub node_stop: Option<usize>,
+    pub ponder: bool,
+}
+
+// returns none if no mate within mate_in moves was found, or none was before a limit
+pub fn go_mate(
+    position: &Position,
+    mate_in: usize,
+    search_moves: &[HalfMove],
+    limits: &MateLimits,
+    searcher: &Searcher,
+    sink: &dyn InfoSink,
+) -> Option<SearchResult> {
+    let mut search = MateSearch::new(
+        Arc::clone(&searcher.stop.flag),
+        limits,
+        Arc::clone(&searcher.ponder_hit),
+    );
+    let start_time = Instant::now();
+
+    for moves in 1..=mate_in {
+        for checks_only in [true, false] {
+            let Some(line) = search.attack(position, moves, checks_only, search_moves) else {
+                continue;
+            };
+
+            let elapsed = start_time.elapsed().as_millis();
+            sink.info(&SearchInfo {
+                depth: moves * 2 - 1,
+                seldepth: line.len(),
+                time: elapsed,
+                nodes: search.nodes,
+                hashfull: search.table.len() * 1000 / MAX_ENTRIES,
+                tbhits: 0,
+                score: Score::Mate(moves as i32),
+                bound: Bound::Exact,
+                pv: line.clone(),
+            });
+            sink.bestmove(Some(line[0]), line.get(1).copied());
+
+            return Some(SearchResult {
+                score: win_for(position.move_next),
+                depth: moves * 2 - 1,
+                nodes: search.nodes,
+                iterations: vec![(elapsed, line[0])],
+                moves: line,
+            });
+        }
+
+        if search.is_interrupted() {
+            break;
+        }
+    }
+
+    sink.message(&format!("no mate in {} found", mate_in));
+    return None;
+}
+
+impl MateSearch {
+    fn new(stop: Arc<AtomicBool>, limits: &MateLimits, ponder_hit: Arc<AtomicBool>) -> Self {
+        let time_stop = if limits.ponder {
+            None
+        } else {
+            limits.time_budget.map(|budget| Instant::now() + budget)
+        };
+
+        return Self {
+            stop,
+            time_stop,
+            time_budget: limits.time_budget,
+            ponder_hit,
+            pondering: limits.ponder,
+            node_stop: limits.node_stop,
+            limit_reached: false,
+            nodes: 0,
+            table: HashMap::new(),
+        };
+    }
+
+    // counts a node, and every so often checks the clock and the node limit
+    fn visit(&mut self) {
+        self.nodes += 1;
+
+        if self.nodes % LIMIT_CHECK_INTERVAL == 0 {
+            // a ponderhit starts the clock
+            if self.pondering && self.ponder_hit.load(Ordering::Relaxed) {
+                self.pondering = false;
+                self.time_stop = self.time_budget.map(|budget| Instant::now() + budget);
+            }
+
+            self.limit_reached = self.time_stop.is_some_and(|time| time <= Instant::now())
+                || self.node_stop.is_some_and(|limit| limit <= self.nodes);
+        }
+    }
+
+    fn is_interrupted(&self) -> bool {
+        return self.limit_reached || self.stop.load(Ordering::Relaxed);
+    }
+
+    fn no_mate_known(&self, hash: u64, moves: usize, checks_only: bool) -> bool {
+        return self.table.get(&hash).is_some_and(|entry| {
+            entry.no_mate_within[0] >= moves || entry.no_mate_within[checks_only as usize] >= moves
+        });
+    }
+
+    fn store(&mut self, hash: u64, no_mate_within: Option<(usize, bool)>, best: Option<HalfMove>) {
+        if self.table.len() >= MAX_ENTRIES {
+            self.table.clear();
+        }
+
+        let entry = self.table.entry(hash).or_default();
+        if let Some((moves, checks_only)) = no_mate_within {
+            let known = &mut entry.no_mate_within[checks_only as usize];
+            *known = (*known).max(moves);
+        }
+        if best.is_some() {
+            entry.best = best;
+        }
+    }
+
+    // the side to move mates within the given number of moves, returns the mating line
+    fn attack(
+        &mut self,
+        position: &Position,
+        moves: usize,
+        checks_only: bool,
+        root_moves: &[HalfMove],
+    ) -> Option<Vec<HalfMove>> {
+        // what holds for some root moves says nothing about the position
+        let hash = position.gen_hash();
+        let restricted = !root_moves.is_empty();
+        if !restricted && self.no_mate_known(hash, moves, checks_only) {
+            return None;
+        }
+
+        let mut candidates = vec![];
+
+        for halfmove in gen_legal_moves(position) {
+            if restricted && !root_moves.contains(&halfmove) {
+                continue;
+            }
+
+            let mut new_pos = position.clone();
+            execute_halfmove(&mut new_pos, halfmove);
+            self.visit();
+
+            // a mating move always gives check or wins by a variant rule, so quiet moves
+            // only matter earlier on
+            let gives_check = is_in_check(&new_pos)
+                || new_pos.variant.rules().game_result(&new_pos)
+                    == Some(win_for(position.move_next));
+            if gives_check || (!checks_only && moves > 1) {
+                let replies = gen_legal_moves(&new_pos);
+                candidates.push((halfmove, new_pos, replies, gives_check));
+            }
+        }
+
+        // the move that mated before, then checks, then the fewest replies
+        let best = self.table.get(&hash).and_then(|entry| entry.best);
+        candidates.sort_by_key(|(halfmove, _, replies, gives_check)| {
+            (Some(*halfmove) != best, !gives_check, replies.len())
+        });
+
+        for (halfmove, new_pos, replies, _) in candidates {
+            if self.is_interrupted() {
+                return None;
+            }
+
+            if let Some(mut line) = self.defend(&new_pos, replies, moves, checks_only) {
+                line.insert(0, halfmove);
+                self.store(hash, None, Some(halfmove));
+                return Some(line);
+            }
+        }
+
+        if !restricted && !self.is_interrupted() {
+            self.store(hash, Some((moves, checks_only)), None);
+        }
+
+        return None;
+    }
+
+    // every reply of the side to move loses within moves - 1 attacking moves. returns
+    // the line of the longest defence
+    fn defend(
+        &mut self,
+        position: &Position,
+        mut replies: Vec<HalfMove>,
+        moves: usize,
+        checks_only: bool,
+    ) -> Option<Vec<HalfMove>> {
+        if replies.is_empty() {
+            // checkmate or a variant win, or stalemate which is no mate at all
+            let rules = position.variant.rules();
+            let mated = match rules
+                .game_result(position)
+                .or(rules.no_moves_result(position))
+            {
+                Some(score) => score == win_for(position.move_next.opposite()),
+                None => is_in_check(position),
+            };
+
+            return if mated { Some(vec![]) } else { None };
+        }
+
+        if moves == 1 {
+            return None;
+        }
+
+        let hash = position.gen_hash();
+        if self.no_mate_known(hash, moves, checks_only) {
+            return None;
+        }
+
+        // the reply that escaped before is the likeliest to escape again
+        if let Some(escape) = self.table.get(&hash).and_then(|entry| entry.best) {
+            replies.sort_by_key(|&reply| reply != escape);
+        }
+
+        let mut longest: Vec<HalfMove> = vec![];
+
+        for reply in replies {
+            if self.is_interrupted() {
+                return None;
+            }
+
+            let mut new_pos = position.clone();
+            execute_halfmove(&mut new_pos, reply);
+            self.visit();
+
+            let Some(mut line) = self.attack(&new_pos, moves - 1, checks_only, &[]) else {
+                if !self.is_interrupted() {
+                    self.store(hash, Some((moves, checks_only)), Some(reply));
+                }
+                return None;
+            };
+            line.insert(0, reply);
+
+            if line.len() > longest.len() {
+                longest = line;
+            }
+        }
+
+        return Some(longest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info::SilentInfo;
+    use crate::Engine;
+
+    const UNLIMITED: MateLimits = MateLimits {
+        time_budget: None,
+        node_stop: None,
+        ponder: false,
+    };
+
+    fn mate(fen: &str, mate_in: usize, search_moves: &[&str]) -> Option<SearchResult> {
+        return mate_within(fen, mate_in, search_moves, &UNLIMITED);
+    }
+
+    fn mate_within(
+        fen: &str,
+        mate_in: usize,
+        search_moves: &[&str],
+        limits: &MateLimits,
+    ) -> Option<SearchResult> {
+        let position = Position::from_fen(fen).unwrap();
+        let search_moves: Vec<HalfMove> = search_moves
+            .iter()
+            .map(|uci| crate::parse_legal_move(&position, uci, false).unwrap())
+            .collect();
+
+        return go_mate(
+            &position,
+            mate_in,
+            &search_moves,
+            limits,
+            &Engine::new().searcher(),
+            &SilentInfo,
+        );
+    }
+
+    // the line ends in checkmate
+    fn mates(fen: &str, line: &[HalfMove]) -> bool {
+        let mut position = Position::from_fen(fen).unwrap();
+        for &halfmove in line {
+            assert!(gen_legal_moves(&position).contains(&halfmove));
+            execute_halfmove(&mut position, halfmove);
+        }
+
+        return is_in_check(&position) && gen_legal_moves(&position).is_empty();
+    }
+
+    #[test]
+    fn finds_the_shortest_mate() {
+        let fen = "8/R7/8/8/8/8/8/4K1k1 w - - 0 1";
+        let result = mate(fen, 6, &[]).unwrap();
+
+        assert_eq!(result.depth, 7);
+        assert_eq!(result.moves.len(), 7);
+        assert!(mates(fen, &result.moves));
+        assert!(mate(fen, 3, &[]).is_none());
+    }
+
+    #[test]
+    fn finds_quiet_mating_moves() {
+        // 1. Ra6 bxa6 2. b7#
+        let fen = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+        let result = mate(fen, 2, &[]).unwrap();
+
+        assert_eq!(result.moves[0].move_to_uci(false), "a1a6");
+        assert!(mates(fen, &result.moves));
+    }
+
+    #[test]
+    fn proves_long_mates() {
+        // the rook mate takes seven moves from here
+        let fen = "k7/8/8/8/8/8/8/KR6 w - - 0 1";
+        let result = mate(fen, 7, &[]).unwrap();
+
+        assert_eq!(result.moves.len(), 13);
+        assert!(mates(fen, &result.moves));
+    }
+
+    #[test]
+    fn keeps_to_search_moves() {
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+
+        assert!(mate(fen, 1, &["a1a7"]).is_none());
+        assert_eq!(
+            mate(fen, 1, &["a1a8"]).unwrap().moves[0].move_to_uci(false),
+            "a1a8"
+        );
+    }
+
+    #[test]
+    fn tells_stalemate_from_mate() {
+        let mut search = MateSearch::new(
+            Arc::new(AtomicBool::new(false)),
+            &UNLIMITED,
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        let mated = Position::from_fen("k1Q5/8/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(
+            search
+                .defend(&mated, vec![], 1, true)
+                .map(|line| line.len()),
+            Some(0)
+        );
+
+        let stalemate = Position::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(search.defend(&stalemate, vec![], 1, false).is_none());
+
+        assert!(mate(&Position::startpos().to_fen(), 2, &[]).is_none());
+    }
+
+    #[test]
+    fn keeps_to_the_limits() {
+        let fen = Position::startpos().to_fen();
+
+        let start = Instant::now();
+        let limits = MateLimits {
+            time_budget: Some(Duration::from_millis(200)),
+            ..UNLIMITED
+        };
+        assert!(mate_within(&fen, 6, &[], &limits).is_none());
+        assert!(start.elapsed() < Duration::from_millis(1000));
+
+        let position = Position::startpos();
+        let limits = MateLimits {
+            node_stop: Some(5000),
+            ..UNLIMITED
+        };
+        let mut search = MateSearch::new(
+            Arc::new(AtomicBool::new(false)),
+            &limits,
+            Arc::new(AtomicBool::new(false)),
+        );
+        assert!(search.attack(&position, 6, false, &[]).is_none());
+        assert!(search.nodes < 5000 + LIMIT_CHECK_INTERVAL);
+    }
+}
@@ -0,0 +1,235 @@
+// Perft regression suite. Counts the legal move paths of a fixed set of standard,
+// chess960 and variant positions and compares them with their known results, so move
+// generation bugs show up as a wrong count.
+//
+// usage: perft [depth]
+
+use crate::{execute_halfmove, gen_legal_moves, Position, Variant};
+use std::process;
+use std::time::Instant;
+
+const DEFAULT_DEPTH: usize = 3;
+
+// variant, fen and the node counts from depth 1 on
+const PERFT_SUITE: [(Variant, &str, &[u64]); 23] = [
+    (
+        Variant::Standard,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902, 197281, 4865609],
+    ),
+    (
+        Variant::Standard,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[48, 2039, 97862, 4085603],
+    ),
+    (
+        Variant::Standard,
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[14, 191, 2812, 43238, 674624],
+    ),
+    (
+        Variant::Standard,
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[6, 264, 9467, 422333],
+    ),
+    (
+        Variant::Standard,
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[44, 1486, 62379, 2103487],
+    ),
+    (
+        Variant::Standard,
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        &[46, 2079, 89890, 3894594],
+    ),
+    // chess960
+    (
+        Variant::Standard,
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        &[21, 528, 12189, 326672],
+    ),
+    (
+        Variant::Standard,
+        "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        &[21, 807, 18002, 667366],
+    ),
+    (
+        Variant::Standard,
+        "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        &[20, 479, 10471, 273318],
+    ),
+    (
+        Variant::Standard,
+        "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+        &[22, 593, 13440, 382958],
+    ),
+    (
+        Variant::Standard,
+        "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+        &[28, 1120, 31058, 1171749],
+    ),
+    (
+        Variant::Atomic,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902, 197326],
+    ),
+    (
+        Variant::Atomic,
+        "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1",
+        &[40, 1238, 45237, 1434825],
+    ),
+    (
+        Variant::Atomic,
+        "rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1",
+        &[28, 833, 23353, 714499],
+    ),
+    // king of the hill: the start position is published with the standard counts, since
+    // no king reaches the centre in five plies. the second one puts both kings next to it
+    // and is checked against shakmaty's king of the hill move generator
+    (
+        Variant::KingOfTheHill,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902, 197281, 4865609],
+    ),
+    (
+        Variant::KingOfTheHill,
+        "rnbq1bnr/ppp2ppp/3k4/4p3/3P4/4K3/PPP2PPP/RNBQ1BNR w - - 0 6",
+        &[38, 1320, 46003, 1564678, 53736037],
+    ),
+    (
+        Variant::ThreeCheck,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 1+1 0 1",
+        &[48, 2039, 97848, 4081798],
+    ),
+    // crazyhouse
+    (
+        Variant::Crazyhouse,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+        &[20, 400, 8902, 197281, 4888832],
+    ),
+    (
+        Variant::Crazyhouse,
+        "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1",
+        &[301, 75353],
+    ),
+    (
+        Variant::Crazyhouse,
+        "2k5/8/8/8/8/8/8/4K3[Qn] w - - 0 1",
+        &[67, 3083, 88634],
+    ),
+    // antichess
+    (
+        Variant::Antichess,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+        &[20, 400, 8067, 153299, 2732672],
+    ),
+    (
+        Variant::Antichess,
+        "8/1p6/8/8/8/8/P7/8 w - - 0 1",
+        &[2, 4, 4, 3, 1, 0],
+    ),
+    (
+        Variant::Antichess,
+        "8/2p5/8/8/8/8/P7/8 w - - 0 1",
+        &[2, 4, 4, 4, 4, 4, 4, 4, 12, 36, 312, 2557, 30873],
+    ),
+];
+
+pub fn perft_suite_command(args: &[String]) {
+    let depth = match args.first().map(|arg| arg.parse::<usize>()) {
+        None => DEFAULT_DEPTH,
+        Some(Ok(depth)) if depth >= 1 => depth,
+        _ => {
+            println!("Error - perft depth must be a positive number: perft [depth]");
+            return;
+        }
+    };
+
+    let mut passed = 0;
+    let mut total_nodes = 0;
+    let start_time = Instant::now();
+
+    for (variant, fen, counts) in PERFT_SUITE.iter() {
+        // positions without a known count that deep are checked as far as possible
+        let depth = depth.min(counts.len());
+        let position = Position::from_variant_fen(fen, *variant).unwrap();
+
+        let nodes = perft(&position, depth);
+        let expected = counts[depth - 1];
+        total_nodes += nodes;
+
+        if nodes == expected {
+            passed += 1;
+            println!("ok      depth {} {:>10}  {}", depth, nodes, fen);
+        } else {
+            println!(
+                "failed  depth {} {:>10}  {} (expected {})",
+                depth, nodes, fen, expected
+            );
+        }
+    }
+
+    println!();
+    println!(
+        "Passed {}/{}, {} nodes in {} ms",
+        passed,
+        PERFT_SUITE.len(),
+        total_nodes,
+        start_time.elapsed().as_millis()
+    );
+
+    if passed < PERFT_SUITE.len() {
+        process::exit(1);
+    }
+}
+
+pub fn perft(position: &Position, depth: usize) -> u64 {
+    let moves = gen_legal_moves(position);
+
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    return moves
+        .into_iter()
+        .map(|halfmove| {
+            let mut new_pos = position.clone();
+            execute_halfmove(&mut new_pos, halfmove);
+            perft(&new_pos, depth - 1)
+        })
+        .sum();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deep enough for castling, promotions and drops, shallow enough for a debug build
+    const TEST_DEPTH: usize = 3;
+
+    #[test]
+    fn passes_the_suite() {
+        for (variant, fen, counts) in PERFT_SUITE.iter() {
+            let depth = TEST_DEPTH.min(counts.len());
+            let position = Position::from_variant_fen(fen, *variant).unwrap();
+
+            assert_eq!(perft(&position, depth), counts[depth - 1], "{}", fen);
+        }
+    }
+
+    #[test]
+    fn ends_on_the_hill() {
+        let fen = "rnbq1bnr/ppp2ppp/3k4/4p3/3P4/4K3/PPP2PPP/RNBQ1BNR w - - 0 6";
+        let koth = Position::from_variant_fen(fen, Variant::KingOfTheHill).unwrap();
+        let standard = Position::from_variant_fen(fen, Variant::Standard).unwrap();
+
+        // Ke4 wins on the spot, so only its 35 replies are missing
+        assert_eq!(perft(&koth, 1), perft(&standard, 1));
+        assert_eq!(perft(&koth, 2) + 35, perft(&standard, 2));
+
+        // nothing is left to play once a king stands on the hill
+        let fen = "rnbq1bnr/ppp2ppp/3k4/4p3/3PK3/8/PPP2PPP/RNBQ1BNR b - - 1 6";
+        let position = Position::from_variant_fen(fen, Variant::KingOfTheHill).unwrap();
+        assert_eq!(perft(&position, 1), 0);
+    }
+}
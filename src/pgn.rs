@@ -0,0 +1,682 @@
+// PGN reading and writing. A game is stored as a tree of moves, where the first
+// child of each node continues the main line and any further children are variations.
+
+use crate::{execute_halfmove, Color, HalfMove, Position};
+use std::fs;
+
+// suffix annotations and the NAGs they stand for
+const SUFFIX_NAGS: [(&str, u8); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+const MAX_LINE_LENGTH: usize = 80;
+
+pub struct GameNode {
+    // none for the root node
+    pub halfmove: Option<HalfMove>,
+    pub parent: usize,
+    pub children: Vec<usize>,
+    pub nags: Vec<u8>,
+    // comment placed before the first move of a variation
+    pub starting_comment: Option<String>,
+    pub comment: Option<String>,
+}
+
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub nodes: Vec<GameNode>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+    Move(String),
+}
+
+impl GameNode {
+    fn new(parent: usize, halfmove: Option<HalfMove>) -> Self {
+        Self {
+            halfmove,
+            parent,
+            children: vec![],
+            nags: vec![],
+            starting_comment: None,
+            comment: None,
+        }
+    }
+}
+
+impl Game {
+    fn empty() -> Self {
+        Self {
+            tags: vec![],
+            nodes: vec![GameNode::new(0, None)],
+        }
+    }
+
+    // a game with the seven tag roster filled in with unknown values
+    pub fn new() -> Self {
+        let mut game = Game::empty();
+
+        for name in ["Event", "Site", "Date", "Round", "White", "Black"] {
+            game.set_tag(name, "?");
+        }
+        game.set_tag("Date", "????.??.??");
+        game.set_tag("Result", "*");
+
+        return game;
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self
+            .tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str());
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn result(&self) -> &str {
+        return self.tag("Result").unwrap_or("*");
+    }
+
+    pub fn start_position(&self) -> Result<Position, String> {
+        return match self.tag("FEN") {
+            Some(fen) => Position::from_fen(fen).map_err(|e| format!("invalid FEN tag: {}", e)),
+            None => Ok(Position::startpos()),
+        };
+    }
+
+    // adds a move below parent, reusing the existing node if the move is already there
+    pub fn add_move(&mut self, parent: usize, halfmove: HalfMove) -> usize {
+        if let Some(&existing) = self.nodes[parent]
+            .children
+            .iter()
+            .find(|&&child| self.nodes[child].halfmove == Some(halfmove))
+        {
+            return existing;
+        }
+
+        self.nodes.push(GameNode::new(parent, Some(halfmove)));
+        let index = self.nodes.len() - 1;
+        self.nodes[parent].children.push(index);
+
+        return index;
+    }
+
+    pub fn mainline(&self) -> Vec<HalfMove> {
+        let mut moves = vec![];
+        let mut node = 0;
+
+        while let Some(&child) = self.nodes[node].children.first() {
+            moves.push(self.nodes[child].halfmove.unwrap());
+            node = child;
+        }
+
+        return moves;
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+
+        for (name, value) in self.tags.iter() {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn += &format!("[{} \"{}\"]\n", name, escaped);
+        }
+        pgn.push('\n');
+
+        let mut tokens = vec![];
+
+        if let Some(comment) = &self.nodes[0].comment {
+            push_comment(&mut tokens, comment);
+        }
+
+        if let Ok(position) = self.start_position() {
+            self.write_line(0, position, &mut tokens, true);
+        }
+
+        tokens.push(self.result().to_string());
+
+        pgn += &wrap_tokens(&tokens);
+        pgn.push('\n');
+
+        return pgn;
+    }
+
+    fn write_line(
+        &self,
+        node: usize,
+        mut position: Position,
+        tokens: &mut Vec<String>,
+        mut force_number: bool,
+    ) {
+        let mut node = node;
+
+        while let Some(&main) = self.nodes[node].children.first() {
+            let commented = self.write_move(main, &position, tokens, force_number);
+
+            for &variation in self.nodes[node].children[1..].iter() {
+                tokens.push(String::from("("));
+
+                let commented = self.write_move(variation, &position, tokens, true);
+                let mut variation_position = position.clone();
+                execute_halfmove(
+                    &mut variation_position,
+                    self.nodes[variation].halfmove.unwrap(),
+                );
+                self.write_line(variation, variation_position, tokens, commented);
+
+                tokens.push(String::from(")"));
+            }
+
+            force_number = commented || self.nodes[node].children.len() > 1;
+
+            execute_halfmove(&mut position, self.nodes[main].halfmove.unwrap());
+            node = main;
+        }
+    }
+
+    // returns true if the move was followed by a comment
+    fn write_move(
+        &self,
+        node: usize,
+        position: &Position,
+        tokens: &mut Vec<String>,
+        force_number: bool,
+    ) -> bool {
+        let game_node = &self.nodes[node];
+
+        if let Some(comment) = &game_node.starting_comment {
+            push_comment(tokens, comment);
+        }
+
+        if position.move_next == Color::White {
+            tokens.push(format!("{}.", position.fullmove_number));
+        } else if force_number || game_node.starting_comment.is_some() {
+            tokens.push(format!("{}...", position.fullmove_number));
+        }
+
+        // pgn doesn't use the e.p. suffix
+        let san = game_node.halfmove.unwrap().move_to_san(position);
+        tokens.push(san.replace(" e.p.", ""));
+
+        for nag in game_node.nags.iter() {
+            tokens.push(format!("${}", nag));
+        }
+
+        if let Some(comment) = &game_node.comment {
+            push_comment(tokens, comment);
+            return true;
+        }
+
+        return false;
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+fn push_comment(tokens: &mut Vec<String>, comment: &str) {
+    let words: Vec<&str> = comment.split_whitespace().collect();
+
+    if words.is_empty() {
+        tokens.push(String::from("{}"));
+        return;
+    }
+
+    for (i, word) in words.iter().enumerate() {
+        let mut token = word.to_string();
+        if i == 0 {
+            token.insert(0, '{');
+        }
+        if i == words.len() - 1 {
+            token.push('}');
+        }
+        tokens.push(token);
+    }
+}
+
+fn wrap_tokens(tokens: &[String]) -> String {
+    // variation brackets are attached to the neighbouring tokens
+    let mut words: Vec<String> = vec![];
+    let mut opening = String::new();
+
+    for token in tokens.iter() {
+        if token == "(" {
+            opening.push('(');
+        } else if token == ")" && !words.is_empty() {
+            words.last_mut().unwrap().push(')');
+        } else {
+            words.push(opening.clone() + token);
+            opening.clear();
+        }
+    }
+
+    let mut text = String::new();
+    let mut line_length = 0;
+
+    for word in words.iter() {
+        if line_length > 0 && line_length + 1 + word.len() > MAX_LINE_LENGTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+
+        text += word;
+        line_length += word.len();
+    }
+
+    return text;
+}
+
+pub fn read_pgn_file(path: &str) -> Result<Vec<Result<Game, String>>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+
+    return Ok(parse_pgn(&String::from_utf8_lossy(&bytes)));
+}
+
+// parses every game in the text. games that fail to parse are returned as errors
+// so the remaining games can still be used
+pub fn parse_pgn(text: &str) -> Vec<Result<Game, String>> {
+    let tokens = tokenize(text);
+    let mut games = vec![];
+    let mut start = 0;
+
+    for i in 0..tokens.len() {
+        let is_last = i == tokens.len() - 1;
+        let is_result = matches!(tokens[i], Token::Result(_));
+        // a tag after movetext means the previous game had no result
+        let tags_follow = !is_last
+            && matches!(tokens[i + 1], Token::Tag(_, _))
+            && !matches!(tokens[i], Token::Tag(_, _));
+
+        if is_result || tags_follow || is_last {
+            let chunk = &tokens[start..=i];
+            start = i + 1;
+
+            if chunk
+                .iter()
+                .any(|token| matches!(token, Token::Tag(_, _) | Token::Move(_)))
+            {
+                games.push(parse_game(chunk));
+            }
+        }
+    }
+
+    return games;
+}
+
+fn parse_game(tokens: &[Token]) -> Result<Game, String> {
+    let mut game = Game::empty();
+
+    for token in tokens.iter() {
+        if let Token::Tag(name, value) = token {
+            game.tags.push((name.clone(), value.clone()));
+        }
+    }
+
+    // positions before each node's children, indexed like game.nodes
+    let mut positions = vec![game.start_position()?];
+    let mut current = 0;
+    let mut variation_stack: Vec<usize> = vec![];
+    let mut pending_comment: Option<String> = None;
+    let mut variation_start = false;
+
+    for token in tokens.iter() {
+        match token {
+            Token::Tag(_, _) => {}
+            Token::Comment(text) => {
+                let comment = if variation_start {
+                    &mut pending_comment
+                } else {
+                    &mut game.nodes[current].comment
+                };
+
+                match comment {
+                    Some(existing) => {
+                        existing.push(' ');
+                        existing.push_str(text);
+                    }
+                    None => *comment = Some(text.clone()),
+                }
+            }
+            Token::Nag(nag) => game.nodes[current].nags.push(*nag),
+            Token::VariationStart => {
+                if current == 0 {
+                    return Err(String::from("variation before the first move"));
+                }
+
+                variation_stack.push(current);
+                current = game.nodes[current].parent;
+                variation_start = true;
+            }
+            Token::VariationEnd => {
+                current = variation_stack
+                    .pop()
+                    .ok_or(String::from("unmatched closing parenthesis"))?;
+                variation_start = false;
+            }
+            Token::Result(result) => game.set_tag("Result", result),
+            Token::Move(text) => {
+                let (san, suffix_nag) = split_suffix(text);
+
+                let halfmove = HalfMove::from_san(&positions[current], san)
+                    .ok_or(format!("illegal or ambiguous move {}", text))?;
+
+                let node = game.add_move(current, halfmove);
+                if node == positions.len() {
+                    let mut position = positions[current].clone();
+                    execute_halfmove(&mut position, halfmove);
+                    positions.push(position);
+                }
+
+                game.nodes[node].starting_comment = pending_comment.take();
+                if let Some(nag) = suffix_nag {
+                    game.nodes[node].nags.push(nag);
+                }
+
+                current = node;
+                variation_start = false;
+            }
+        }
+    }
+
+    if !variation_stack.is_empty() {
+        return Err(String::from("unclosed variation"));
+    }
+
+    return Ok(game);
+}
+
+fn split_suffix(text: &str) -> (&str, Option<u8>) {
+    for (suffix, nag) in SUFFIX_NAGS.iter() {
+        if let Some(san) = text.strip_suffix(suffix) {
+            return (san, Some(*nag));
+        }
+    }
+
+    return (text, None);
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(char) = chars.next() {
+        match char {
+            // escape mechanism, the rest of the line is ignored
+            '%' if line_start => {
+                chars.by_ref().find(|&c| c == '\n');
+                continue;
+            }
+            '[' => {
+                let name: String = chars
+                    .by_ref()
+                    .skip_while(|c| c.is_whitespace())
+                    .take_while(|c| !c.is_whitespace())
+                    .collect();
+
+                chars.by_ref().find(|&c| c == '"');
+
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next()),
+                        '"' => break,
+                        _ => value.push(c),
+                    }
+                }
+
+                chars.by_ref().find(|&c| c == ']');
+                tokens.push(Token::Tag(name, value));
+            }
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Comment(
+                    comment.split_whitespace().collect::<Vec<&str>>().join(" "),
+                ));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                line_start = true;
+                continue;
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            '$' => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                }
+
+                if let Ok(nag) = digits.parse::<u8>() {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut symbol = char.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();[$".contains(c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    chars.next();
+                }
+
+                match symbol.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => tokens.push(Token::Result(symbol)),
+                    _ => {
+                        let san = strip_move_number(&symbol);
+                        if !san.is_empty() {
+                            tokens.push(Token::Move(san.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        line_start = char == '\n';
+    }
+
+    return tokens;
+}
+
+// move numbers such as "12." or "12..." may be attached to the move. the digits only
+// count as one when dots follow, as 0-0 castles
+fn strip_move_number(symbol: &str) -> &str {
+    let rest = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+
+    if rest.is_empty() || rest.starts_with('.') {
+        return rest.trim_start_matches('.');
+    }
+
+    return symbol;
+}
+
+pub fn pgn_command(args: &[String]) {
+    if args.is_empty() {
+        println!("usage: pgn <games.pgn>...");
+        return;
+    }
+
+    for path in args.iter() {
+        let games = match read_pgn_file(path) {
+            Ok(games) => games,
+            Err(e) => {
+                println!("Error - {}", e);
+                continue;
+            }
+        };
+
+        for (i, game) in games.iter().enumerate() {
+            match game {
+                Ok(game) => println!("{}", game.to_pgn()),
+                Err(e) => println!("Error - {} game {}: {}", path, i + 1, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = r#"[Event "Casual \"blitz\""]
+[White "Morphy"]
+[Black "Duke"]
+[Result "1-0"]
+
+{Opera game} 1. e4 e5 2. Nf3 d6 3. d4 Bg4?! (3... exd4 4. Nxd4 $1) 4. dxe5
+; the rest of the line
+Bxf3 5. Qxf3 dxe5 1-0
+"#;
+
+    fn game(text: &str) -> Game {
+        let mut games = parse_pgn(text);
+        assert_eq!(games.len(), 1);
+
+        return games.remove(0).unwrap();
+    }
+
+    fn san_line(game: &Game) -> Vec<String> {
+        let mut position = game.start_position().unwrap();
+        let mut line = vec![];
+
+        for halfmove in game.mainline() {
+            line.push(halfmove.move_to_san(&position));
+            execute_halfmove(&mut position, halfmove);
+        }
+
+        return line;
+    }
+
+    #[test]
+    fn reads_tags_and_moves() {
+        let game = game(GAME);
+
+        assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.tag("White"), Some("Morphy"));
+        assert_eq!(game.tag("Site"), None);
+        assert_eq!(game.result(), "1-0");
+        assert_eq!(
+            san_line(&game),
+            ["e4", "e5", "Nf3", "d6", "d4", "Bg4", "dxe5", "Bxf3", "Qxf3", "dxe5"]
+        );
+    }
+
+    #[test]
+    fn reads_comments_nags_and_variations() {
+        let game = game(GAME);
+
+        assert_eq!(game.nodes[0].comment.as_deref(), Some("Opera game"));
+
+        // 3... Bg4 is the sixth move, with exd4 as its variation
+        let mut node = 0;
+        for _ in 0..5 {
+            node = game.nodes[node].children[0];
+        }
+        assert_eq!(game.nodes[node].children.len(), 2);
+
+        let bg4 = game.nodes[node].children[0];
+        assert_eq!(game.nodes[bg4].nags, [6]);
+
+        let exd4 = game.nodes[node].children[1];
+        let nxd4 = game.nodes[exd4].children[0];
+        assert_eq!(game.nodes[nxd4].nags, [1]);
+        assert!(game.nodes[nxd4].children.is_empty());
+
+        let dxe5 = game.nodes[bg4].children[0];
+        assert_eq!(
+            game.nodes[dxe5].comment.as_deref(),
+            Some("the rest of the line")
+        );
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let pgn = game(GAME).to_pgn();
+
+        assert!(pgn.starts_with("[Event \"Casual \\\"blitz\\\"\"]\n"));
+        assert!(pgn.contains("3. d4 Bg4 $6 (3... exd4 4. Nxd4 $1) 4. dxe5"));
+        assert!(pgn.ends_with("1-0\n"));
+        assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+        let again = game(&pgn);
+        assert_eq!(again.to_pgn(), pgn);
+        assert_eq!(again.mainline(), game(GAME).mainline());
+    }
+
+    #[test]
+    fn starts_from_the_fen_tag() {
+        let game = game("[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 1\"]\n\n1... Kd7 2. O-O-O+ *");
+
+        assert_eq!(san_line(&game), ["Kd7", "O-O-O+"]);
+        assert_eq!(game.result(), "*");
+
+        let pgn = game.to_pgn();
+        assert!(pgn.contains("1... Kd7 2. O-O-O+ *"));
+    }
+
+    #[test]
+    fn reads_castling_written_with_zeros() {
+        let game = game(
+            "1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.0-0 Nf6 5.d3 d6 6.Nc3 Be6 7.Be3 Qd7 8.a3 0-0-0 \
+             9.b4 9...Kb8 *",
+        );
+
+        assert_eq!(
+            san_line(&game)[6..],
+            ["O-O", "Nf6", "d3", "d6", "Nc3", "Be6", "Be3", "Qd7", "a3", "O-O-O", "b4", "Kb8"]
+        );
+    }
+
+    #[test]
+    fn keeps_going_after_a_broken_game() {
+        let text = "[Event \"a\"]\n\n1. e4 e5 1/2-1/2\n\n\
+                    [Event \"b\"]\n\n1. e4 Ke7 2. Ke3 *\n\n\
+                    [Event \"c\"]\n\n1. d4 (1. e4) *\n\n\
+                    [Event \"d\"]\n\n1. Nf3 Nf6";
+        let games = parse_pgn(text);
+
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].as_ref().unwrap().result(), "1/2-1/2");
+        assert!(games[1].is_err());
+        assert_eq!(games[2].as_ref().unwrap().nodes[0].children.len(), 2);
+        assert_eq!(games[3].as_ref().unwrap().mainline().len(), 2);
+    }
+
+    #[test]
+    fn rejects_broken_variations() {
+        assert!(parse_game(&tokenize("(1. e4) *")).is_err());
+        assert!(parse_game(&tokenize("1. e4 ) *")).is_err());
+        assert!(parse_game(&tokenize("1. e4 (1. d4 *")).is_err());
+    }
+}
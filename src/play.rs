@@ -0,0 +1,656 @@
+// A game against the engine in the terminal. Moves are typed in san or coordinates, the
+// board is drawn in color with chess symbols, and the finished game can be saved as pgn.
+//
+// usage: endgame2 play [color white|black] [level 1-10] [tc minutes+seconds] [pgnout FILE]
+//        [plain]
+
+use crate::adjudication::{AdjudicationRules, Adjudicator};
+use crate::info::{InfoSink, Score, SearchInfo, SilentInfo};
+use crate::pgn::Game;
+use crate::{gen_legal_moves, parse_legal_move, piece_to_char};
+use crate::{Color, Engine, HalfMove, Position, SearchLimits};
+use std::fs;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// nodes the engine may search at each level
+const LEVEL_NODES: [usize; 10] = [
+    500, 2000, 8000, 25000, 60000, 150000, 400000, 1000000, 3000000, 10000000,
+];
+const DEFAULT_LEVEL: usize = 7;
+
+const HINT_MOVETIME: u64 = 500;
+
+// ansi colors for the board
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const LAST_MOVE_SQUARE: &str = "\x1b[48;5;143m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+const RESET: &str = "\x1b[0m";
+
+struct PlayOptions {
+    // the side the player has
+    color: Color,
+    level: usize,
+    // base and increment in ms
+    clock: Option<(u64, u64)>,
+    pgn_out: Option<String>,
+    // letters instead of symbols, and no colors
+    plain: bool,
+}
+
+struct PlayGame {
+    engine: Engine,
+    moves: Vec<HalfMove>,
+    adjudicator: Adjudicator,
+    // ms left for white and black
+    clocks: [i64; 2],
+}
+
+#[derive(Debug, PartialEq)]
+enum PlayerInput {
+    Help,
+    Moves,
+    Undo,
+    Hint,
+    Level(usize),
+    Flip,
+    Resign,
+    Quit,
+    Move(HalfMove),
+}
+
+// keeps the last iteration, to show what the engine thought of its move
+struct PlayInfo {
+    last: Mutex<Option<SearchInfo>>,
+}
+
+pub fn play_command(args: &[String]) {
+    let mut options = match parse_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Error - {}", e);
+            println!(
+                "usage: play [color white|black] [level 1-10] [tc minutes+seconds] [pgnout FILE] [plain]"
+            );
+            return;
+        }
+    };
+
+    let mut game = PlayGame::new(&options);
+    let mut lines = io::stdin().lines().map_while(Result::ok);
+
+    println!(
+        "You play {}. Type help for the commands.",
+        color_name(options.color)
+    );
+
+    let (result, reason, termination) = loop {
+        if let Some(end) = game.game_end(&options) {
+            print_board(&game, &options);
+            break end;
+        }
+
+        let to_move = game.engine.position.move_next;
+        if to_move != options.color {
+            game.engine_move(&options);
+            continue;
+        }
+
+        print_board(&game, &options);
+        print!("{}> ", color_name(to_move));
+        let _ = io::stdout().flush();
+
+        let start_time = Instant::now();
+        let Some(input) = lines.next() else {
+            break ("*", String::from("Game abandoned"), "abandoned");
+        };
+
+        if options.clock.is_some() {
+            game.clocks[to_move as usize] -= start_time.elapsed().as_millis() as i64;
+            if game.clock(to_move) < 0 {
+                continue;
+            }
+        }
+
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        let command = match parse_input(&input, &game.engine.position) {
+            Ok(command) => command,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+
+        match command {
+            PlayerInput::Help => print_help(),
+            PlayerInput::Moves => {
+                let position = &game.engine.position;
+                let moves: Vec<String> = gen_legal_moves(position)
+                    .iter()
+                    .map(|halfmove| halfmove.move_to_san(position))
+                    .collect();
+                println!("{}", moves.join(" "));
+            }
+            PlayerInput::Undo => game.take_back(options.color),
+            PlayerInput::Hint => {
+                let limits = SearchLimits {
+                    movetime: Some(HINT_MOVETIME),
+                    ..Default::default()
+                };
+                let position = game.engine.position.clone();
+                let result = game
+                    .engine
+                    .searcher()
+                    .search(position.clone(), &limits, &SilentInfo);
+                println!("Hint: {}", result.moves[0].move_to_san(&position));
+            }
+            PlayerInput::Level(level) => {
+                options.level = level;
+                println!("Level {}", level);
+            }
+            PlayerInput::Flip => options.color = options.color.opposite(),
+            PlayerInput::Resign => {
+                let result = if to_move == Color::White {
+                    "0-1"
+                } else {
+                    "1-0"
+                };
+                break (result, format!("{} resigns", color_name(to_move)), "normal");
+            }
+            PlayerInput::Quit => break ("*", String::from("Game abandoned"), "abandoned"),
+            PlayerInput::Move(halfmove) => {
+                game.play(halfmove);
+                game.add_increment(to_move, &options);
+            }
+        }
+    };
+
+    println!("{} {{{}}}", result, reason);
+
+    save_pgn(&game, &options, result, &reason, termination, &mut lines);
+}
+
+// a line typed by the player, checked against the position. the error is the message
+// to show for a garbled command or an illegal move
+fn parse_input(input: &str, position: &Position) -> Result<PlayerInput, String> {
+    let mut tokens = input.split_whitespace();
+    let Some(command) = tokens.next() else {
+        return Err(String::from("Type help for the commands"));
+    };
+
+    return match command {
+        "help" => Ok(PlayerInput::Help),
+        "moves" => Ok(PlayerInput::Moves),
+        "undo" | "takeback" => Ok(PlayerInput::Undo),
+        "hint" => Ok(PlayerInput::Hint),
+        "level" => tokens
+            .next()
+            .and_then(|level| level.parse::<usize>().ok())
+            .filter(|level| (1..=LEVEL_NODES.len()).contains(level))
+            .map(PlayerInput::Level)
+            .ok_or(format!(
+                "Error - the level goes from 1 to {}",
+                LEVEL_NODES.len()
+            )),
+        "flip" => Ok(PlayerInput::Flip),
+        "resign" => Ok(PlayerInput::Resign),
+        "quit" => Ok(PlayerInput::Quit),
+        // the whole line is the move, so "exd6 e.p." reads but "e4 e5" doesn't
+        _ => parse_legal_move(position, input.trim(), false)
+            .map(PlayerInput::Move)
+            .ok_or(format!(
+                "Illegal move {}, type moves for the legal ones",
+                input.trim()
+            )),
+    };
+}
+
+fn parse_options(args: &[String]) -> Result<PlayOptions, String> {
+    let mut options = PlayOptions {
+        color: Color::White,
+        level: DEFAULT_LEVEL,
+        clock: None,
+        pgn_out: None,
+        plain: false,
+    };
+
+    let mut tokens = args.iter();
+
+    while let Some(token) = tokens.next() {
+        if token == "plain" {
+            options.plain = true;
+            continue;
+        }
+
+        let value = tokens
+            .next()
+            .ok_or(format!("missing value for {}", token))?;
+        let invalid = || format!("invalid value for {}: {}", token, value);
+
+        match token.as_str() {
+            "color" => {
+                options.color = match value.as_str() {
+                    "white" => Color::White,
+                    "black" => Color::Black,
+                    _ => return Err(invalid()),
+                }
+            }
+            "level" => {
+                options.level = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|level| (1..=LEVEL_NODES.len()).contains(level))
+                    .ok_or_else(invalid)?
+            }
+            "tc" => {
+                let (base, increment) = value.split_once('+').unwrap_or((value, "0"));
+                let base = base.parse::<f64>().map_err(|_| invalid())?;
+                let increment = increment.parse::<f64>().map_err(|_| invalid())?;
+
+                options.clock = Some(((base * 60000.0) as u64, (increment * 1000.0) as u64));
+            }
+            "pgnout" => options.pgn_out = Some(value.clone()),
+            _ => return Err(format!("unexpected argument {}", token)),
+        }
+    }
+
+    return Ok(options);
+}
+
+fn print_help() {
+    println!("  e4, Nf3, e2e4  play a move, in san or coordinates");
+    println!("  moves          list the legal moves");
+    println!("  undo           take back your last move");
+    println!("  hint           suggest a move");
+    println!(
+        "  level N        set the engine's strength, from 1 to {}",
+        LEVEL_NODES.len()
+    );
+    println!("  flip           switch sides with the engine");
+    println!("  resign, quit   end the game");
+}
+
+fn color_name(color: Color) -> &'static str {
+    return if color == Color::White {
+        "White"
+    } else {
+        "Black"
+    };
+}
+
+fn lost_on_time(color: Color) -> (&'static str, String, &'static str) {
+    let result = if color == Color::White { "0-1" } else { "1-0" };
+    return (
+        result,
+        format!("{} loses on time", color_name(color)),
+        "time forfeit",
+    );
+}
+
+fn format_clock(ms: i64) -> String {
+    let seconds = ms.max(0) / 1000;
+    return format!("{}:{:02}", seconds / 60, seconds % 60);
+}
+
+// from the player's side, with the squares of the last move marked
+fn print_board(game: &PlayGame, options: &PlayOptions) {
+    let position = &game.engine.position;
+    let last_move = game.moves.last();
+
+    println!();
+    if options.clock.is_some() {
+        println!(
+            "White {}   Black {}",
+            format_clock(game.clock(Color::White)),
+            format_clock(game.clock(Color::Black))
+        );
+    }
+
+    let ranks: Vec<u8> = if options.color == Color::White {
+        (0..8).rev().collect()
+    } else {
+        (0..8).collect()
+    };
+    let files: Vec<u8> = if options.color == Color::White {
+        (0..8).collect()
+    } else {
+        (0..8).rev().collect()
+    };
+
+    for &rank in ranks.iter() {
+        print!("{} ", rank + 1);
+
+        for &file in files.iter() {
+            let square = rank * 8 + file;
+            let piece = position.board[square as usize];
+
+            if options.plain {
+                print!("{} ", piece_to_char(piece, false));
+                continue;
+            }
+
+            let background = if last_move.is_some_and(|m| m.from == square || m.to == square) {
+                LAST_MOVE_SQUARE
+            } else if (rank + file) % 2 == 1 {
+                LIGHT_SQUARE
+            } else {
+                DARK_SQUARE
+            };
+            let symbol = match piece {
+                Some(piece) => piece_to_char(Some(piece), true),
+                None => ' ',
+            };
+            let foreground = match piece.map(|piece| piece.get_color()) {
+                Some(Color::White) => WHITE_PIECE,
+                _ => BLACK_PIECE,
+            };
+
+            print!("{}{} {} {}", background, foreground, symbol, RESET);
+        }
+
+        println!();
+    }
+
+    let file_labels: Vec<String> = files
+        .iter()
+        .map(|&file| ((b'a' + file) as char).to_string())
+        .collect();
+    if options.plain {
+        println!("  {}", file_labels.join(" "));
+    } else {
+        println!("   {}", file_labels.join("  "));
+    }
+}
+
+fn save_pgn(
+    game: &PlayGame,
+    options: &PlayOptions,
+    result: &str,
+    reason: &str,
+    termination: &str,
+    lines: &mut impl Iterator<Item = String>,
+) {
+    if game.moves.is_empty() {
+        return;
+    }
+
+    let path = match &options.pgn_out {
+        Some(path) => path.clone(),
+        None => {
+            print!("Save the game as (empty to skip): ");
+            let _ = io::stdout().flush();
+
+            match lines.next() {
+                Some(path) if !path.trim().is_empty() => path.trim().to_string(),
+                _ => return,
+            }
+        }
+    };
+
+    let mut pgn_game = Game::new();
+    let engine_name = format!("EndGame2 level {}", options.level);
+    let (white, black) = if options.color == Color::White {
+        ("Player", engine_name.as_str())
+    } else {
+        (engine_name.as_str(), "Player")
+    };
+
+    pgn_game.set_tag("Event", "EndGame2 terminal game");
+    pgn_game.set_tag("White", white);
+    pgn_game.set_tag("Black", black);
+    pgn_game.set_tag("Result", result);
+    if let Some((base, increment)) = options.clock {
+        pgn_game.set_tag(
+            "TimeControl",
+            &format!("{}+{}", base as f64 / 1000.0, increment as f64 / 1000.0),
+        );
+    }
+    pgn_game.set_tag("Termination", termination);
+
+    let mut node = 0;
+    for &halfmove in game.moves.iter() {
+        node = pgn_game.add_move(node, halfmove);
+    }
+    pgn_game.nodes[node].comment = Some(reason.to_string());
+
+    match fs::write(&path, pgn_game.to_pgn()) {
+        Ok(()) => println!("Saved to {}", path),
+        Err(e) => println!("Error - could not write {}: {}", path, e),
+    }
+}
+
+impl PlayGame {
+    fn new(options: &PlayOptions) -> Self {
+        let engine = Engine::new();
+        let base = options.clock.map_or(0, |(base, _)| base as i64);
+
+        Self {
+            adjudicator: Adjudicator::new(AdjudicationRules::default(), &engine.position),
+            engine,
+            moves: vec![],
+            clocks: [base; 2],
+        }
+    }
+
+    fn clock(&self, color: Color) -> i64 {
+        return self.clocks[color as usize];
+    }
+
+    // the result, reason and pgn termination once a flag has fallen or the rules end the
+    // game, as with mate, stalemate or a draw
+    fn game_end(&self, options: &PlayOptions) -> Option<(&'static str, String, &'static str)> {
+        if options.clock.is_some() {
+            for color in [Color::White, Color::Black] {
+                if self.clock(color) < 0 {
+                    return Some(lost_on_time(color));
+                }
+            }
+        }
+
+        return self
+            .adjudicator
+            .check(&self.engine.position)
+            .map(|adjudication| {
+                (
+                    adjudication.result,
+                    adjudication.reason,
+                    adjudication.termination,
+                )
+            });
+    }
+
+    fn play(&mut self, halfmove: HalfMove) {
+        self.engine.play(halfmove);
+        self.adjudicator.add_move(&self.engine.position, None);
+        self.moves.push(halfmove);
+    }
+
+    fn add_increment(&mut self, color: Color, options: &PlayOptions) {
+        if let Some((_, increment)) = options.clock {
+            self.clocks[color as usize] += increment as i64;
+        }
+    }
+
+    // back to the player's last turn. the clocks stay as they are
+    fn take_back(&mut self, color: Color) {
+        let player_turn = |played: usize| (played % 2 == 0) == (color == Color::White);
+        let Some(target) = (0..self.moves.len())
+            .rev()
+            .find(|&played| player_turn(played))
+        else {
+            println!("Nothing to take back");
+            return;
+        };
+
+        let mut moves = self.moves.clone();
+        moves.truncate(target);
+
+        let position = Position::startpos();
+        self.engine.set_position(position.clone());
+        self.adjudicator = Adjudicator::new(AdjudicationRules::default(), &position);
+        self.moves.clear();
+        for halfmove in moves {
+            self.play(halfmove);
+        }
+    }
+
+    fn engine_move(&mut self, options: &PlayOptions) {
+        let to_move = self.engine.position.move_next;
+        let position = self.engine.position.clone();
+
+        let mut limits = SearchLimits {
+            nodes: Some(LEVEL_NODES[options.level - 1]),
+            ..Default::default()
+        };
+        if options.clock.is_some() {
+            let increment = options.clock.map_or(0, |(_, increment)| increment);
+            limits.clock = Some([
+                self.clock(Color::White).max(1) as u64,
+                self.clock(Color::Black).max(1) as u64,
+                increment,
+                increment,
+            ]);
+        }
+
+        println!("EndGame2 is thinking...");
+        let start_time = Instant::now();
+        let sink = PlayInfo {
+            last: Mutex::new(None),
+        };
+        let result = self
+            .engine
+            .searcher()
+            .search(position.clone(), &limits, &sink);
+
+        if options.clock.is_some() {
+            self.clocks[to_move as usize] -= start_time.elapsed().as_millis() as i64;
+        }
+
+        let best = result.moves[0];
+        let evaluation = match sink.last.lock().unwrap().as_ref() {
+            Some(info) => match info.score {
+                Score::Cp(score) => format!(" ({:+.2})", score as f64 / 100.0),
+                Score::Mate(moves) => format!(" (#{})", moves),
+            },
+            None => String::new(),
+        };
+        println!(
+            "EndGame2 plays {}{}",
+            best.move_to_san(&position),
+            evaluation
+        );
+
+        self.play(best);
+        self.add_increment(to_move, options);
+    }
+}
+
+impl InfoSink for PlayInfo {
+    fn info(&self, info: &SearchInfo) {
+        *self.last.lock().unwrap() = Some(info.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> PlayOptions {
+        return PlayOptions {
+            color: Color::White,
+            level: 1,
+            clock: None,
+            pgn_out: None,
+            plain: true,
+        };
+    }
+
+    // plays the moves as if typed, each of them accepted
+    fn play_moves(game: &mut PlayGame, moves: &[&str]) {
+        for san in moves {
+            match parse_input(san, &game.engine.position) {
+                Ok(PlayerInput::Move(halfmove)) => game.play(halfmove),
+                other => panic!("{} read as {:?}", san, other),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_garbled_and_illegal_input() {
+        let position = Position::startpos();
+
+        for input in [
+            "xx", "e5", "e2e5", "Ke2", "O-O", "e4 e5", "P@e4", "level", "level 0",
+        ] {
+            assert!(parse_input(input, &position).is_err(), "{}", input);
+        }
+        assert!(parse_input("level 11", &position).is_err());
+        assert!(parse_input("level x", &position).is_err());
+
+        let e4 = parse_legal_move(&position, "e2e4", false).unwrap();
+        assert_eq!(parse_input("e4", &position), Ok(PlayerInput::Move(e4)));
+        assert_eq!(parse_input(" e2e4 ", &position), Ok(PlayerInput::Move(e4)));
+        assert_eq!(parse_input("level 3", &position), Ok(PlayerInput::Level(3)));
+        assert_eq!(parse_input("takeback", &position), Ok(PlayerInput::Undo));
+        assert_eq!(parse_input("resign", &position), Ok(PlayerInput::Resign));
+
+        let en_passant = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let capture = parse_legal_move(&en_passant, "e5d6", false).unwrap();
+        assert_eq!(
+            parse_input("exd6 e.p.", &en_passant),
+            Ok(PlayerInput::Move(capture))
+        );
+    }
+
+    #[test]
+    fn ends_on_mate_and_stalemate() {
+        let options = options();
+
+        let mut game = PlayGame::new(&options);
+        play_moves(&mut game, &["f3", "e5", "g4"]);
+        assert_eq!(game.game_end(&options), None);
+        play_moves(&mut game, &["Qh4#"]);
+        assert_eq!(
+            game.game_end(&options),
+            Some(("0-1", String::from("Black mates"), "normal"))
+        );
+
+        // the shortest known stalemate
+        let mut game = PlayGame::new(&options);
+        play_moves(
+            &mut game,
+            &[
+                "e3", "a5", "Qh5", "Ra6", "Qxa5", "h5", "h4", "Rah6", "Qxc7", "f6", "Qxd7+", "Kf7",
+                "Qxb7", "Qd3", "Qxb8", "Qh7", "Qxc8", "Kg6",
+            ],
+        );
+        assert_eq!(game.game_end(&options), None);
+        play_moves(&mut game, &["Qe6"]);
+        assert_eq!(
+            game.game_end(&options),
+            Some(("1/2-1/2", String::from("Draw by stalemate"), "normal"))
+        );
+    }
+
+    #[test]
+    fn ends_when_a_flag_falls() {
+        let options = PlayOptions {
+            clock: Some((1000, 0)),
+            ..options()
+        };
+
+        let mut game = PlayGame::new(&options);
+        assert_eq!(game.game_end(&options), None);
+        game.clocks[Color::Black as usize] = -1;
+        assert_eq!(
+            game.game_end(&options),
+            Some(("1-0", String::from("Black loses on time"), "time forfeit"))
+        );
+    }
+}
@@ -0,0 +1,91 @@
+// Rules that change between variants. Every method defaults to standard chess, so a
+// variant only overrides the parts it changes, and the rest of the engine goes through
+// the rules of the position's variant instead of calling the standard code directly.
+
+use crate::{gen_pseudolegal_moves, get_piece_value, is_in_check, is_piece_attacked};
+use crate::{make_halfmove, Color, HalfMove, HalfmoveFlag, Piece, Position};
+
+// score of a won game, high enough that the search treats it like a mate
+pub const VARIANT_WIN: i32 = 50000;
+
+pub trait Rules {
+    // moves before the legality check, which are all the search looks at
+    fn gen_possible(&self, position: &Position) -> Vec<HalfMove> {
+        return gen_pseudolegal_moves(position);
+    }
+
+    fn execute_halfmove(&self, position: &mut Position, halfmove: HalfMove) {
+        make_halfmove(position, halfmove);
+    }
+
+    // whether halfmove, which led from position to new_pos, is legal
+    fn is_legal(&self, position: &Position, new_pos: &Position, halfmove: HalfMove) -> bool {
+        if halfmove.flag == Some(HalfmoveFlag::Castle) && is_in_check(position) {
+            return false;
+        }
+
+        let king_pos = if position.move_next == Color::White {
+            new_pos.piece_set.white_king
+        } else {
+            new_pos.piece_set.black_king
+        };
+
+        return !is_piece_attacked(king_pos, position.move_next, new_pos);
+    }
+
+    // white relative score of a game that ended by a variant rule, before any move is
+    // generated. checkmate and stalemate are left to the search as usual
+    fn game_result(&self, _position: &Position) -> Option<i32> {
+        return None;
+    }
+
+    // score of a side that has no moves left, or None to leave it to the search
+    fn no_moves_result(&self, _position: &Position) -> Option<i32> {
+        return None;
+    }
+
+    // material and placement of one piece, from its own side
+    fn piece_value(&self, piece: Piece, index: u8) -> i32 {
+        return get_piece_value(piece, index);
+    }
+
+    // white relative, added to the material eval
+    fn eval(&self, _position: &Position) -> i32 {
+        return 0;
+    }
+}
+
+pub struct Standard;
+
+impl Rules for Standard {}
+
+// a win for color, from white's side
+pub fn win_for(color: Color) -> i32 {
+    return if color == Color::White {
+        VARIANT_WIN
+    } else {
+        -VARIANT_WIN
+    };
+}
+
+pub fn king_square(position: &Position, color: Color) -> u8 {
+    return if color == Color::White {
+        position.piece_set.white_king
+    } else {
+        position.piece_set.black_king
+    };
+}
+
+// the side that just moved didn't leave its king to be captured, so a variant win from
+// its move counts. the search plays moves without checking their legality
+pub fn mover_king_safe(position: &Position) -> bool {
+    let mover = position.move_next.opposite();
+
+    return !is_piece_attacked(king_square(position, mover), mover, position);
+}
+
+pub fn is_capture(position: &Position, halfmove: HalfMove) -> bool {
+    return halfmove.flag == Some(HalfmoveFlag::EnPassant)
+        || (halfmove.flag != Some(HalfmoveFlag::Castle)
+            && position.board[halfmove.to as usize].is_some());
+}
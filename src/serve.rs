@@ -0,0 +1,692 @@
+// Local analysis server. It listens on localhost only and answers json posted over http,
+// so a program can use the engine without driving the uci protocol over a pipe.
+//
+// usage: endgame2 serve [--port N]
+//
+// every endpoint takes a json object with the position, as
+// {"fen": ..., "variant": ..., "chess960": ..., "moves": [...]}, all of them optional
+//
+//   POST /analyse  also {"limits": {...}, "multipv": N}, streams the search as
+//                  newline-delimited json, or as server-sent events when the request
+//                  accepts text/event-stream. closing the connection stops the search
+//   POST /moves    the legal moves
+//   POST /perft    also {"depth": N}, the node count below each legal move
+//   POST /eval     the static eval, from white's side
+
+use crate::info::{InfoSink, Score, SearchInfo};
+use crate::json::Json;
+use crate::perft::perft;
+use crate::tt::Bound;
+use crate::{execute_halfmove, gen_legal_moves, parse_legal_move, position_eval};
+use crate::{Engine, HalfMove, Position, SearchLimits, StopHandle, Variant, DEFAULT_NODES};
+use hashbrown::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 8080;
+
+// larger requests are refused, no position needs anywhere near this
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+// perft runs on the connection thread and is only stopped between root moves, so keep
+// every root move's count to a few seconds
+const MAX_PERFT_DEPTH: u64 = 6;
+
+struct Request {
+    method: String,
+    path: String,
+    event_stream: bool,
+    body: Json,
+}
+
+// the position a request asks about, with the positions played on the way to it
+struct Setup {
+    position: Position,
+    repetition_map: HashMap<u64, u8>,
+    chess960: bool,
+}
+
+// sends every search update down the connection as it comes. a failed write means the
+// client went away, which stops the search
+struct StreamSink {
+    stream: Mutex<TcpStream>,
+    event_stream: bool,
+    position: Position,
+    chess960: bool,
+    stop: StopHandle,
+    // the multipv line being searched
+    line: usize,
+    best: Mutex<Option<(HalfMove, Option<HalfMove>)>>,
+}
+
+pub fn serve_command(args: &[String]) {
+    let port = match args {
+        [] => DEFAULT_PORT,
+        [flag, port] if flag == "--port" => match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => {
+                println!("Error - invalid port {}", port);
+                return;
+            }
+        },
+        _ => {
+            println!("usage: serve [--port N]");
+            return;
+        }
+    };
+
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("Error - could not listen on port {}: {}", port, e);
+            return;
+        }
+    };
+
+    // port 0 lets the system pick one, so print the one actually used
+    match listener.local_addr() {
+        Ok(address) => println!("Listening on http://{}", address),
+        Err(_) => println!("Listening on port {}", port),
+    }
+
+    serve(listener);
+}
+
+// answers connections on listener until the process ends
+pub fn serve(listener: TcpListener) {
+    // analyses share the engine and its hash, so they run one at a time
+    let engine = Arc::new(Mutex::new(Engine::new()));
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        let engine = Arc::clone(&engine);
+        thread::spawn(move || handle_connection(stream, &engine));
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, engine: &Mutex<Engine>) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err((status, message)) => {
+            respond_error(&mut stream, status, &message);
+            return;
+        }
+    };
+
+    if request.method != "POST" {
+        respond_error(&mut stream, 405, "only POST is supported");
+        return;
+    }
+
+    let setup = match parse_setup(&request.body) {
+        Ok(setup) => setup,
+        Err(e) => {
+            respond_error(&mut stream, 400, &e);
+            return;
+        }
+    };
+
+    let body = match request.path.as_str() {
+        "/analyse" | "/analyze" => {
+            analyse(stream, &request, setup, engine);
+            return;
+        }
+        "/perft" => {
+            perft_request(stream, &request.body, &setup);
+            return;
+        }
+        "/moves" => legal_moves(&setup),
+        "/eval" => static_eval(&setup),
+        _ => {
+            respond_error(&mut stream, 404, &format!("no endpoint {}", request.path));
+            return;
+        }
+    };
+
+    respond(&mut stream, 200, &body);
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, (u16, String)> {
+    let bad_request = |message: &str| (400, message.to_string());
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .map_err(|_| bad_request("could not read the request"))?;
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(bad_request("malformed request line"));
+    };
+
+    let mut content_length = 0;
+    let mut event_stream = false;
+
+    loop {
+        let mut header = String::new();
+        reader
+            .read_line(&mut header)
+            .map_err(|_| bad_request("could not read the headers"))?;
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(bad_request("malformed header"));
+        };
+        let value = value.trim();
+
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = value
+                    .parse::<usize>()
+                    .map_err(|_| bad_request("invalid content length"))?
+            }
+            "accept" => event_stream = value.contains("text/event-stream"),
+            _ => {}
+        }
+    }
+
+    if content_length > MAX_BODY_SIZE {
+        return Err((413, String::from("request body too large")));
+    }
+
+    let mut body = vec![0; content_length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|_| bad_request("could not read the body"))?;
+
+    let body = String::from_utf8(body).map_err(|_| bad_request("body isn't utf-8"))?;
+
+    // an empty body asks about the starting position
+    let body = if body.trim().is_empty() {
+        Json::Object(vec![])
+    } else {
+        Json::parse(&body).map_err(|e| (400, format!("invalid json: {}", e)))?
+    };
+
+    if !matches!(body, Json::Object(_)) {
+        return Err(bad_request("body must be a json object"));
+    }
+
+    return Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        event_stream,
+        body,
+    });
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Json) {
+    let body = format!("{}\n", body);
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        body.len(),
+        body
+    );
+}
+
+fn respond_error(stream: &mut TcpStream, status: u16, message: &str) {
+    respond(
+        stream,
+        status,
+        &Json::Object(vec![(String::from("error"), Json::from(message))]),
+    );
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    return match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "",
+    };
+}
+
+fn object(entries: Vec<(&str, Json)>) -> Json {
+    return Json::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect(),
+    );
+}
+
+fn parse_setup(body: &Json) -> Result<Setup, String> {
+    let variant = match body.get("variant") {
+        Some(name) => name
+            .as_str()
+            .and_then(Variant::from_name)
+            .ok_or(format!("unknown variant {}", name))?,
+        None => Variant::Standard,
+    };
+
+    let chess960 = match body.get("chess960") {
+        Some(value) => value.as_bool().ok_or("chess960 must be true or false")?,
+        None => false,
+    };
+
+    let mut position = match body.get("fen") {
+        Some(fen) => {
+            let fen = fen.as_str().ok_or("fen must be a string")?;
+            Position::from_variant_fen(fen, variant)
+                .map_err(|e| format!("invalid fen {}: {}", fen, e))?
+        }
+        None => {
+            let mut position = Position::startpos();
+            position.variant = variant;
+            position
+        }
+    };
+
+    let mut repetition_map = HashMap::new();
+    repetition_map.insert(position.gen_hash(), 1);
+
+    let moves = match body.get("moves") {
+        Some(moves) => moves.as_array().ok_or("moves must be an array")?,
+        None => &[],
+    };
+
+    for move_string in moves {
+        let halfmove = move_string
+            .as_str()
+            .and_then(|move_string| parse_legal_move(&position, move_string, chess960))
+            .ok_or(format!("illegal move {}", move_string))?;
+
+        execute_halfmove(&mut position, halfmove);
+        *repetition_map.entry(position.gen_hash()).or_insert(0) += 1;
+    }
+
+    return Ok(Setup {
+        position,
+        repetition_map,
+        chess960,
+    });
+}
+
+fn parse_limits(body: &Json, setup: &Setup) -> Result<SearchLimits, String> {
+    let mut limits = SearchLimits::default();
+
+    let Some(entries) = body.get("limits") else {
+        return Ok(limits);
+    };
+    let Json::Object(entries) = entries else {
+        return Err(String::from("limits must be an object"));
+    };
+
+    for (name, value) in entries {
+        let number = || value.as_u64().ok_or(format!("{} must be a number", name));
+
+        match name.as_str() {
+            "depth" => limits.depth = Some(number()? as usize),
+            "nodes" => limits.nodes = Some(number()? as usize),
+            "movetime" => limits.movetime = Some(number()?),
+            "movestogo" => limits.moves_to_go = Some(number()?),
+            "mate" => limits.mate = Some(number()? as usize),
+            "wtime" | "btime" | "winc" | "binc" => {
+                let index = ["wtime", "btime", "winc", "binc"]
+                    .iter()
+                    .position(|limit| limit == name)
+                    .unwrap();
+                limits.clock.get_or_insert([0; 4])[index] = number()?;
+            }
+            "infinite" => {
+                limits.infinite = value.as_bool().ok_or("infinite must be true or false")?
+            }
+            "searchmoves" => {
+                let moves = value.as_array().ok_or("searchmoves must be an array")?;
+
+                for move_string in moves {
+                    let halfmove = move_string
+                        .as_str()
+                        .and_then(|move_string| {
+                            parse_legal_move(&setup.position, move_string, setup.chess960)
+                        })
+                        .ok_or(format!("searchmove {} is not legal", move_string))?;
+                    limits.search_moves.push(halfmove);
+                }
+            }
+            _ => return Err(format!("unknown limit {}", name)),
+        }
+    }
+
+    return Ok(limits);
+}
+
+// further lines are searched one by one, each without the best moves of the lines before
+fn analyse(mut stream: TcpStream, request: &Request, setup: Setup, engine: &Mutex<Engine>) {
+    let mut limits = match parse_limits(&request.body, &setup) {
+        Ok(limits) => limits,
+        Err(e) => {
+            respond_error(&mut stream, 400, &e);
+            return;
+        }
+    };
+
+    let multipv = match request.body.get("multipv") {
+        Some(value) => match value.as_u64() {
+            Some(multipv) if multipv >= 1 => multipv as usize,
+            _ => {
+                respond_error(&mut stream, 400, "multipv must be a positive number");
+                return;
+            }
+        },
+        None => 1,
+    };
+
+    // as with a bare go, a search without limits stops after a fixed number of nodes
+    if !limits.infinite && limits.is_unbounded() {
+        limits.nodes = Some(DEFAULT_NODES);
+    }
+
+    let content_type = if request.event_stream {
+        "text/event-stream"
+    } else {
+        "application/x-ndjson"
+    };
+
+    if write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        content_type
+    )
+    .is_err()
+    {
+        return;
+    }
+
+    let mut engine = engine.lock().unwrap();
+
+    // positions of different variants share hash keys
+    if engine.position.variant != setup.position.variant {
+        engine.tt.clear();
+    }
+
+    engine.set_position(setup.position.clone());
+    engine.repetition_map = setup.repetition_map;
+
+    let root_moves = if limits.search_moves.is_empty() {
+        gen_legal_moves(&setup.position)
+    } else {
+        limits.search_moves.clone()
+    };
+
+    // the client sends nothing more, so a read only returns once it hangs up. without
+    // this an infinite search would only notice at its next info line
+    let done = Arc::new(AtomicBool::new(false));
+    let watcher = stream.try_clone().ok().map(|reader| {
+        let stop = engine.stop.clone();
+        let done = Arc::clone(&done);
+
+        thread::spawn(move || watch_disconnect(reader, &stop, &done))
+    });
+
+    let mut sink = StreamSink {
+        stream: Mutex::new(stream),
+        event_stream: request.event_stream,
+        position: setup.position.clone(),
+        chess960: setup.chess960,
+        stop: engine.stop.clone(),
+        line: 1,
+        best: Mutex::new(None),
+    };
+
+    let mut best_lines: Vec<(HalfMove, Option<HalfMove>)> = vec![];
+
+    for line in 1..=multipv.min(root_moves.len()) {
+        sink.line = line;
+
+        let mut line_limits = limits.clone();
+        if line > 1 {
+            line_limits.search_moves = root_moves
+                .iter()
+                .copied()
+                .filter(|halfmove| best_lines.iter().all(|&(best, _)| best != *halfmove))
+                .collect();
+        }
+
+        engine
+            .searcher()
+            .search(setup.position.clone(), &line_limits, &sink);
+
+        match sink.best.lock().unwrap().take() {
+            Some(best) => best_lines.push(best),
+            None => break,
+        }
+
+        if engine.stop.is_stopped() {
+            break;
+        }
+    }
+
+    let uci = |halfmove: HalfMove| Json::from(halfmove.move_to_uci(setup.chess960));
+    let (best, ponder) = match best_lines.first() {
+        Some(&(best, ponder)) => (uci(best), ponder.map_or(Json::Null, uci)),
+        // checkmate or stalemate on the board
+        None => (Json::Null, Json::Null),
+    };
+
+    sink.send(object(vec![
+        ("type", Json::from("bestmove")),
+        ("bestmove", best),
+        ("ponder", ponder),
+    ]));
+
+    // a failed write or the watcher can still stop the search up to here, and a stop
+    // after the reset would cut the next analysis short
+    done.store(true, Ordering::Relaxed);
+    let _ = sink.stream.lock().unwrap().shutdown(Shutdown::Read);
+    if let Some(watcher) = watcher {
+        let _ = watcher.join();
+    }
+    engine.stop.reset();
+}
+
+fn watch_disconnect(mut reader: TcpStream, stop: &StopHandle, done: &AtomicBool) {
+    let mut buffer = [0; 512];
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) => {}
+            Err(_) => break,
+        }
+    }
+
+    if !done.load(Ordering::Relaxed) {
+        stop.stop();
+    }
+}
+
+impl StreamSink {
+    fn send(&self, event: Json) {
+        let mut stream = self.stream.lock().unwrap();
+
+        let sent = if self.event_stream {
+            write!(stream, "data: {}\n\n", event)
+        } else {
+            writeln!(stream, "{}", event)
+        };
+
+        if sent.and_then(|_| stream.flush()).is_err() {
+            self.stop.stop();
+        }
+    }
+}
+
+impl InfoSink for StreamSink {
+    fn info(&self, info: &SearchInfo) {
+        let score = match info.score {
+            Score::Cp(score) => object(vec![("cp", Json::from(score))]),
+            Score::Mate(moves) => object(vec![("mate", Json::from(moves))]),
+        };
+        let (win, draw, loss) = info.score.wdl();
+
+        let mut position = self.position.clone();
+        let mut san = vec![];
+        for &halfmove in info.pv.iter() {
+            san.push(Json::from(halfmove.move_to_san(&position)));
+            execute_halfmove(&mut position, halfmove);
+        }
+
+        self.send(object(vec![
+            ("type", Json::from("info")),
+            ("multipv", Json::from(self.line)),
+            ("depth", Json::from(info.depth)),
+            ("seldepth", Json::from(info.seldepth)),
+            ("time", Json::from(info.time)),
+            ("nodes", Json::from(info.nodes)),
+            (
+                "nps",
+                Json::from(info.nodes as u128 * 1000 / info.time.max(1)),
+            ),
+            ("hashfull", Json::from(info.hashfull)),
+            ("tbhits", Json::from(info.tbhits)),
+            ("score", score),
+            ("lowerbound", Json::from(info.bound == Bound::Lower)),
+            ("upperbound", Json::from(info.bound == Bound::Upper)),
+            (
+                "wdl",
+                Json::Array(vec![
+                    Json::from(win as usize),
+                    Json::from(draw as usize),
+                    Json::from(loss as usize),
+                ]),
+            ),
+            (
+                "pv",
+                Json::Array(
+                    info.pv
+                        .iter()
+                        .map(|halfmove| Json::from(halfmove.move_to_uci(self.chess960)))
+                        .collect(),
+                ),
+            ),
+            ("san", Json::Array(san)),
+        ]));
+    }
+
+    // sent once every line is done
+    fn bestmove(&self, best: Option<HalfMove>, ponder: Option<HalfMove>) {
+        *self.best.lock().unwrap() = best.map(|best| (best, ponder));
+    }
+
+    fn message(&self, text: &str) {
+        self.send(object(vec![
+            ("type", Json::from("message")),
+            ("text", Json::from(text)),
+        ]));
+    }
+}
+
+fn legal_moves(setup: &Setup) -> Json {
+    let moves = gen_legal_moves(&setup.position)
+        .iter()
+        .map(|halfmove| {
+            object(vec![
+                ("uci", Json::from(halfmove.move_to_uci(setup.chess960))),
+                ("san", Json::from(halfmove.move_to_san(&setup.position))),
+            ])
+        })
+        .collect();
+
+    return object(vec![
+        ("fen", Json::from(setup.position.to_fen())),
+        ("moves", Json::Array(moves)),
+    ]);
+}
+
+fn perft_request(mut stream: TcpStream, body: &Json, setup: &Setup) {
+    // as with an analysis, a client that hangs up stops the count
+    let stop = StopHandle::default();
+    let done = Arc::new(AtomicBool::new(false));
+    let watcher = stream.try_clone().ok().map(|reader| {
+        let stop = stop.clone();
+        let done = Arc::clone(&done);
+
+        thread::spawn(move || watch_disconnect(reader, &stop, &done))
+    });
+
+    let result = perft_divide(body, setup, &stop);
+
+    done.store(true, Ordering::Relaxed);
+    let _ = stream.shutdown(Shutdown::Read);
+    if let Some(watcher) = watcher {
+        let _ = watcher.join();
+    }
+
+    match result {
+        Ok(Some(body)) => respond(&mut stream, 200, &body),
+        Ok(None) => {}
+        Err(e) => respond_error(&mut stream, 400, &e),
+    }
+}
+
+// none when stopped before the count is done
+fn perft_divide(body: &Json, setup: &Setup, stop: &StopHandle) -> Result<Option<Json>, String> {
+    let depth = body
+        .get("depth")
+        .and_then(Json::as_u64)
+        .filter(|depth| (1..=MAX_PERFT_DEPTH).contains(depth))
+        .ok_or(format!("depth must be from 1 to {}", MAX_PERFT_DEPTH))?;
+
+    let mut total = 0;
+    let mut divide = vec![];
+
+    for halfmove in gen_legal_moves(&setup.position) {
+        if stop.is_stopped() {
+            return Ok(None);
+        }
+
+        let mut new_pos = setup.position.clone();
+        execute_halfmove(&mut new_pos, halfmove);
+
+        let nodes = if depth == 1 {
+            1
+        } else {
+            perft(&new_pos, depth as usize - 1)
+        };
+
+        total += nodes;
+        divide.push((halfmove.move_to_uci(setup.chess960), Json::from(nodes)));
+    }
+
+    return Ok(Some(object(vec![
+        ("depth", Json::from(depth)),
+        ("nodes", Json::from(total)),
+        ("moves", Json::Object(divide)),
+    ])));
+}
+
+fn static_eval(setup: &Setup) -> Json {
+    return object(vec![
+        ("fen", Json::from(setup.position.to_fen())),
+        (
+            "eval",
+            Json::from(position_eval(&setup.position, &setup.repetition_map)),
+        ),
+    ]);
+}
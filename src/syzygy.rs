@@ -0,0 +1,1371 @@
+// Syzygy tablebase probing, following the layout used by the generator and the
+// reference prober in Stockfish. Tables are found when the path is set, and read into
+// memory the first time their material is probed. After that a probe takes no lock and
+// allocates nothing, so search threads don't wait on each other.
+
+#![allow(clippy::needless_range_loop)]
+
+use crate::{execute_halfmove, gen_legal_moves, is_in_check, Color, HalfMove, HalfmoveFlag};
+use crate::{CastlingRights, Piece, Position, Variant};
+use hashbrown::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+const TB_PIECES: usize = 7;
+
+// above any dtz plus halfmove clock, so a cursed win still ranks above a draw
+const MAX_DTZ: i32 = 1 << 18;
+
+// below the 30000 mate threshold used by the search
+const TB_WIN_SCORE: i32 = 20000;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// pairs data flags
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_i32(value: i32) -> Wdl {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => Wdl::Draw,
+        }
+    }
+
+    fn negate(self) -> Wdl {
+        return Wdl::from_i32(-(self as i32));
+    }
+
+    fn signum(self) -> i32 {
+        return (self as i32).signum();
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ProbeState {
+    Ok,
+    Fail,
+    ZeroingBestMove,
+    ChangeStm,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum TableType {
+    Wdl,
+    Dtz,
+}
+
+struct Tables {
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[usize; 64]; 10],
+    binomial: [[u64; 64]; TB_PIECES],
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn off_a1h8(square: usize) -> i32 {
+    return (square / 8) as i32 - (square % 8) as i32;
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+
+    TABLES.get_or_init(|| {
+        let mut tables = Tables {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; TB_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                tables.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        // squares below the diagonal first, the a1-d4 diagonal last
+        let mut diagonal = vec![];
+        code = 0;
+        for square in 0..28 {
+            if off_a1h8(square) < 0 && square % 8 <= 3 {
+                tables.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 && square % 8 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            tables.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // every legal placement of two kings with the first in the a1-d1-d4 triangle
+        let mut both_on_diagonal = vec![];
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if tables.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+
+                for s2 in 0..64 {
+                    let file_dist = (s1 % 8).abs_diff(s2 % 8);
+                    let rank_dist = (s1 / 8).abs_diff(s2 / 8);
+
+                    // adjacent kings, or first on the diagonal and second above it
+                    if (file_dist <= 1 && rank_dist <= 1) || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0)
+                    {
+                        continue;
+                    } else if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        tables.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            tables.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        tables.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..TB_PIECES.min(n + 1) {
+                tables.binomial[k][n] = if k > 0 {
+                    tables.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { tables.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // lead pawn closest to the edge and lowest rank gets the highest value
+        let mut available_squares = 47;
+        for lead_pawns_count in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+
+                    if lead_pawns_count == 1 {
+                        tables.map_pawns[square] = available_squares;
+                        available_squares -= 1;
+                        tables.map_pawns[square ^ 7] = available_squares;
+                        available_squares = available_squares.saturating_sub(1);
+                    }
+
+                    tables.lead_pawn_idx[lead_pawns_count][square] = idx;
+                    idx += tables.binomial[lead_pawns_count - 1][tables.map_pawns[square]];
+                }
+
+                tables.lead_pawns_size[lead_pawns_count][file] = idx;
+            }
+        }
+
+        tables
+    })
+}
+
+fn read_u16_le(bytes: &[u8], offset: usize) -> u16 {
+    return u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> u32 {
+    return u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ]);
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> u32 {
+    // blocks may end right at the end of the file, pad with zeroes
+    let mut buf = [0; 4];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = *bytes.get(offset + i).unwrap_or(&0);
+    }
+    return u32::from_be_bytes(buf);
+}
+
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    max_sym_len: u8,
+    min_sym_len: u8,
+    num_blocks: u32,
+    block_size: usize,
+    span: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: u32,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    // byte offsets of the win, loss, cursed win and blessed loss dtz maps
+    map_idx: [usize; 4],
+}
+
+struct Table {
+    bytes: Vec<u8>,
+    table_type: TableType,
+    key: u64,
+    key2: u64,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    pawn_count: [usize; 2],
+    items: Vec<Vec<PairsData>>,
+}
+
+fn piece_to_tb_code(piece: Piece) -> u8 {
+    let code = match piece {
+        Piece::Pawn(_) => 1,
+        Piece::Knight(_) => 2,
+        Piece::Bishop(_) => 3,
+        Piece::Rook(_) => 4,
+        Piece::Queen(_) => 5,
+        Piece::King(_) => 6,
+    };
+
+    if piece.get_color() == Color::Black {
+        return code | 8;
+    }
+    return code;
+}
+
+fn left_symbol(bytes: &[u8], btree: usize, sym: u16) -> u16 {
+    let lr = btree + 3 * sym as usize;
+    return (((bytes[lr + 1] & 0xf) as u16) << 8) | bytes[lr] as u16;
+}
+
+fn right_symbol(bytes: &[u8], btree: usize, sym: u16) -> u16 {
+    let lr = btree + 3 * sym as usize;
+    return ((bytes[lr + 2] as u16) << 4) | (bytes[lr + 1] >> 4) as u16;
+}
+
+fn set_symlen(bytes: &[u8], d: &mut PairsData, sym: u16, visited: &mut [bool]) -> u8 {
+    visited[sym as usize] = true;
+
+    let right = right_symbol(bytes, d.btree, sym);
+    if right == 0xfff {
+        return 0;
+    }
+
+    let left = left_symbol(bytes, d.btree, sym);
+
+    if !visited[left as usize] {
+        d.symlen[left as usize] = set_symlen(bytes, d, left, visited);
+    }
+    if !visited[right as usize] {
+        d.symlen[right as usize] = set_symlen(bytes, d, right, visited);
+    }
+
+    return d.symlen[left as usize]
+        .wrapping_add(d.symlen[right as usize])
+        .wrapping_add(1);
+}
+
+impl Table {
+    fn new(bytes: Vec<u8>, name: &str, table_type: TableType) -> Option<Table> {
+        let magic = if table_type == TableType::Wdl {
+            WDL_MAGIC
+        } else {
+            DTZ_MAGIC
+        };
+
+        if bytes.len() < 16 || bytes[0..4] != magic {
+            return None;
+        }
+
+        let (white, black) = name.split_once('v')?;
+        let key = name_key(name)?;
+
+        let mut has_unique_pieces = false;
+        for side in [white, black] {
+            for piece in ['Q', 'R', 'B', 'N', 'P'] {
+                if side.chars().filter(|&c| c == piece).count() == 1 {
+                    has_unique_pieces = true;
+                }
+            }
+        }
+
+        let white_pawns = white.chars().filter(|&c| c == 'P').count();
+        let black_pawns = black.chars().filter(|&c| c == 'P').count();
+
+        // the leading color is the one with fewer pawns
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        let mut table = Table {
+            bytes,
+            table_type,
+            key,
+            key2: swap_colors(key),
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count,
+            items: vec![],
+        };
+
+        if table.piece_count > TB_PIECES {
+            return None;
+        }
+
+        table.setup();
+
+        return Some(table);
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let side = stm % self.items.len();
+        let file = if self.has_pawns { file } else { 0 };
+        return &self.items[side][file];
+    }
+
+    fn setup(&mut self) {
+        let sides = if self.table_type == TableType::Wdl && self.key != self.key2 {
+            2
+        } else {
+            1
+        };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+
+        self.items = vec![vec![PairsData::default(); files]; sides];
+
+        // first byte after the magic stores the split and pawn flags
+        let mut data = 5;
+
+        for file in 0..files {
+            let order = [
+                [
+                    (self.bytes[data] & 0xf) as usize,
+                    if pp {
+                        (self.bytes[data + 1] & 0xf) as usize
+                    } else {
+                        0xf
+                    },
+                ],
+                [
+                    (self.bytes[data] >> 4) as usize,
+                    if pp {
+                        (self.bytes[data + 1] >> 4) as usize
+                    } else {
+                        0xf
+                    },
+                ],
+            ];
+            data += 1 + pp as usize;
+
+            for k in 0..self.piece_count {
+                for side in 0..sides {
+                    self.items[side][file].pieces[k] = if side == 1 {
+                        self.bytes[data] >> 4
+                    } else {
+                        self.bytes[data] & 0xf
+                    };
+                }
+                data += 1;
+            }
+
+            for side in 0..sides {
+                self.set_groups(side, file, order[side]);
+            }
+        }
+
+        data += data & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                data = self.set_sizes(side, file, data);
+            }
+        }
+
+        if self.table_type == TableType::Dtz {
+            data = self.set_dtz_map(data, files);
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                d.sparse_index = data;
+                data += d.sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let d = &mut self.items[side][file];
+                d.block_length = data;
+                data += d.block_length_size as usize * 2;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                data = (data + 0x3f) & !0x3f;
+                let d = &mut self.items[side][file];
+                d.data = data;
+                data += d.num_blocks as usize * d.block_size;
+            }
+        }
+    }
+
+    fn set_groups(&mut self, side: usize, file: usize, order: [usize; 2]) {
+        let t = tables();
+        let piece_count = self.piece_count;
+        let has_pawns = self.has_pawns;
+        let has_unique_pieces = self.has_unique_pieces;
+        let pp = has_pawns && self.pawn_count[1] > 0;
+        let d = &mut self.items[side][file];
+
+        let mut n = 0;
+        let mut first_len: i32 = if has_pawns {
+            0
+        } else if has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[n] = 1;
+
+        for i in 1..piece_count {
+            // the leading group takes its first pieces, and any group runs of equal ones
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        // the groups are encoded in a per-table order
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                d.group_idx[0] = idx;
+                idx *= if has_pawns {
+                    t.lead_pawns_size[d.group_len[0]][file]
+                } else if has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                d.group_idx[1] = idx;
+                idx *= t.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= t.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+
+        d.group_idx[n] = idx;
+    }
+
+    fn set_sizes(&mut self, side: usize, file: usize, mut data: usize) -> usize {
+        let bytes = &self.bytes;
+        let d = &mut self.items[side][file];
+
+        d.flags = bytes[data];
+        data += 1;
+
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            d.num_blocks = 0;
+            d.block_length_size = 0;
+            d.span = 0;
+            d.sparse_index_size = 0;
+            // the single stored value
+            d.min_sym_len = bytes[data];
+            return data + 1;
+        }
+
+        let group_end = d.group_len.iter().position(|&len| len == 0).unwrap_or(0);
+        let tb_size = d.group_idx[group_end];
+
+        d.block_size = 1 << bytes[data];
+        d.span = 1 << bytes[data + 1];
+        d.sparse_index_size = tb_size.div_ceil(d.span as u64) as usize;
+        let padding = bytes[data + 2];
+        d.num_blocks = read_u32_le(bytes, data + 3);
+        d.block_length_size = d.num_blocks + padding as u32;
+        d.max_sym_len = bytes[data + 7];
+        d.min_sym_len = bytes[data + 8];
+        data += 9;
+        d.lowest_sym = data;
+
+        let base_len = (d.max_sym_len - d.min_sym_len + 1) as usize;
+        d.base64 = vec![0; base_len];
+
+        // canonical huffman: longer symbols have lower values
+        for i in (0..base_len.saturating_sub(1)).rev() {
+            let lowest_i = read_u16_le(bytes, d.lowest_sym + 2 * i) as u64;
+            let lowest_next = read_u16_le(bytes, d.lowest_sym + 2 * (i + 1)) as u64;
+            d.base64[i] = (d.base64[i + 1] + lowest_i - lowest_next) / 2;
+        }
+        for i in 0..base_len {
+            d.base64[i] <<= 64 - i - d.min_sym_len as usize;
+        }
+
+        data += base_len * 2;
+        let symlen_size = read_u16_le(bytes, data) as usize;
+        data += 2;
+        d.btree = data;
+        d.symlen = vec![0; symlen_size];
+
+        let mut visited = vec![false; symlen_size];
+        for sym in 0..symlen_size {
+            if !visited[sym] {
+                let len = set_symlen(bytes, d, sym as u16, &mut visited);
+                d.symlen[sym] = len;
+            }
+        }
+
+        return data + symlen_size * 3 + (symlen_size & 1);
+    }
+
+    fn set_dtz_map(&mut self, mut data: usize, files: usize) -> usize {
+        for file in 0..files {
+            let flags = self.items[0][file].flags;
+            if flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+
+            if flags & FLAG_WIDE != 0 {
+                data += data & 1;
+                for i in 0..4 {
+                    self.items[0][file].map_idx[i] = data + 2;
+                    data += 2 * read_u16_le(&self.bytes, data) as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    self.items[0][file].map_idx[i] = data + 1;
+                    data += self.bytes[data] as usize + 1;
+                }
+            }
+        }
+
+        return data + (data & 1);
+    }
+
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> i32 {
+        let bytes = &self.bytes;
+
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return d.min_sym_len as i32;
+        }
+
+        // find the block holding idx, starting from the nearest sparse index entry
+        let k = (idx / d.span as u64) as usize;
+
+        let mut block = read_u32_le(bytes, d.sparse_index + 6 * k) as usize;
+        let mut offset = read_u16_le(bytes, d.sparse_index + 6 * k + 4) as i64;
+
+        let diff = (idx % d.span as u64) as i64 - (d.span / 2) as i64;
+        offset += diff;
+
+        let block_length = |block: usize| read_u16_le(bytes, d.block_length + 2 * block) as i64;
+
+        while offset < 0 {
+            block -= 1;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + block * d.block_size;
+
+        let mut buf64 =
+            ((read_u32_be(bytes, ptr) as u64) << 32) | read_u32_be(bytes, ptr + 4) as u64;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let mut sym: u16;
+
+        loop {
+            let mut len = 0;
+
+            while buf64 < d.base64[len] {
+                len += 1;
+            }
+
+            sym = ((buf64 - d.base64[len]) >> (64 - len - d.min_sym_len as usize)) as u16;
+            sym = sym.wrapping_add(read_u16_le(bytes, d.lowest_sym + 2 * len));
+
+            if offset < d.symlen[sym as usize] as i64 + 1 {
+                break;
+            }
+
+            offset -= d.symlen[sym as usize] as i64 + 1;
+            len += d.min_sym_len as usize;
+            buf64 <<= len;
+            buf64_size -= len;
+
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (read_u32_be(bytes, ptr) as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // expand the symbol through its pairs until reaching a single value
+        while d.symlen[sym as usize] != 0 {
+            let left = left_symbol(bytes, d.btree, sym);
+
+            if offset < d.symlen[left as usize] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= d.symlen[left as usize] as i64 + 1;
+                sym = right_symbol(bytes, d.btree, sym);
+            }
+        }
+
+        return left_symbol(bytes, d.btree, sym) as i32;
+    }
+
+    fn map_dtz_score(&self, file: usize, value: i32, wdl: Wdl) -> i32 {
+        let d = self.get(0, file);
+        let mut value = value;
+
+        // win, loss, cursed win and blessed loss each have their own map
+        let map_index = match wdl {
+            Wdl::Win => 0,
+            Wdl::Loss => 1,
+            Wdl::CursedWin => 2,
+            Wdl::BlessedLoss => 3,
+            Wdl::Draw => 0,
+        };
+
+        if d.flags & FLAG_MAPPED != 0 {
+            if d.flags & FLAG_WIDE != 0 {
+                value = read_u16_le(&self.bytes, d.map_idx[map_index] + 2 * value as usize) as i32;
+            } else {
+                value = self.bytes[d.map_idx[map_index] + value as usize] as i32;
+            }
+        }
+
+        // dtz can be stored in moves, convert to plies
+        if (wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+
+        return value + 1;
+    }
+
+    // returns the raw value stored for the position, before mapping
+    fn probe(&self, position: &Position, state: &mut ProbeState) -> Option<(i32, usize)> {
+        let t = tables();
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns_count = 0;
+        let mut lead_pawns = [false; 64];
+        let mut tb_file = 0;
+
+        let symmetric_black_to_move = self.key == self.key2 && position.move_next == Color::Black;
+        let black_stronger = material_key(position) != self.key;
+
+        let flip = symmetric_black_to_move || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip as usize) ^ (position.move_next == Color::Black) as usize;
+
+        if self.has_pawns {
+            // pawns of the leading color come first in every file table
+            let lead_color = if (self.get(0, 0).pieces[0] ^ flip_color) & 8 != 0 {
+                Color::Black
+            } else {
+                Color::White
+            };
+
+            for square in 0..64 {
+                if position.board[square] == Some(Piece::Pawn(lead_color)) {
+                    lead_pawns[square] = true;
+                    squares[size] = square ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns_count = size;
+
+            let mut max_index = 0;
+            for i in 1..lead_pawns_count {
+                if t.map_pawns[squares[i]] > t.map_pawns[squares[max_index]] {
+                    max_index = i;
+                }
+            }
+            squares.swap(0, max_index);
+
+            tb_file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+
+        // dtz tables only store one side to move
+        if self.table_type == TableType::Dtz {
+            let flags = self.get(stm, tb_file).flags;
+            let symmetric = self.key == self.key2 && !self.has_pawns;
+            if (flags & FLAG_STM) as usize != stm && !symmetric {
+                *state = ProbeState::ChangeStm;
+                return None;
+            }
+        }
+
+        for square in 0..64 {
+            if lead_pawns[square] {
+                continue;
+            }
+            if let Some(piece) = position.board[square] {
+                squares[size] = square ^ flip_squares;
+                pieces[size] = piece_to_tb_code(piece) ^ flip_color;
+                size += 1;
+            }
+        }
+
+        let d = self.get(stm, tb_file);
+
+        // reorder pieces to match the sequence stored in the table
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            for j in (i + 1)..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // map the lead piece into the a1-d1-d4 triangle
+        if squares[0] % 8 > 3 {
+            for square in squares.iter_mut().take(size) {
+                *square ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+
+        if self.has_pawns {
+            idx = t.lead_pawn_idx[lead_pawns_count][squares[0]];
+
+            squares[1..lead_pawns_count].sort_by_key(|&square| t.map_pawns[square]);
+
+            for i in 1..lead_pawns_count {
+                idx += t.binomial[i][t.map_pawns[squares[i]]];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                for square in squares.iter_mut().take(size) {
+                    *square ^= 56;
+                }
+            }
+
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+
+                if off_a1h8(squares[i]) > 0 {
+                    for square in squares.iter_mut().take(size).skip(i) {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 =
+                    (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+
+                let rank = |square: usize| square / 8;
+
+                idx = if off_a1h8(squares[0]) != 0 {
+                    ((t.map_a1d1d4[squares[0]] * 63 + (squares[1] - adjust1)) * 62 + squares[2]
+                        - adjust2) as u64
+                } else if off_a1h8(squares[1]) != 0 {
+                    ((6 * 63 + rank(squares[0]) * 28 + t.map_b1h1h7[squares[1]]) * 62 + squares[2]
+                        - adjust2) as u64
+                } else if off_a1h8(squares[2]) != 0 {
+                    (6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(squares[0]) * 7 * 28
+                        + (rank(squares[1]) - adjust1) * 28
+                        + t.map_b1h1h7[squares[2]]) as u64
+                } else {
+                    (6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(squares[0]) * 7 * 6
+                        + (rank(squares[1]) - adjust1) * 6
+                        + (rank(squares[2]) - adjust2)) as u64
+                };
+            } else {
+                idx = t.map_kk[t.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+
+        idx *= d.group_idx[0];
+
+        // encode remaining pawns, then the remaining pieces
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+
+        while d.group_len[next] != 0 {
+            let group_end = group_start + d.group_len[next];
+            squares[group_start..group_end].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..d.group_len[next] {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start]
+                    .iter()
+                    .filter(|&&s| square > s)
+                    .count();
+                n += t.binomial[i + 1][square - adjust - 8 * remaining_pawns as usize];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        return Some((self.decompress_pairs(d, idx), tb_file));
+    }
+}
+
+// search score for a tablebase result, preferring faster wins and slower losses
+pub fn wdl_to_score(wdl: Wdl, ply: usize) -> i32 {
+    return match wdl {
+        Wdl::Win => TB_WIN_SCORE - ply as i32,
+        Wdl::CursedWin => 2,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -2,
+        Wdl::Loss => -TB_WIN_SCORE + ply as i32,
+    };
+}
+
+// bits per piece type count in a material key
+const KEY_BITS: u32 = 4;
+// the white pieces in the low half of a material key, the black ones above
+const KEY_SIDE_BITS: u32 = 5 * KEY_BITS;
+
+// the pieces of each color other than the kings, counted in 4 bits each in the order
+// of the table names
+pub fn material_key(position: &Position) -> u64 {
+    let mut key = 0;
+
+    for piece in position.board.iter().flatten() {
+        let shift = match piece {
+            Piece::King(_) => continue,
+            Piece::Queen(_) => 0,
+            Piece::Rook(_) => 1,
+            Piece::Bishop(_) => 2,
+            Piece::Knight(_) => 3,
+            Piece::Pawn(_) => 4,
+        } * KEY_BITS
+            + if piece.get_color() == Color::Black {
+                KEY_SIDE_BITS
+            } else {
+                0
+            };
+        key += 1 << shift;
+    }
+
+    return key;
+}
+
+// the material key of a table name like KRPvKR, none if it isn't one
+fn name_key(name: &str) -> Option<u64> {
+    let (white, black) = name.split_once('v')?;
+    if white.len() + black.len() > TB_PIECES {
+        return None;
+    }
+
+    let mut key = 0;
+    for (side, side_shift) in [(white, 0), (black, KEY_SIDE_BITS)] {
+        for letter in side.strip_prefix('K')?.chars() {
+            let index = "QRBNP".find(letter)? as u32;
+            key += 1 << (index * KEY_BITS + side_shift);
+        }
+    }
+
+    return Some(key);
+}
+
+fn swap_colors(key: u64) -> u64 {
+    let side_mask = (1 << KEY_SIDE_BITS) - 1;
+    return (key >> KEY_SIDE_BITS) | ((key & side_mask) << KEY_SIDE_BITS);
+}
+
+pub fn piece_count(position: &Position) -> usize {
+    return position.piece_set.all.len();
+}
+
+fn is_zeroing(position: &Position, halfmove: HalfMove) -> bool {
+    return is_capture(position, halfmove)
+        || matches!(position.board[halfmove.from as usize], Some(Piece::Pawn(_)));
+}
+
+fn is_capture(position: &Position, halfmove: HalfMove) -> bool {
+    return halfmove.flag == Some(HalfmoveFlag::EnPassant)
+        || (halfmove.flag != Some(HalfmoveFlag::Castle)
+            && position.board[halfmove.to as usize].is_some());
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    return match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    };
+}
+
+// a table file found on the path, read the first time it is probed
+struct TableEntry {
+    name: String,
+    path: PathBuf,
+    table: OnceLock<Option<Table>>,
+}
+
+pub struct Tablebase {
+    // by material key, only filled in while opening
+    wdl_tables: HashMap<u64, TableEntry>,
+    dtz_tables: HashMap<u64, TableEntry>,
+    max_pieces: usize,
+    hits: AtomicUsize,
+}
+
+impl Tablebase {
+    pub fn empty() -> Self {
+        Self {
+            wdl_tables: HashMap::new(),
+            dtz_tables: HashMap::new(),
+            max_pieces: 0,
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    // path may list several directories, separated like the PATH variable
+    pub fn open(path: &str) -> Self {
+        let mut tablebase = Self::empty();
+
+        if path.is_empty() || path == "<empty>" {
+            return tablebase;
+        }
+
+        let separator = if cfg!(windows) { ';' } else { ':' };
+
+        for dir in path.split(separator) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+
+            for entry in entries.map_while(Result::ok) {
+                let file_path = entry.path();
+                let (Some(stem), Some(extension)) = (
+                    file_path.file_stem().and_then(|s| s.to_str()),
+                    file_path.extension().and_then(|s| s.to_str()),
+                ) else {
+                    continue;
+                };
+
+                let Some(key) = name_key(stem) else {
+                    continue;
+                };
+
+                let tables = match extension {
+                    "rtbw" => {
+                        tablebase.max_pieces = tablebase.max_pieces.max(stem.len() - 1);
+                        &mut tablebase.wdl_tables
+                    }
+                    "rtbz" => &mut tablebase.dtz_tables,
+                    _ => continue,
+                };
+
+                tables.insert(
+                    key,
+                    TableEntry {
+                        name: stem.to_string(),
+                        path: file_path,
+                        table: OnceLock::new(),
+                    },
+                );
+            }
+        }
+
+        return tablebase;
+    }
+
+    pub fn table_count(&self) -> usize {
+        return self.wdl_tables.len() + self.dtz_tables.len();
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        return self.max_pieces;
+    }
+
+    pub fn hits(&self) -> usize {
+        return self.hits.load(Ordering::Relaxed);
+    }
+
+    pub fn reset_hits(&self) {
+        self.hits.store(0, Ordering::Relaxed);
+    }
+
+    // whether the position can be probed at all
+    pub fn can_probe(&self, position: &Position, probe_limit: usize) -> bool {
+        let count = piece_count(position);
+
+        return count <= self.max_pieces.min(probe_limit)
+            && position.castling_rights == CastlingRights::none()
+            && position.variant == Variant::Standard;
+    }
+
+    fn table(&self, position: &Position, table_type: TableType) -> Option<&Table> {
+        let tables = if table_type == TableType::Wdl {
+            &self.wdl_tables
+        } else {
+            &self.dtz_tables
+        };
+
+        // a table covers both colors, named with the stronger side first
+        let key = material_key(position);
+        let entry = tables.get(&key).or_else(|| tables.get(&swap_colors(key)))?;
+
+        return entry
+            .table
+            .get_or_init(|| {
+                fs::read(&entry.path)
+                    .ok()
+                    .and_then(|bytes| Table::new(bytes, &entry.name, table_type))
+            })
+            .as_ref();
+    }
+
+    fn probe_wdl_table(&self, position: &Position, state: &mut ProbeState) -> Wdl {
+        if piece_count(position) == 2 {
+            return Wdl::Draw;
+        }
+
+        let Some(table) = self.table(position, TableType::Wdl) else {
+            *state = ProbeState::Fail;
+            return Wdl::Draw;
+        };
+
+        match table.probe(position, state) {
+            Some((value, _)) => return Wdl::from_i32(value - 2),
+            None => return Wdl::Draw,
+        }
+    }
+
+    fn probe_dtz_table(&self, position: &Position, wdl: Wdl, state: &mut ProbeState) -> i32 {
+        let Some(table) = self.table(position, TableType::Dtz) else {
+            *state = ProbeState::Fail;
+            return 0;
+        };
+
+        match table.probe(position, state) {
+            Some((value, file)) => return table.map_dtz_score(file, value, wdl),
+            None => return 0,
+        }
+    }
+
+    // captures (and pawn moves when looking for zeroing moves) must be searched
+    // since the tables store "don't care" values where they are the best move
+    fn search(&self, position: &Position, state: &mut ProbeState, check_zeroing: bool) -> Wdl {
+        let mut best = Wdl::Loss;
+        let moves = gen_legal_moves(position);
+        let mut move_count = 0;
+
+        for halfmove in moves.iter() {
+            if !is_capture(position, *halfmove)
+                && (!check_zeroing
+                    || !matches!(position.board[halfmove.from as usize], Some(Piece::Pawn(_))))
+            {
+                continue;
+            }
+
+            move_count += 1;
+
+            let mut new_pos = position.clone();
+            execute_halfmove(&mut new_pos, *halfmove);
+            let value = self.search(&new_pos, state, false).negate();
+
+            if *state == ProbeState::Fail {
+                return Wdl::Draw;
+            }
+
+            if value > best {
+                best = value;
+
+                if value >= Wdl::Win {
+                    *state = ProbeState::ZeroingBestMove;
+                    return value;
+                }
+            }
+        }
+
+        let no_more_moves = move_count != 0 && move_count == moves.len();
+
+        let value = if no_more_moves {
+            best
+        } else {
+            let value = self.probe_wdl_table(position, state);
+            if *state == ProbeState::Fail {
+                return Wdl::Draw;
+            }
+            value
+        };
+
+        if best >= value {
+            *state = if best > Wdl::Draw || no_more_moves {
+                ProbeState::ZeroingBestMove
+            } else {
+                ProbeState::Ok
+            };
+            return best;
+        }
+
+        *state = ProbeState::Ok;
+        return value;
+    }
+
+    // win/draw/loss from the side to move's perspective
+    pub fn probe_wdl(&self, position: &Position) -> Option<Wdl> {
+        let mut state = ProbeState::Ok;
+        let wdl = self.search(position, &mut state, false);
+
+        if state == ProbeState::Fail {
+            return None;
+        }
+
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        return Some(wdl);
+    }
+
+    // distance to zeroing in plies, signed from the side to move's perspective
+    pub fn probe_dtz(&self, position: &Position) -> Option<i32> {
+        let mut state = ProbeState::Ok;
+        let dtz = self.probe_dtz_state(position, &mut state);
+
+        if state == ProbeState::Fail {
+            return None;
+        }
+
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        return Some(dtz);
+    }
+
+    fn probe_dtz_state(&self, position: &Position, state: &mut ProbeState) -> i32 {
+        *state = ProbeState::Ok;
+        let wdl = self.search(position, state, true);
+
+        if *state == ProbeState::Fail || wdl == Wdl::Draw {
+            return 0;
+        }
+
+        if *state == ProbeState::ZeroingBestMove {
+            return dtz_before_zeroing(wdl);
+        }
+
+        let dtz = self.probe_dtz_table(position, wdl, state);
+
+        if *state == ProbeState::Fail {
+            return 0;
+        }
+
+        if *state != ProbeState::ChangeStm {
+            let cursed = wdl == Wdl::BlessedLoss || wdl == Wdl::CursedWin;
+            return (dtz + if cursed { 100 } else { 0 }) * wdl.signum();
+        }
+
+        // the table stores the other side to move, so do a 1-ply search
+        let mut min_dtz = 0xffff;
+
+        for halfmove in gen_legal_moves(position) {
+            let zeroing = is_zeroing(position, halfmove);
+
+            let mut new_pos = position.clone();
+            execute_halfmove(&mut new_pos, halfmove);
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&new_pos, state, false))
+            } else {
+                -self.probe_dtz_state(&new_pos, state)
+            };
+
+            if dtz == 1 && is_in_check(&new_pos) && gen_legal_moves(&new_pos).is_empty() {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+        }
+
+        if min_dtz == 0xffff {
+            return -1;
+        }
+        return min_dtz;
+    }
+
+    // keeps the root moves that preserve the best reachable result, preferring
+    // the fastest conversion when winning. returns None if a table is missing.
+    pub fn filter_root_moves(&self, position: &Position) -> Option<Vec<HalfMove>> {
+        let mut ranked = vec![];
+
+        for halfmove in gen_legal_moves(position) {
+            let mut new_pos = position.clone();
+            execute_halfmove(&mut new_pos, halfmove);
+
+            let mut dtz = if new_pos.halfmove_clock == 0 {
+                dtz_before_zeroing(self.probe_wdl(&new_pos)?.negate())
+            } else {
+                let dtz = -self.probe_dtz(&new_pos)?;
+                dtz + dtz.signum()
+            };
+
+            if dtz == 2 && is_in_check(&new_pos) && gen_legal_moves(&new_pos).is_empty() {
+                dtz = 1;
+            }
+
+            let rank = root_rank(dtz, position.halfmove_clock as i32);
+
+            ranked.push((halfmove, rank, dtz));
+        }
+
+        let best_rank = ranked.iter().map(|&(_, rank, _)| rank).max()?;
+        ranked.retain(|&(_, rank, _)| rank == best_rank);
+
+        // among winning moves, keep the ones closest to zeroing so progress is made
+        if best_rank > 0 {
+            let min_dtz = ranked.iter().map(|&(_, _, dtz)| dtz).min()?;
+            ranked.retain(|&(_, _, dtz)| dtz == min_dtz);
+        }
+
+        return Some(
+            ranked
+                .into_iter()
+                .map(|(halfmove, _, _)| halfmove)
+                .collect(),
+        );
+    }
+}
+
+// how good a root move is from its dtz after the move. wins and losses the fifty move
+// rule can't spoil all rank the same, the others by how far they are from it
+fn root_rank(dtz: i32, halfmove_clock: i32) -> i32 {
+    return if dtz > 0 {
+        if dtz + halfmove_clock <= 99 {
+            MAX_DTZ
+        } else {
+            MAX_DTZ - (dtz + halfmove_clock)
+        }
+    } else if dtz < 0 {
+        if -dtz * 2 + halfmove_clock < 100 {
+            -MAX_DTZ
+        } else {
+            -MAX_DTZ + (-dtz + halfmove_clock)
+        }
+    } else {
+        0
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_cursed_wins_above_draws() {
+        assert_eq!(root_rank(20, 0), MAX_DTZ);
+        assert!(root_rank(120, 0) < MAX_DTZ);
+
+        // the longest dtz of the 7-man tables, late in the fifty moves
+        assert!(root_rank(1034, 99) > root_rank(0, 99));
+        assert!(root_rank(-1034, 99) < root_rank(0, 99));
+        assert!(root_rank(-1034, 99) > -MAX_DTZ);
+    }
+
+    #[test]
+    fn keys_material_by_name_and_position() {
+        let position = Position::from_fen("8/8/8/4k3/8/8/2P5/R3K3 w - - 0 1").unwrap();
+        assert_eq!(Some(material_key(&position)), name_key("KRPvK"));
+        assert_eq!(
+            swap_colors(material_key(&position)),
+            name_key("KvKRP").unwrap()
+        );
+        assert_ne!(name_key("KRvKP"), name_key("KPvKR"));
+
+        assert_eq!(name_key("KRvX"), None);
+        assert_eq!(name_key("KQRBNvKQR"), None);
+    }
+}
@@ -0,0 +1,125 @@
+// Three-check. Besides checkmate, a side also wins by giving its third check. The checks
+// are counted in the position and written in the fen as the checks each side has left,
+// as in 3+3, or as the checks given so far at the end, as in +0+0.
+
+use crate::rules::{mover_king_safe, win_for, Rules};
+use crate::{is_in_check, make_halfmove, Color, HalfMove, Position};
+
+const CHECKS_TO_WIN: u8 = 3;
+
+// by checks given, a side close to its third check is close to winning
+const CHECK_BONUS: [i32; 3] = [0, 80, 250];
+
+pub struct ThreeCheck;
+
+impl Rules for ThreeCheck {
+    fn execute_halfmove(&self, position: &mut Position, halfmove: HalfMove) {
+        let mover = position.move_next;
+
+        make_halfmove(position, halfmove);
+
+        if is_in_check(position) {
+            position.checks[side(mover)] += 1;
+        }
+    }
+
+    fn game_result(&self, position: &Position) -> Option<i32> {
+        let mover = position.move_next.opposite();
+
+        if position.checks[side(mover)] >= CHECKS_TO_WIN && mover_king_safe(position) {
+            return Some(win_for(mover));
+        }
+
+        return None;
+    }
+
+    fn eval(&self, position: &Position) -> i32 {
+        let bonus = |color| CHECK_BONUS[position.checks[side(color)].min(2) as usize];
+
+        return bonus(Color::White) - bonus(Color::Black);
+    }
+}
+
+fn side(color: Color) -> usize {
+    return match color {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+}
+
+pub fn checks_string(position: &Position) -> String {
+    return format!(
+        "{}+{}",
+        CHECKS_TO_WIN.saturating_sub(position.checks[0]),
+        CHECKS_TO_WIN.saturating_sub(position.checks[1])
+    );
+}
+
+// 3+3, the checks left, returned as the checks given
+pub fn parse_remaining_checks(token: &str) -> Option<[u8; 2]> {
+    let (white, black) = token.split_once('+')?;
+    let white = white.parse::<u8>().ok().filter(|&n| n <= CHECKS_TO_WIN)?;
+    let black = black.parse::<u8>().ok().filter(|&n| n <= CHECKS_TO_WIN)?;
+
+    return Some([CHECKS_TO_WIN - white, CHECKS_TO_WIN - black]);
+}
+
+// +0+0, the checks given
+pub fn parse_given_checks(token: &str) -> Option<[u8; 2]> {
+    let (white, black) = token.strip_prefix('+')?.split_once('+')?;
+
+    return Some([white.parse().ok()?, black.parse().ok()?]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::VARIANT_WIN;
+    use crate::{execute_halfmove, gen_legal_moves, parse_legal_move, Variant};
+
+    fn play(position: &mut Position, uci: &str) {
+        let halfmove = parse_legal_move(position, uci, false).unwrap();
+        execute_halfmove(position, halfmove);
+    }
+
+    #[test]
+    fn parses_check_counters() {
+        assert_eq!(parse_remaining_checks("3+3"), Some([0, 0]));
+        assert_eq!(parse_remaining_checks("2+1"), Some([1, 2]));
+        assert_eq!(parse_remaining_checks("4+0"), None);
+        assert_eq!(parse_remaining_checks("3-3"), None);
+
+        assert_eq!(parse_given_checks("+1+2"), Some([1, 2]));
+        assert_eq!(parse_given_checks("1+2"), None);
+
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 1+3 0 1";
+        let position = Position::from_variant_fen(fen, Variant::ThreeCheck).unwrap();
+        assert_eq!(position.checks, [2, 0]);
+        assert_eq!(checks_string(&position), "1+3");
+        assert_eq!(position.to_fen(), fen);
+    }
+
+    #[test]
+    fn wins_with_the_third_check() {
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 1+3 0 1";
+        let mut position = Position::from_variant_fen(fen, Variant::ThreeCheck).unwrap();
+
+        play(&mut position, "h1h7");
+        assert_eq!(position.checks, [2, 0]);
+        assert_eq!(ThreeCheck.game_result(&position), None);
+
+        play(&mut position, "e8f8");
+        play(&mut position, "h7h8");
+        assert_eq!(position.checks, [3, 0]);
+        assert_eq!(ThreeCheck.game_result(&position), Some(VARIANT_WIN));
+        assert!(gen_legal_moves(&position).is_empty());
+    }
+
+    #[test]
+    fn rewards_checks_given() {
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 1+2 0 1";
+        let position = Position::from_variant_fen(fen, Variant::ThreeCheck).unwrap();
+
+        assert_eq!(ThreeCheck.eval(&position), 250 - 80);
+    }
+}
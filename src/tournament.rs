@@ -0,0 +1,933 @@
+// Engine vs engine matches. Two UCI engines run as child processes and play each
+// opening twice with colours reversed. Results are reported as an Elo estimate and
+// a sequential probability ratio test against the given Elo bounds.
+//
+// usage: endgame2 match <engine1> <engine2> [openings FILE] [games N] [concurrency N]
+//        [tc BASE+INC] [timemargin MS] [pgnout FILE] [elo0 N] [elo1 N] [alpha N] [beta N]
+//        [resignscore CP] [resignmoves N] [drawscore CP] [drawmoves N] [drawafter N]
+//        [syzygy PATH] [chess960]
+//
+// "self" can be given as an engine to use the current executable. With chess960 the
+// engines are told so and castling moves are sent and read as king takes rook.
+
+use crate::adjudication::{AdjudicationRules, Adjudicator};
+use crate::pgn::{read_pgn_file, Game};
+use crate::syzygy::Tablebase;
+use crate::{execute_halfmove, gen_legal_moves, Color, HalfMove, Position};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how long an engine that ran out of time gets to answer stop before it is restarted
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone)]
+struct MatchOptions {
+    engines: [String; 2],
+    openings: Option<String>,
+    games: usize,
+    concurrency: usize,
+    base_ms: u64,
+    increment_ms: u64,
+    // time an engine may go over its clock before it loses on time
+    time_margin_ms: u64,
+    pgn_out: Option<String>,
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+    adjudication: AdjudicationRules,
+    chess960: bool,
+}
+
+#[derive(Clone)]
+struct Opening {
+    // none for the standard starting position
+    fen: Option<String>,
+    moves: Vec<HalfMove>,
+}
+
+struct UciEngine {
+    name: String,
+    process: Child,
+    stdin: ChildStdin,
+    output: Receiver<String>,
+}
+
+struct PlayedMove {
+    halfmove: HalfMove,
+    // centipawns from the mover's point of view, if the engine reported one
+    score: Option<i32>,
+    time_ms: u128,
+}
+
+struct GameRecord {
+    index: usize,
+    // engine index playing white
+    white: usize,
+    opening: Opening,
+    moves: Vec<PlayedMove>,
+    result: &'static str,
+    reason: String,
+    termination: &'static str,
+}
+
+#[derive(Default)]
+struct MatchScore {
+    // from the point of view of the first engine
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+impl UciEngine {
+    fn start(path: &str, chess960: bool) -> Result<Self, String> {
+        let mut process = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start {}: {}", path, e))?;
+
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+
+        // read on a separate thread so a hanging engine can be timed out
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = UciEngine {
+            name: path.to_string(),
+            process,
+            stdin,
+            output,
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line(HANDSHAKE_TIMEOUT)?;
+
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+
+        if chess960 {
+            engine.send("setoption name UCI_Chess960 value true")?;
+        }
+        engine.sync()?;
+
+        return Ok(engine);
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        return writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("{} stopped responding: {}", self.name, e));
+    }
+
+    fn read_line(&mut self, timeout: Duration) -> Result<String, String> {
+        return match self.output.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(format!("{} timed out", self.name)),
+            Err(RecvTimeoutError::Disconnected) => Err(format!("{} exited", self.name)),
+        };
+    }
+
+    fn sync(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+
+        while self.read_line(HANDSHAKE_TIMEOUT)?.trim() != "readyok" {}
+
+        return Ok(());
+    }
+
+    // a search that ran out of time goes on, and its late bestmove would be taken as the
+    // reply to the next go. isready is answered during a search, so sync can't catch it
+    fn stop(&mut self) -> Result<(), String> {
+        self.send("stop")?;
+
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if self.read_line(remaining)?.starts_with("bestmove") {
+                return Ok(());
+            }
+        }
+    }
+
+    fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        return self.sync();
+    }
+
+    // returns the best move token and the last reported score
+    fn search(
+        &mut self,
+        position_command: &str,
+        go_command: &str,
+        timeout: Duration,
+    ) -> Result<(String, Option<i32>), String> {
+        self.send(position_command)?;
+        // make sure the position is set up before the clock starts
+        self.sync()?;
+        self.send(go_command)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut score = None;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = self.read_line(remaining)?;
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("info") => {
+                    while let Some(token) = tokens.next() {
+                        if token != "score" {
+                            continue;
+                        }
+
+                        let value = tokens.nth(1).and_then(|value| value.parse::<i32>().ok());
+                        score = match (line.contains("score mate"), value) {
+                            (true, Some(moves)) if moves > 0 => Some(30000 - moves),
+                            (true, Some(moves)) => Some(-30000 - moves),
+                            (false, Some(cp)) => Some(cp),
+                            _ => score,
+                        };
+                    }
+                }
+                Some("bestmove") => {
+                    let best_move = tokens.next().unwrap_or("").to_string();
+                    return Ok((best_move, score));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        let quit_time = Instant::now();
+        while quit_time.elapsed() < Duration::from_secs(1) {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+impl Opening {
+    fn start_position(&self) -> Result<Position, String> {
+        let mut position = match &self.fen {
+            Some(fen) => {
+                Position::from_fen(fen).map_err(|e| format!("invalid opening {}: {}", fen, e))?
+            }
+            None => Position::startpos(),
+        };
+
+        for halfmove in self.moves.iter() {
+            execute_halfmove(&mut position, *halfmove);
+        }
+
+        return Ok(position);
+    }
+}
+
+impl MatchScore {
+    fn games(&self) -> u32 {
+        return self.wins + self.losses + self.draws;
+    }
+
+    fn add(&mut self, record: &GameRecord) {
+        let first_is_white = record.white == 0;
+
+        match (record.result, first_is_white) {
+            ("1-0", true) | ("0-1", false) => self.wins += 1,
+            ("1-0", false) | ("0-1", true) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    // mean score and its per game variance
+    fn mean_and_variance(&self) -> (f64, f64) {
+        let games = self.games() as f64;
+        let mean = (self.wins as f64 + self.draws as f64 / 2.0) / games;
+
+        let variance = (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2))
+            / games;
+
+        return (mean, variance);
+    }
+
+    // elo difference and the half width of its 95% confidence interval
+    fn elo(&self) -> (f64, f64) {
+        if self.games() == 0 {
+            return (0.0, 0.0);
+        }
+
+        let (mean, variance) = self.mean_and_variance();
+        let error = 1.96 * (variance / self.games() as f64).sqrt();
+
+        let margin = (elo_from_score(mean + error) - elo_from_score(mean - error)) / 2.0;
+
+        return (elo_from_score(mean), margin);
+    }
+
+    // generalized sprt log likelihood ratio of elo1 against elo0
+    fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+
+        let (mean, variance) = self.mean_and_variance();
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let score0 = score_from_elo(elo0);
+        let score1 = score_from_elo(elo1);
+
+        return self.games() as f64 * (score1 - score0) * (2.0 * mean - score0 - score1)
+            / (2.0 * variance);
+    }
+}
+
+fn score_from_elo(elo: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    return -400.0 * (1.0 / score - 1.0).log10();
+}
+
+// the llr at or below which h0 is accepted, and at or above which h1 is, for false
+// positive rate alpha and false negative rate beta
+fn sprt_bounds(alpha: f64, beta: f64) -> (f64, f64) {
+    return ((beta / (1.0 - alpha)).ln(), ((1.0 - beta) / alpha).ln());
+}
+
+fn sprt_result(llr: f64, lower_bound: f64, upper_bound: f64) -> Option<&'static str> {
+    if llr >= upper_bound {
+        return Some("H1");
+    } else if llr <= lower_bound {
+        return Some("H0");
+    }
+
+    return None;
+}
+
+pub fn match_command(args: &[String]) {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Error - {}", e);
+            println!(
+                "usage: match <engine1> <engine2> [openings FILE] [games N] [concurrency N] [tc BASE+INC] [timemargin MS] [pgnout FILE] [elo0 N] [elo1 N] [alpha N] [beta N] [resignscore CP] [resignmoves N] [drawscore CP] [drawmoves N] [drawafter N] [syzygy PATH] [chess960]"
+            );
+            return;
+        }
+    };
+
+    let openings = match &options.openings {
+        Some(path) => match load_openings(path) {
+            Ok(openings) if !openings.is_empty() => openings,
+            Ok(_) => {
+                println!("Error - no openings found in {}", path);
+                return;
+            }
+            Err(e) => {
+                println!("Error - {}", e);
+                return;
+            }
+        },
+        None => vec![Opening {
+            fen: None,
+            moves: vec![],
+        }],
+    };
+
+    // names are taken from the engines themselves
+    let names: Vec<String> = match options
+        .engines
+        .iter()
+        .map(|path| UciEngine::start(path, options.chess960).map(|engine| engine.name.clone()))
+        .collect()
+    {
+        Ok(names) => names,
+        Err(e) => {
+            println!("Error - {}", e);
+            return;
+        }
+    };
+
+    let (lower_bound, upper_bound) = sprt_bounds(options.alpha, options.beta);
+
+    println!(
+        "Started match of {} games between {} and {}",
+        options.games, names[0], names[1]
+    );
+
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let openings = Arc::new(openings);
+    let (sender, receiver) = mpsc::channel();
+
+    for _ in 0..options.concurrency.min(options.games) {
+        let options = options.clone();
+        let next_game = Arc::clone(&next_game);
+        let stop = Arc::clone(&stop);
+        let openings = Arc::clone(&openings);
+        let sender = sender.clone();
+
+        thread::spawn(move || {
+            let mut engines = vec![];
+            for path in options.engines.iter() {
+                match UciEngine::start(path, options.chess960) {
+                    Ok(engine) => engines.push(engine),
+                    Err(e) => {
+                        let _ = sender.send(Err(e));
+                        return;
+                    }
+                }
+            }
+
+            loop {
+                let index = next_game.fetch_add(1, Ordering::SeqCst);
+                if index >= options.games || stop.load(Ordering::SeqCst) {
+                    return;
+                }
+
+                // each opening is played twice, once with each engine as white
+                let opening = &openings[(index / 2) % openings.len()];
+                let white = index % 2;
+
+                let record = play_game(&mut engines, index, white, opening, &options);
+                if sender.send(record).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    drop(sender);
+
+    let mut score = MatchScore::default();
+
+    for record in receiver {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                println!("Error - {}", e);
+                stop.store(true, Ordering::SeqCst);
+                continue;
+            }
+        };
+
+        score.add(&record);
+
+        println!(
+            "Finished game {} ({} vs {}): {} {{{}}}",
+            record.index + 1,
+            names[record.white],
+            names[1 - record.white],
+            record.result,
+            record.reason
+        );
+        print_score(&score, &names, &options, lower_bound, upper_bound);
+
+        if let Some(path) = &options.pgn_out {
+            let pgn = record_to_pgn(&record, &names, &options);
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", pgn));
+
+            if let Err(e) = written {
+                println!("Error - could not write {}: {}", path, e);
+            }
+        }
+
+        let llr = score.llr(options.elo0, options.elo1);
+        if let Some(accepted) = sprt_result(llr, lower_bound, upper_bound) {
+            if !stop.load(Ordering::SeqCst) {
+                println!("SPRT: {} was accepted", accepted);
+                stop.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    println!("Finished match");
+}
+
+fn print_score(
+    score: &MatchScore,
+    names: &[String],
+    options: &MatchOptions,
+    lower_bound: f64,
+    upper_bound: f64,
+) {
+    let (mean, _) = score.mean_and_variance();
+    let (elo, margin) = score.elo();
+
+    println!(
+        "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+        names[0],
+        names[1],
+        score.wins,
+        score.losses,
+        score.draws,
+        mean,
+        score.games()
+    );
+    println!(
+        "Elo difference: {:.1} +/- {:.1}, LLR: {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}]",
+        elo,
+        margin,
+        score.llr(options.elo0, options.elo1),
+        lower_bound,
+        upper_bound,
+        options.elo0,
+        options.elo1
+    );
+}
+
+fn parse_options(args: &[String]) -> Result<MatchOptions, String> {
+    let mut options = MatchOptions {
+        engines: [String::new(), String::new()],
+        openings: None,
+        games: 100,
+        concurrency: 1,
+        base_ms: 10000,
+        increment_ms: 100,
+        time_margin_ms: 100,
+        pgn_out: None,
+        elo0: 0.0,
+        elo1: 5.0,
+        alpha: 0.05,
+        beta: 0.05,
+        adjudication: AdjudicationRules {
+            resign_score: 1000,
+            resign_moves: 0,
+            draw_score: 10,
+            draw_moves: 0,
+            draw_after: 40,
+            tablebase: None,
+        },
+        chess960: false,
+    };
+
+    let mut engine_count = 0;
+    let mut tokens = args.iter();
+
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "openings" | "games" | "concurrency" | "tc" | "timemargin" | "pgnout" | "elo0"
+            | "elo1" | "alpha" | "beta" | "resignscore" | "resignmoves" | "drawscore"
+            | "drawmoves" | "drawafter" | "syzygy" => {
+                let value = tokens
+                    .next()
+                    .ok_or(format!("missing value for {}", token))?;
+                let invalid = || format!("invalid value for {}: {}", token, value);
+
+                match token.as_str() {
+                    "openings" => options.openings = Some(value.clone()),
+                    "pgnout" => options.pgn_out = Some(value.clone()),
+                    "syzygy" => {
+                        let tablebase = Tablebase::open(value);
+                        if tablebase.table_count() == 0 {
+                            return Err(format!("no tablebase files found in {}", value));
+                        }
+                        options.adjudication.tablebase = Some(Arc::new(tablebase));
+                    }
+                    "resignscore" | "drawscore" => {
+                        let parsed = value.parse::<i32>().map_err(|_| invalid())?;
+
+                        if token == "resignscore" {
+                            options.adjudication.resign_score = parsed;
+                        } else {
+                            options.adjudication.draw_score = parsed;
+                        }
+                    }
+                    "resignmoves" | "drawmoves" => {
+                        let parsed = value.parse::<u32>().map_err(|_| invalid())?;
+
+                        if token == "resignmoves" {
+                            options.adjudication.resign_moves = parsed;
+                        } else {
+                            options.adjudication.draw_moves = parsed;
+                        }
+                    }
+                    "drawafter" => {
+                        options.adjudication.draw_after = value.parse().map_err(|_| invalid())?
+                    }
+                    "games" => options.games = value.parse().map_err(|_| invalid())?,
+                    "concurrency" => {
+                        options.concurrency = value.parse().map_err(|_| invalid())?;
+                        options.concurrency = options.concurrency.max(1);
+                    }
+                    "timemargin" => {
+                        options.time_margin_ms = value.parse().map_err(|_| invalid())?
+                    }
+                    "tc" => {
+                        let (base, increment) = value.split_once('+').unwrap_or((value, "0"));
+                        let base = base.parse::<f64>().map_err(|_| invalid())?;
+                        let increment = increment.parse::<f64>().map_err(|_| invalid())?;
+
+                        options.base_ms = (base * 1000.0) as u64;
+                        options.increment_ms = (increment * 1000.0) as u64;
+                    }
+                    _ => {
+                        let parsed = value.parse::<f64>().map_err(|_| invalid())?;
+
+                        match token.as_str() {
+                            "elo0" => options.elo0 = parsed,
+                            "elo1" => options.elo1 = parsed,
+                            "alpha" => options.alpha = parsed,
+                            _ => options.beta = parsed,
+                        }
+                    }
+                }
+            }
+            "chess960" => options.chess960 = true,
+            _ => {
+                if engine_count == 2 {
+                    return Err(format!("unexpected argument {}", token));
+                }
+
+                options.engines[engine_count] = engine_path(token)?;
+                engine_count += 1;
+            }
+        }
+    }
+
+    if engine_count < 2 {
+        return Err(String::from("two engines are needed"));
+    }
+    if !(0.0..1.0).contains(&options.alpha) || !(0.0..1.0).contains(&options.beta) {
+        return Err(String::from("alpha and beta must be between 0 and 1"));
+    }
+
+    return Ok(options);
+}
+
+fn engine_path(token: &str) -> Result<String, String> {
+    if token != "self" {
+        return Ok(token.to_string());
+    }
+
+    return std::env::current_exe()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| format!("could not find the current executable: {}", e));
+}
+
+fn load_openings(path: &str) -> Result<Vec<Opening>, String> {
+    if path.to_lowercase().ends_with(".pgn") {
+        let games = read_pgn_file(path)?;
+
+        return Ok(games
+            .iter()
+            .filter_map(|game| game.as_ref().ok())
+            .map(|game| Opening {
+                fen: game.tag("FEN").map(String::from),
+                moves: game.mainline(),
+            })
+            .collect());
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+
+    let mut openings = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // epd lines carry no move counters, so only the first four fields are used
+        let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+        if fields.len() < 4 {
+            continue;
+        }
+
+        openings.push(Opening {
+            fen: Some(format!("{} 0 1", fields.join(" "))),
+            moves: vec![],
+        });
+    }
+
+    return Ok(openings);
+}
+
+fn play_game(
+    engines: &mut [UciEngine],
+    index: usize,
+    white: usize,
+    opening: &Opening,
+    options: &MatchOptions,
+) -> Result<GameRecord, String> {
+    for engine in engines.iter_mut() {
+        engine.new_game()?;
+    }
+
+    let mut position = opening.start_position()?;
+    let mut move_tokens: Vec<String> = opening
+        .moves
+        .iter()
+        .map(|m| m.move_to_uci(options.chess960))
+        .collect();
+    let mut moves = vec![];
+
+    let mut adjudicator = Adjudicator::new(options.adjudication.clone(), &position);
+
+    // clocks in milliseconds, indexed by color
+    let mut clocks = [options.base_ms as i64; 2];
+
+    let start = match &opening.fen {
+        Some(fen) => format!("position fen {}", fen),
+        None => String::from("position startpos"),
+    };
+
+    let (result, reason, termination) = loop {
+        if let Some(adjudication) = adjudicator.check(&position) {
+            break (
+                adjudication.result,
+                adjudication.reason,
+                adjudication.termination,
+            );
+        }
+
+        let side = if position.move_next == Color::White {
+            0
+        } else {
+            1
+        };
+        let side_name = if side == 0 { "White" } else { "Black" };
+        let loss = if side == 0 { "0-1" } else { "1-0" };
+        let engine = if side == 0 { white } else { 1 - white };
+
+        let position_command = if move_tokens.is_empty() {
+            start.clone()
+        } else {
+            format!("{} moves {}", start, move_tokens.join(" "))
+        };
+        let go_command = format!(
+            "go wtime {} btime {} winc {} binc {}",
+            clocks[0].max(1),
+            clocks[1].max(1),
+            options.increment_ms,
+            options.increment_ms
+        );
+
+        // the engine gets its whole clock plus the margin before it is treated as hung
+        let allowed_ms = clocks[side].max(0) as u64 + options.time_margin_ms;
+
+        let search_start = Instant::now();
+        let reply = engines[engine].search(
+            &position_command,
+            &go_command,
+            Duration::from_millis(allowed_ms),
+        );
+        let elapsed = search_start.elapsed().as_millis();
+
+        let (best_move, score) = match reply {
+            Ok(reply) => reply,
+            Err(_) if elapsed >= allowed_ms as u128 => {
+                if engines[engine].stop().is_err() {
+                    engines[engine] = UciEngine::start(&options.engines[engine], options.chess960)?;
+                }
+                break (loss, format!("{} loses on time", side_name), "time forfeit");
+            }
+            Err(e) => return Err(e),
+        };
+
+        clocks[side] -= elapsed as i64;
+        if clocks[side] < -(options.time_margin_ms as i64) {
+            break (loss, format!("{} loses on time", side_name), "time forfeit");
+        }
+        clocks[side] += options.increment_ms as i64;
+
+        let legal_move = gen_legal_moves(&position)
+            .into_iter()
+            .find(|halfmove| halfmove.move_to_uci(options.chess960) == best_move);
+
+        let Some(halfmove) = legal_move else {
+            let reason = format!("{} makes an illegal move: {}", side_name, best_move);
+            break (loss, reason, "illegal move");
+        };
+
+        execute_halfmove(&mut position, halfmove);
+        adjudicator.add_move(&position, score);
+
+        move_tokens.push(best_move);
+        moves.push(PlayedMove {
+            halfmove,
+            score,
+            time_ms: elapsed,
+        });
+    };
+
+    return Ok(GameRecord {
+        index,
+        white,
+        opening: opening.clone(),
+        moves,
+        result,
+        reason,
+        termination,
+    });
+}
+
+fn record_to_pgn(record: &GameRecord, names: &[String], options: &MatchOptions) -> String {
+    let mut game = Game::new();
+
+    game.set_tag("Event", "EndGame2 match");
+    game.set_tag("Round", &(record.index + 1).to_string());
+    game.set_tag("White", &names[record.white]);
+    game.set_tag("Black", &names[1 - record.white]);
+    game.set_tag("Result", record.result);
+
+    if options.chess960 {
+        game.set_tag("Variant", "Chess960");
+    }
+    if let Some(fen) = &record.opening.fen {
+        game.set_tag("FEN", fen);
+        game.set_tag("SetUp", "1");
+    }
+
+    game.set_tag(
+        "TimeControl",
+        &format!(
+            "{}+{}",
+            options.base_ms as f64 / 1000.0,
+            options.increment_ms as f64 / 1000.0
+        ),
+    );
+    game.set_tag("Termination", record.termination);
+
+    let mut node = 0;
+    for halfmove in record.opening.moves.iter() {
+        node = game.add_move(node, *halfmove);
+    }
+    if node != 0 {
+        game.nodes[node].comment = Some(String::from("book"));
+    }
+
+    for played in record.moves.iter() {
+        node = game.add_move(node, played.halfmove);
+
+        let score = match played.score {
+            Some(score) if score >= 29000 => format!("+M{}", 30000 - score),
+            Some(score) if score <= -29000 => format!("-M{}", score + 30000),
+            Some(score) => format!("{:+.2}", score as f64 / 100.0),
+            None => String::from("?"),
+        };
+        game.nodes[node].comment =
+            Some(format!("{} {:.3}s", score, played.time_ms as f64 / 1000.0));
+    }
+
+    game.nodes[node].comment = match game.nodes[node].comment.take() {
+        Some(comment) if node != 0 => Some(format!("{}, {}", comment, record.reason)),
+        _ => Some(record.reason.clone()),
+    };
+
+    return game.to_pgn();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: u32, losses: u32, draws: u32) -> MatchScore {
+        return MatchScore {
+            wins,
+            losses,
+            draws,
+        };
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() < 1e-3,
+            "{} is not {}",
+            value,
+            expected
+        );
+    }
+
+    // reference values worked out with the cutechess elo and fishtest sprt formulas
+
+    #[test]
+    fn measures_elo_with_error_bars() {
+        let (elo, margin) = score(100, 50, 50).elo();
+        assert_close(elo, 88.7395);
+        assert_close(margin, 42.8438);
+
+        let (elo, margin) = score(1000, 900, 2100).elo();
+        assert_close(elo, 8.6877);
+        assert_close(margin, 7.4216);
+
+        let (elo, margin) = score(30, 30, 40).elo();
+        assert_close(elo, 0.0);
+        assert_close(margin, 53.1590);
+
+        assert_eq!(score(0, 0, 0).elo(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn weighs_the_hypotheses() {
+        assert_close(score(100, 50, 50).llr(0.0, 5.0), 1.0164);
+        assert_close(score(1000, 900, 2100).llr(0.0, 5.0), 2.1604);
+        assert_close(score(900, 1000, 2100).llr(0.0, 5.0), -3.9066);
+
+        // nothing to go on yet
+        assert_eq!(score(0, 0, 0).llr(0.0, 5.0), 0.0);
+        assert_eq!(score(0, 0, 10).llr(0.0, 5.0), 0.0);
+    }
+
+    #[test]
+    fn stops_at_the_sprt_bounds() {
+        let (lower_bound, upper_bound) = sprt_bounds(0.05, 0.05);
+        assert_close(lower_bound, -2.9444);
+        assert_close(upper_bound, 2.9444);
+
+        let result = |wins, losses, draws| {
+            let llr = score(wins, losses, draws).llr(0.0, 5.0);
+            return sprt_result(llr, lower_bound, upper_bound);
+        };
+
+        assert_eq!(result(1300, 1000, 1700), Some("H1"));
+        assert_eq!(result(2000, 2100, 3900), Some("H0"));
+        assert_eq!(result(1000, 900, 2100), None);
+        assert_eq!(result(900, 1000, 2100), Some("H0"));
+    }
+}
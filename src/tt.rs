@@ -0,0 +1,222 @@
+// Transposition table shared by all search threads. Entries are two atomic words,
+// the key xor'd with the data and the data itself, so a torn write from another
+// thread shows up as a key mismatch instead of a wrong result.
+
+use crate::{HalfMove, HalfmoveFlag};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const ENTRY_SIZE: usize = 16;
+
+const FLAGS: [HalfmoveFlag; 13] = [
+    HalfmoveFlag::KnightPromotion,
+    HalfmoveFlag::BishopPromotion,
+    HalfmoveFlag::RookPromotion,
+    HalfmoveFlag::QueenPromotion,
+    HalfmoveFlag::KingPromotion,
+    HalfmoveFlag::Castle,
+    HalfmoveFlag::EnPassant,
+    HalfmoveFlag::DoublePawnMove,
+    HalfmoveFlag::PawnDrop,
+    HalfmoveFlag::KnightDrop,
+    HalfmoveFlag::BishopDrop,
+    HalfmoveFlag::RookDrop,
+    HalfmoveFlag::QueenDrop,
+];
+
+// what a stored score says about the position. a search cut off by alpha-beta only
+// knows that the score is at least (lower) or at most (upper) what it found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let count = (megabytes.max(1) * 1024 * 1024 / ENTRY_SIZE).max(1);
+
+        Self {
+            entries: (0..count)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for entry in self.entries.iter() {
+            entry[0].store(0, Ordering::Relaxed);
+            entry[1].store(0, Ordering::Relaxed);
+        }
+    }
+
+    // permille of the table in use, estimated from its first entries
+    pub fn hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| entry[1].load(Ordering::Relaxed) != 0)
+            .count();
+
+        return used * 1000 / sample;
+    }
+
+    fn entry(&self, hash: u64) -> &[AtomicU64; 2] {
+        return &self.entries[(hash % self.entries.len() as u64) as usize];
+    }
+
+    fn data(&self, hash: u64) -> Option<u64> {
+        let entry = self.entry(hash);
+        let key = entry[0].load(Ordering::Relaxed);
+        let data = entry[1].load(Ordering::Relaxed);
+
+        if key ^ data != hash || data == 0 {
+            return None;
+        }
+
+        return Some(data);
+    }
+
+    // score, bound and best move of a position searched to at least the given depth
+    pub fn probe(&self, hash: u64, depth: usize) -> Option<(i32, Bound, Option<HalfMove>)> {
+        let data = self.data(hash)?;
+
+        let entry_depth = (data >> 32) as u8 as usize;
+        if entry_depth < depth {
+            return None;
+        }
+
+        let bound = match (data >> 58) & 3 {
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => Bound::Exact,
+        };
+
+        return Some((data as u32 as i32, bound, decode_move((data >> 40) as u32)));
+    }
+
+    // the best move found for a position at any depth
+    pub fn best_move(&self, hash: u64) -> Option<HalfMove> {
+        return decode_move((self.data(hash)? >> 40) as u32);
+    }
+
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: usize,
+        score: i32,
+        bound: Bound,
+        best_move: Option<HalfMove>,
+    ) {
+        let entry = self.entry(hash);
+
+        // keep deeper results for the same position
+        let old_data = entry[1].load(Ordering::Relaxed);
+        let old_key = entry[0].load(Ordering::Relaxed) ^ old_data;
+        if old_key == hash && ((old_data >> 32) as u8 as usize) > depth {
+            return;
+        }
+
+        let bound = match bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+
+        let data = score as u32 as u64
+            | (depth.min(u8::MAX as usize) as u64) << 32
+            | (best_move.map_or(0, encode_move) as u64) << 40
+            | bound << 58;
+
+        entry[0].store(hash ^ data, Ordering::Relaxed);
+        entry[1].store(data, Ordering::Relaxed);
+    }
+}
+
+// from and to squares, the flag and the capture bit in 18 bits, with 0 for no move
+fn encode_move(halfmove: HalfMove) -> u32 {
+    let flag = halfmove
+        .flag
+        .and_then(|flag| FLAGS.iter().position(|&f| f == flag))
+        .map_or(0, |index| index as u32 + 1);
+
+    return 1
+        | (halfmove.from as u32) << 1
+        | (halfmove.to as u32) << 7
+        | flag << 13
+        | (halfmove.is_capture as u32) << 17;
+}
+
+fn decode_move(bits: u32) -> Option<HalfMove> {
+    if bits & 1 == 0 {
+        return None;
+    }
+
+    let flag = (bits >> 13) & 15;
+
+    return Some(HalfMove {
+        from: ((bits >> 1) & 63) as u8,
+        to: ((bits >> 7) & 63) as u8,
+        flag: if flag == 0 {
+            None
+        } else {
+            Some(FLAGS[flag as usize - 1])
+        },
+        is_capture: (bits >> 17) & 1 == 1,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_legal_move, Position};
+
+    #[test]
+    fn keeps_scores_bounds_and_moves() {
+        let tt = TranspositionTable::new(1);
+        let position = Position::startpos();
+        let halfmove = parse_legal_move(&position, "e2e4", false);
+
+        tt.store(42, 5, -123, Bound::Lower, halfmove);
+
+        assert_eq!(tt.probe(42, 5), Some((-123, Bound::Lower, halfmove)));
+        assert_eq!(tt.probe(42, 6), None);
+        assert_eq!(tt.probe(43, 1), None);
+        assert_eq!(tt.best_move(42), halfmove);
+    }
+
+    #[test]
+    fn prefers_deeper_results() {
+        let tt = TranspositionTable::new(1);
+
+        tt.store(7, 6, 10, Bound::Exact, None);
+        tt.store(7, 3, 20, Bound::Upper, None);
+        assert_eq!(tt.probe(7, 0), Some((10, Bound::Exact, None)));
+
+        tt.store(7, 8, 30, Bound::Upper, None);
+        assert_eq!(tt.probe(7, 0), Some((30, Bound::Upper, None)));
+
+        tt.clear();
+        assert_eq!(tt.probe(7, 0), None);
+    }
+
+    #[test]
+    fn encodes_every_kind_of_move() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3",
+        ];
+
+        for fen in fens {
+            let position = Position::from_fen(fen).unwrap();
+            for halfmove in crate::gen_legal_moves(&position) {
+                assert_eq!(decode_move(encode_move(halfmove)), Some(halfmove));
+            }
+        }
+    }
+}
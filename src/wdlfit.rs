@@ -0,0 +1,205 @@
+// Fits the win/draw/loss model behind UCI_ShowWDL to played games. Every move played
+// by an engine in a match PGN carries its score, and the game result says how a
+// position with that score turned out for the side that moved. The model parameters
+// are the ones that make those results most likely.
+//
+// usage: endgame2 wdlfit <games.pgn>...
+
+use crate::pgn::{read_pgn_file, Game};
+use crate::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+pub fn wdlfit_command(args: &[String]) {
+    if args.is_empty() {
+        println!("Error - no PGN files given");
+        println!("usage: wdlfit <games.pgn>...");
+        return;
+    }
+
+    let mut samples = vec![];
+    let mut game_count = 0;
+    let mut skipped_count = 0;
+
+    for input in args.iter() {
+        let games = match read_pgn_file(input) {
+            Ok(games) => games,
+            Err(e) => {
+                println!("Error - {}", e);
+                continue;
+            }
+        };
+
+        for game in games.iter() {
+            match game.as_ref().ok().and_then(scored_positions) {
+                Some(positions) => {
+                    samples.extend(positions);
+                    game_count += 1;
+                }
+                None => skipped_count += 1,
+            }
+        }
+    }
+
+    if samples.is_empty() {
+        println!("Error - no scored moves found");
+        return;
+    }
+
+    let (a, b) = fit(&samples);
+
+    println!(
+        "Games: {} (skipped {})\nPositions: {}\nWDL_MODEL_A: {:.1}\nWDL_MODEL_B: {:.1}",
+        game_count,
+        skipped_count,
+        samples.len(),
+        a,
+        b
+    );
+}
+
+// the score of every move from the engine that played it, with how the game ended for
+// that side. book moves and mate scores say nothing about the model. none for a game
+// without a result
+fn scored_positions(game: &Game) -> Option<Vec<(i32, Outcome)>> {
+    let winner = match game.result() {
+        "1-0" => Some(Color::White),
+        "0-1" => Some(Color::Black),
+        "1/2-1/2" => None,
+        _ => return None,
+    };
+    let mut mover = game.start_position().ok()?.move_next;
+
+    let mut positions = vec![];
+    let mut node = 0;
+
+    while let Some(&child) = game.nodes[node].children.first() {
+        let score = game.nodes[child]
+            .comment
+            .as_deref()
+            .and_then(|comment| comment.split_whitespace().next())
+            .filter(|token| !token.contains('M'))
+            .and_then(|token| token.parse::<f64>().ok());
+
+        if let Some(score) = score {
+            let outcome = match winner {
+                Some(color) if color == mover => Outcome::Win,
+                Some(_) => Outcome::Loss,
+                None => Outcome::Draw,
+            };
+            positions.push(((score * 100.0).round() as i32, outcome));
+        }
+
+        mover = mover.opposite();
+        node = child;
+    }
+
+    return Some(positions);
+}
+
+// the model of info::win_rate, as a chance
+fn win_chance(score: i32, a: f64, b: f64) -> f64 {
+    let score = score.clamp(-8000, 8000) as f64;
+    return 1.0 / (1.0 + ((a - score) / b).exp());
+}
+
+fn log_likelihood(samples: &[(i32, Outcome)], a: f64, b: f64) -> f64 {
+    return samples
+        .iter()
+        .map(|&(score, outcome)| {
+            let win = win_chance(score, a, b);
+            let loss = win_chance(-score, a, b);
+            let chance = match outcome {
+                Outcome::Win => win,
+                Outcome::Loss => loss,
+                Outcome::Draw => 1.0 - win - loss,
+            };
+
+            return chance.max(1e-12).ln();
+        })
+        .sum();
+}
+
+// maximum likelihood a and b, by a pattern search that moves to the best neighbour
+// and halves its steps once the current point is better than all of them. a stays
+// positive, or the model has no room left for draws
+fn fit(samples: &[(i32, Outcome)]) -> (f64, f64) {
+    let (mut a, mut b) = (200.0, 100.0);
+    let (mut a_step, mut b_step) = (100.0, 50.0);
+    let mut best = log_likelihood(samples, a, b);
+
+    while a_step > 0.01 || b_step > 0.01 {
+        let mut moved = false;
+
+        for (da, db) in [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)] {
+            let next_a = (a + da * a_step).max(0.0);
+            let next_b = (b + db * b_step).max(1.0);
+            let likelihood = log_likelihood(samples, next_a, next_b);
+
+            if likelihood > best {
+                (a, b, best) = (next_a, next_b, likelihood);
+                moved = true;
+            }
+        }
+
+        if !moved {
+            a_step /= 2.0;
+            b_step /= 2.0;
+        }
+    }
+
+    return (a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse_pgn;
+
+    #[test]
+    fn reads_scores_for_the_mover() {
+        let games = parse_pgn(
+            "[Result \"0-1\"]\n\n1. e4 {book} e5 {-0.35 0.100s} 2. Nf3 {+1.20 0.100s} \
+             Nc6 {?} 3. Bc4 {-M2 0.100s} 0-1\n",
+        );
+        let positions = scored_positions(games[0].as_ref().unwrap()).unwrap();
+
+        assert_eq!(positions, [(-35, Outcome::Win), (120, Outcome::Loss)]);
+
+        let unfinished = parse_pgn("[Result \"*\"]\n\n1. e4 {+0.10 0.100s} *\n");
+        assert!(scored_positions(unfinished[0].as_ref().unwrap()).is_none());
+    }
+
+    #[test]
+    fn finds_the_model_behind_the_results() {
+        let (a, b) = (300.0, 80.0);
+
+        // every score played out often enough that the results follow the model
+        let mut samples = vec![];
+        for score in (-800..=800).step_by(8) {
+            let win = win_chance(score, a, b);
+            let loss = win_chance(-score, a, b);
+
+            for i in 0..40 {
+                let quantile = (i as f64 + 0.5) / 40.0;
+                let outcome = if quantile < win {
+                    Outcome::Win
+                } else if quantile > 1.0 - loss {
+                    Outcome::Loss
+                } else {
+                    Outcome::Draw
+                };
+                samples.push((score, outcome));
+            }
+        }
+
+        let (fitted_a, fitted_b) = fit(&samples);
+        assert!((fitted_a - a).abs() < 10.0, "a = {}", fitted_a);
+        assert!((fitted_b - b).abs() < 5.0, "b = {}", fitted_b);
+    }
+}
@@ -0,0 +1,603 @@
+// The xboard (CECP) front end, for programs that speak the winboard protocol instead of
+// uci. It is picked by sending xboard as the first command. The engine thinks on its own
+// thread with the same search as uci, so input like ? and force is read while it does.
+
+use crate::adjudication::{AdjudicationRules, Adjudicator};
+use crate::info::{InfoSink, Score, SearchInfo, SilentInfo};
+use crate::tt::TranspositionTable;
+use crate::{execute_halfmove, gen_legal_moves, parse_legal_move};
+use crate::{
+    Color, Engine, HalfMove, HalfmoveFlag, Position, SearchLimits, Variant, DEFAULT_NODES,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// how long hint searches when the last search left no expected reply
+const HINT_MOVETIME: u64 = 500;
+
+// as set by level, st and sd, and the clocks from time and otim
+#[derive(Default)]
+struct TimeControl {
+    // moves per session, 0 when the base time is for the whole game
+    moves_per_session: u64,
+    // ms
+    base: u64,
+    increment: u64,
+    move_time: Option<u64>,
+    depth: Option<usize>,
+    // ms left on the engine's and the opponent's clock
+    own_time: Option<u64>,
+    opponent_time: Option<u64>,
+}
+
+struct XboardState {
+    engine: Engine,
+    // the game so far, kept for undo
+    start: Position,
+    moves: Vec<HalfMove>,
+    adjudicator: Adjudicator,
+    game_over: bool,
+    // the engine plays neither side
+    force: bool,
+    engine_color: Color,
+    time_control: TimeControl,
+    post: Arc<AtomicBool>,
+    // the reply the last search expected
+    hint: Option<HalfMove>,
+}
+
+// a running search, and whether its move is still wanted
+struct Thinking {
+    handle: JoinHandle<()>,
+    discard: Arc<AtomicBool>,
+}
+
+// prints thinking output when post is on, and keeps the expected reply for hint
+struct XboardInfo {
+    position: Position,
+    post: Arc<AtomicBool>,
+    ponder: Mutex<Option<HalfMove>>,
+}
+
+pub fn xboard_loop(lines: impl Iterator<Item = String>) {
+    let state = Arc::new(Mutex::new(XboardState::new()));
+    let mut thinking: Option<Thinking> = None;
+
+    for input in lines {
+        let mut tokens = input.split_whitespace();
+        let Some(command) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match command {
+            // these leave a running search alone
+            "?" => {
+                if thinking.is_some() {
+                    state.lock().unwrap().engine.stop.stop();
+                }
+            }
+            "post" => state.lock().unwrap().post.store(true, Ordering::Relaxed),
+            "nopost" => state.lock().unwrap().post.store(false, Ordering::Relaxed),
+            "time" | "otim" => clock_command(command, &args, &state),
+            "ping" => println!("pong {}", args.first().unwrap_or(&"")),
+            "hint" => hint_command(&state, thinking.is_some()),
+            "protover" => protover_command(),
+            "accepted" | "rejected" | "computer" | "name" | "rating" | "ics" | "easy" | "hard"
+            | "random" | "draw" | "." => {}
+            "quit" => {
+                interrupt(&mut thinking, &state);
+                return;
+            }
+            _ => {
+                interrupt(&mut thinking, &state);
+                run_command(command, &args, &state);
+
+                if state.lock().unwrap().engine_to_move() {
+                    thinking = Some(think(&state));
+                }
+            }
+        }
+    }
+
+    // end of input is treated like quit
+    interrupt(&mut thinking, &state);
+}
+
+fn protover_command() {
+    println!(
+        "feature done=0 myname=\"EndGame2\" ping=1 setboard=1 playother=1 usermove=1 time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 memory=1 smp=1"
+    );
+    println!("feature variants=\"normal,fischerandom,crazyhouse,atomic,kingofthehill,3check\"");
+    println!("feature done=1");
+}
+
+// the commands that end a running search first, without playing its move
+fn run_command(command: &str, args: &[&str], state: &Arc<Mutex<XboardState>>) {
+    let mut state = state.lock().unwrap();
+
+    match command {
+        "new" => {
+            state.engine.tt.clear();
+            state.engine.options.variant = Variant::Standard;
+            state.engine.options.chess960 = false;
+            state.reset(Position::startpos());
+            state.check_result();
+            state.force = false;
+            state.engine_color = Color::Black;
+            state.time_control.depth = None;
+            state.time_control.own_time = None;
+            state.time_control.opponent_time = None;
+        }
+        "variant" => {
+            let (variant, chess960) = match args.first() {
+                Some(&"normal") => (Variant::Standard, false),
+                Some(&"fischerandom") => (Variant::Standard, true),
+                Some(name) => match Variant::from_name(name) {
+                    Some(variant) if variant != Variant::Antichess => (variant, false),
+                    _ => {
+                        println!("Error (unsupported variant): {}", name);
+                        return;
+                    }
+                },
+                None => {
+                    println!("Error (missing variant): variant");
+                    return;
+                }
+            };
+
+            state.engine.options.variant = variant;
+            state.engine.options.chess960 = chess960;
+
+            let mut position = Position::startpos();
+            position.variant = variant;
+            state.reset(position);
+            state.check_result();
+        }
+        "force" => state.force = true,
+        "go" => {
+            state.force = false;
+            state.engine_color = state.engine.position.move_next;
+        }
+        "playother" => {
+            state.force = false;
+            state.engine_color = state.engine.position.move_next.opposite();
+        }
+        "usermove" => {
+            let Some(move_string) = args.first() else {
+                println!("Error (missing move): usermove");
+                return;
+            };
+
+            let chess960 = state.engine.options.chess960;
+            match parse_legal_move(&state.engine.position, move_string, chess960) {
+                Some(halfmove) => {
+                    state.hint = None;
+                    state.play(halfmove);
+                    state.check_result();
+                }
+                None => println!("Illegal move: {}", move_string),
+            }
+        }
+        "level" => match parse_level(args) {
+            Some((moves_per_session, base, increment)) => {
+                let time_control = &mut state.time_control;
+                time_control.moves_per_session = moves_per_session;
+                time_control.base = base;
+                time_control.increment = increment;
+                time_control.move_time = None;
+                time_control.own_time = None;
+                time_control.opponent_time = None;
+            }
+            None => println!("Error (bad time control): level {}", args.join(" ")),
+        },
+        "st" => match args.first().and_then(|seconds| seconds.parse::<f64>().ok()) {
+            Some(seconds) if seconds > 0.0 => {
+                state.time_control.move_time = Some((seconds * 1000.0) as u64)
+            }
+            _ => println!("Error (bad time): st {}", args.join(" ")),
+        },
+        "sd" => match args.first().and_then(|depth| depth.parse::<usize>().ok()) {
+            Some(depth) if depth > 0 => state.time_control.depth = Some(depth),
+            _ => println!("Error (bad depth): sd {}", args.join(" ")),
+        },
+        "undo" => state.take_back(1),
+        "remove" => state.take_back(2),
+        "setboard" => {
+            let fen = args.join(" ");
+            let variant = state.engine.options.variant;
+
+            match Position::from_variant_fen(&fen, variant) {
+                Ok(position) => {
+                    // a position that is already mate or stalemate gets its result at once
+                    state.reset(position);
+                    state.check_result();
+                }
+                Err(e) => println!("tellusererror Illegal position: {}", e),
+            }
+        }
+        // the gui decided the game, so the engine stops playing until the next new
+        "result" => state.game_over = true,
+        "memory" => match args.first().and_then(|size| size.parse::<usize>().ok()) {
+            Some(size) if size >= 1 => {
+                state.engine.options.hash_size = size;
+                state.engine.tt = Arc::new(TranspositionTable::new(size));
+            }
+            _ => println!("Error (bad size): memory {}", args.join(" ")),
+        },
+        "cores" => match args.first().and_then(|cores| cores.parse::<usize>().ok()) {
+            Some(cores) if (1..=256).contains(&cores) => state.engine.options.threads = cores,
+            _ => println!("Error (bad number): cores {}", args.join(" ")),
+        },
+        "xboard" => {}
+        _ => println!("Error (unknown command): {}", command),
+    }
+}
+
+// level MPS BASE INC, with the base in minutes or minutes:seconds and the increment in seconds
+fn parse_level(args: &[&str]) -> Option<(u64, u64, u64)> {
+    let [moves_per_session, base, increment] = args else {
+        return None;
+    };
+
+    let moves_per_session = moves_per_session.parse::<u64>().ok()?;
+
+    let base = match base.split_once(':') {
+        Some((minutes, seconds)) => {
+            minutes.parse::<u64>().ok()? * 60_000 + seconds.parse::<u64>().ok()? * 1000
+        }
+        None => base.parse::<u64>().ok()? * 60_000,
+    };
+
+    let increment = increment.parse::<f64>().ok().filter(|inc| *inc >= 0.0)?;
+
+    return Some((moves_per_session, base, (increment * 1000.0) as u64));
+}
+
+// time and otim give the clocks in centiseconds
+fn clock_command(command: &str, args: &[&str], state: &Mutex<XboardState>) {
+    let Some(centiseconds) = args.first().and_then(|time| time.parse::<i64>().ok()) else {
+        println!("Error (bad time): {} {}", command, args.join(" "));
+        return;
+    };
+
+    // a flagged clock can go below zero
+    let ms = Some(centiseconds.max(0) as u64 * 10);
+
+    let mut state = state.lock().unwrap();
+    if command == "time" {
+        state.time_control.own_time = ms;
+    } else {
+        state.time_control.opponent_time = ms;
+    }
+}
+
+fn hint_command(state: &Mutex<XboardState>, searching: bool) {
+    let (position, hint, searcher) = {
+        let state = state.lock().unwrap();
+        (
+            state.engine.position.clone(),
+            state.hint,
+            state.engine.searcher(),
+        )
+    };
+
+    let hint = match hint {
+        Some(hint) => Some(hint),
+        // a search of its own would share the stop with the running one
+        None if searching || gen_legal_moves(&position).is_empty() => None,
+        None => {
+            let limits = SearchLimits {
+                movetime: Some(HINT_MOVETIME),
+                ..Default::default()
+            };
+            searcher
+                .search(position.clone(), &limits, &SilentInfo)
+                .moves
+                .first()
+                .copied()
+        }
+    };
+
+    if let Some(hint) = hint {
+        let state = state.lock().unwrap();
+        println!("Hint: {}", state.move_string(hint));
+    }
+}
+
+// ends a running search, dropping its move if it hasn't been played yet
+fn interrupt(thinking: &mut Option<Thinking>, state: &Mutex<XboardState>) {
+    let Some(thinking) = thinking.take() else {
+        return;
+    };
+
+    thinking.discard.store(true, Ordering::Relaxed);
+
+    let stop = state.lock().unwrap().engine.stop.clone();
+    stop.stop();
+    let _ = thinking.handle.join();
+    stop.reset();
+}
+
+fn think(state: &Arc<Mutex<XboardState>>) -> Thinking {
+    let discard = Arc::new(AtomicBool::new(false));
+
+    let (searcher, position, limits, book_move, sink) = {
+        let state = state.lock().unwrap();
+        let position = state.engine.position.clone();
+
+        (
+            state.engine.searcher(),
+            position.clone(),
+            state.limits(),
+            state.engine.book_move(&position),
+            XboardInfo {
+                position,
+                post: Arc::clone(&state.post),
+                ponder: Mutex::new(None),
+            },
+        )
+    };
+
+    let handle = {
+        let state = Arc::clone(state);
+        let discard = Arc::clone(&discard);
+
+        thread::spawn(move || {
+            // nothing to play once the game is over on the board
+            if gen_legal_moves(&position).is_empty() {
+                return;
+            }
+
+            let best = match book_move {
+                Some(book_move) => book_move,
+                None => searcher.search(position, &limits, &sink).moves[0],
+            };
+
+            let mut state = state.lock().unwrap();
+            if discard.load(Ordering::Relaxed) {
+                return;
+            }
+
+            println!("move {}", state.move_string(best));
+            state.play(best);
+            state.hint = *sink.ponder.lock().unwrap();
+            state.check_result();
+        })
+    };
+
+    return Thinking { handle, discard };
+}
+
+impl XboardState {
+    fn new() -> Self {
+        let engine = Engine::new();
+        let start = engine.position.clone();
+
+        Self {
+            adjudicator: Adjudicator::new(AdjudicationRules::default(), &start),
+            engine,
+            start,
+            moves: vec![],
+            game_over: false,
+            force: false,
+            engine_color: Color::Black,
+            time_control: TimeControl::default(),
+            post: Arc::new(AtomicBool::new(false)),
+            hint: None,
+        }
+    }
+
+    // starts over from position, forgetting the moves of the old game
+    fn reset(&mut self, position: Position) {
+        self.engine.set_position(position.clone());
+        self.adjudicator = Adjudicator::new(AdjudicationRules::default(), &position);
+        self.start = position;
+        self.moves.clear();
+        self.game_over = false;
+        self.hint = None;
+    }
+
+    fn play(&mut self, halfmove: HalfMove) {
+        self.engine.play(halfmove);
+        self.adjudicator.add_move(&self.engine.position, None);
+        self.moves.push(halfmove);
+    }
+
+    // replays the game without its last plies, which also takes back a finished game
+    fn take_back(&mut self, plies: usize) {
+        let mut moves = self.moves.clone();
+        moves.truncate(moves.len().saturating_sub(plies));
+
+        self.reset(self.start.clone());
+        for halfmove in moves {
+            self.play(halfmove);
+        }
+    }
+
+    // prints the result once the game has ended by the rules
+    fn check_result(&mut self) {
+        if let Some(adjudication) = self.adjudicator.check(&self.engine.position) {
+            println!("{} {{{}}}", adjudication.result, adjudication.reason);
+            self.game_over = true;
+        }
+    }
+
+    fn engine_to_move(&self) -> bool {
+        return !self.force
+            && !self.game_over
+            && self.engine_color == self.engine.position.move_next;
+    }
+
+    // coordinates, except for fischer random castling which xboard wants as O-O
+    fn move_string(&self, halfmove: HalfMove) -> String {
+        if self.engine.options.chess960 && halfmove.flag == Some(HalfmoveFlag::Castle) {
+            return halfmove.move_to_san(&self.engine.position);
+        }
+
+        return halfmove.move_to_uci(false);
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let time_control = &self.time_control;
+        let mut limits = SearchLimits {
+            depth: time_control.depth,
+            movetime: time_control.move_time,
+            ..Default::default()
+        };
+
+        if time_control.move_time.is_none()
+            && (time_control.base > 0 || time_control.own_time.is_some())
+        {
+            let own_time = time_control.own_time.unwrap_or(time_control.base);
+            let opponent_time = time_control.opponent_time.unwrap_or(time_control.base);
+            let increment = time_control.increment;
+
+            limits.clock = Some(if self.engine.position.move_next == Color::White {
+                [own_time, opponent_time, increment, increment]
+            } else {
+                [opponent_time, own_time, increment, increment]
+            });
+
+            // moves left until the next time control
+            if time_control.moves_per_session > 0 {
+                let played = (self.engine.position.fullmove_number as u64 - 1)
+                    % time_control.moves_per_session;
+                limits.moves_to_go = Some(time_control.moves_per_session - played);
+            }
+        }
+
+        // as with a bare go, no time control at all searches a fixed number of nodes
+        if limits.is_unbounded() {
+            limits.nodes = Some(DEFAULT_NODES);
+        }
+
+        return limits;
+    }
+}
+
+impl InfoSink for XboardInfo {
+    // ply, score, time in centiseconds, nodes and the pv
+    fn info(&self, info: &SearchInfo) {
+        if !self.post.load(Ordering::Relaxed) {
+            return;
+        }
+
+        // mates are shown as 100000 plus the moves to mate
+        let score = match info.score {
+            Score::Cp(score) => score,
+            Score::Mate(moves) if moves > 0 => 100000 + moves,
+            Score::Mate(moves) => -100000 + moves,
+        };
+
+        let mut position = self.position.clone();
+        let mut pv = vec![];
+        for &halfmove in info.pv.iter() {
+            pv.push(halfmove.move_to_san(&position));
+            execute_halfmove(&mut position, halfmove);
+        }
+
+        println!(
+            "{} {} {} {} {}",
+            info.depth,
+            score,
+            info.time / 10,
+            info.nodes,
+            pv.join(" ")
+        );
+    }
+
+    fn bestmove(&self, _best: Option<HalfMove>, ponder: Option<HalfMove>) {
+        *self.ponder.lock().unwrap() = ponder;
+    }
+
+    fn message(&self, text: &str) {
+        println!("# {}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(state: &Arc<Mutex<XboardState>>, input: &str) {
+        let mut tokens = input.split_whitespace();
+        let command = tokens.next().unwrap();
+        let args: Vec<&str> = tokens.collect();
+
+        run_command(command, &args, state);
+    }
+
+    fn new_state() -> Arc<Mutex<XboardState>> {
+        let state = Arc::new(Mutex::new(XboardState::new()));
+        command(&state, "new");
+
+        return state;
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!(parse_level(&["40", "5", "0"]), Some((40, 300_000, 0)));
+        assert_eq!(parse_level(&["0", "2:30", "1.5"]), Some((0, 150_000, 1500)));
+        assert_eq!(parse_level(&["0", "5"]), None);
+        assert_eq!(parse_level(&["0", "5", "-1"]), None);
+        assert_eq!(parse_level(&["x", "5", "0"]), None);
+    }
+
+    #[test]
+    fn ends_the_game_on_a_finished_setboard() {
+        let state = new_state();
+
+        command(&state, "setboard 7k/6Q1/6K1/8/8/8/8/8 b - - 0 1");
+        command(&state, "go");
+
+        let state = state.lock().unwrap();
+        assert!(state.game_over);
+        assert!(!state.engine_to_move());
+    }
+
+    #[test]
+    fn takes_moves_back() {
+        let state = new_state();
+
+        command(&state, "force");
+        for halfmove in ["e2e4", "e7e5", "g1f3"] {
+            command(&state, &format!("usermove {}", halfmove));
+        }
+        command(&state, "usermove e1e3");
+        assert_eq!(state.lock().unwrap().moves.len(), 3);
+
+        command(&state, "remove");
+        command(&state, "undo");
+        assert_eq!(
+            state.lock().unwrap().engine.position.to_fen(),
+            Position::startpos().to_fen()
+        );
+    }
+
+    #[test]
+    fn plays_a_move_when_asked() {
+        let state = new_state();
+
+        command(&state, "sd 2");
+        command(&state, "usermove e2e4");
+        assert!(state.lock().unwrap().engine_to_move());
+
+        think(&state).handle.join().unwrap();
+
+        let state = state.lock().unwrap();
+        assert_eq!(state.moves.len(), 2);
+        assert!(!state.engine_to_move());
+    }
+
+    #[test]
+    fn stays_quiet_when_there_is_no_move() {
+        let state = new_state();
+
+        command(&state, "setboard 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        think(&state).handle.join().unwrap();
+
+        assert!(state.lock().unwrap().moves.is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+#![allow(clippy::needless_return)]
+
+// runs the analysis server on a loopback port and talks to it as a client would
+
+use endgame2::json::Json;
+use endgame2::serve::serve;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::OnceLock;
+use std::thread;
+
+// one server for all the tests, on a port picked by the system
+fn server() -> SocketAddr {
+    static ADDRESS: OnceLock<SocketAddr> = OnceLock::new();
+
+    return *ADDRESS.get_or_init(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener));
+
+        return address;
+    });
+}
+
+// the status and the body of the response
+fn request(method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(server()).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+
+    return (status, body.to_string());
+}
+
+fn post(path: &str, body: &str) -> (u16, Json) {
+    let (status, body) = request("POST", path, body);
+    return (status, Json::parse(body.trim()).unwrap());
+}
+
+#[test]
+fn lists_legal_moves() {
+    let (status, body) = post("/moves", "");
+    assert_eq!(status, 200);
+    assert_eq!(
+        body.get("moves").and_then(Json::as_array).unwrap().len(),
+        20
+    );
+
+    let (status, body) = post("/moves", r#"{"moves": ["e2e4", "e7e5", "Nf3"]}"#);
+    assert_eq!(status, 200);
+    assert_eq!(
+        body.get("fen").and_then(Json::as_str),
+        Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+    );
+}
+
+#[test]
+fn counts_perft() {
+    let (status, body) = post("/perft", r#"{"depth": 3}"#);
+    assert_eq!(status, 200);
+    assert_eq!(body.get("nodes").and_then(Json::as_u64), Some(8902));
+    assert_eq!(
+        body.get("moves")
+            .and_then(|moves| moves.get("e2e4"))
+            .and_then(Json::as_u64),
+        Some(600)
+    );
+
+    let (status, _) = post("/perft", r#"{"depth": 9}"#);
+    assert_eq!(status, 400);
+}
+
+#[test]
+fn evaluates_positions() {
+    let (status, body) = post("/eval", r#"{"fen": "4k3/8/8/8/8/8/8/QQQQK3 w - - 0 1"}"#);
+    assert_eq!(status, 200);
+    assert!(matches!(body.get("eval"), Some(Json::Number(eval)) if *eval > 1000.0));
+}
+
+#[test]
+fn streams_an_analysis() {
+    let (status, body) = request("POST", "/analyse", r#"{"limits": {"depth": 3}}"#);
+    assert_eq!(status, 200);
+
+    let events: Vec<Json> = body
+        .lines()
+        .map(|line| Json::parse(line).unwrap())
+        .collect();
+    let last = events.last().unwrap();
+
+    assert!(events.len() > 1);
+    assert_eq!(last.get("type").and_then(Json::as_str), Some("bestmove"));
+    assert!(last.get("bestmove").and_then(Json::as_str).is_some());
+}
+
+#[test]
+fn answers_a_finished_game() {
+    let (status, body) = request(
+        "POST",
+        "/analyse",
+        r#"{"fen": "7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", "limits": {"depth": 2}}"#,
+    );
+    assert_eq!(status, 200);
+
+    let last = Json::parse(body.lines().last().unwrap()).unwrap();
+    assert_eq!(last.get("bestmove"), Some(&Json::Null));
+}
+
+#[test]
+fn refuses_bad_requests() {
+    assert_eq!(post("/moves", "{").0, 400);
+    assert_eq!(post("/moves", "[1, 2]").0, 400);
+    assert_eq!(post("/moves", r#"{"fen": "not a fen"}"#).0, 400);
+    assert_eq!(post("/moves", r#"{"moves": ["e2e5"]}"#).0, 400);
+    assert_eq!(post("/analyse", r#"{"limits": {"depth": "deep"}}"#).0, 400);
+    assert_eq!(post("/nowhere", "").0, 404);
+    assert_eq!(request("GET", "/moves", "").0, 405);
+}
+
+#[test]
+fn survives_deep_nesting() {
+    assert_eq!(post("/moves", &"[".repeat(200000)).0, 400);
+
+    // still serving
+    assert_eq!(post("/moves", "").0, 200);
+}
@@ -0,0 +1,213 @@
+#![allow(clippy::needless_return)]
+
+// probes the official 3- and 4-man tables in tests/fixtures/syzygy against endgame theory
+// and the values other probers read from them
+
+use endgame2::syzygy::{Tablebase, Wdl};
+use endgame2::{execute_halfmove, gen_legal_moves, info, Engine, Position, SearchLimits};
+use std::sync::{Arc, OnceLock};
+
+fn tablebase() -> &'static Tablebase {
+    static TABLEBASE: OnceLock<Tablebase> = OnceLock::new();
+
+    return TABLEBASE.get_or_init(|| {
+        Tablebase::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/syzygy"
+        ))
+    });
+}
+
+fn wdl(fen: &str) -> Option<Wdl> {
+    return tablebase().probe_wdl(&Position::from_fen(fen).unwrap());
+}
+
+fn dtz(fen: &str) -> Option<i32> {
+    return tablebase().probe_dtz(&Position::from_fen(fen).unwrap());
+}
+
+fn filtered(fen: &str) -> Vec<String> {
+    let position = Position::from_fen(fen).unwrap();
+    let mut moves: Vec<String> = tablebase()
+        .filter_root_moves(&position)
+        .unwrap()
+        .iter()
+        .map(|halfmove| halfmove.move_to_uci(false))
+        .collect();
+    moves.sort();
+
+    return moves;
+}
+
+#[test]
+fn opens_the_fixtures() {
+    assert_eq!(tablebase().table_count(), 14);
+    assert_eq!(tablebase().max_pieces(), 4);
+
+    let position = Position::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+    assert!(tablebase().can_probe(&position, 7));
+    assert!(!tablebase().can_probe(&position, 2));
+    assert!(!tablebase().can_probe(&Position::startpos(), 7));
+}
+
+#[test]
+fn probes_rook_endings() {
+    assert_eq!(wdl("8/8/8/4k3/8/8/8/R3K3 w - - 0 1"), Some(Wdl::Win));
+    assert_eq!(wdl("8/8/8/4k3/8/8/8/R3K3 b - - 0 1"), Some(Wdl::Loss));
+
+    // the king takes the rook
+    assert_eq!(wdl("8/8/8/8/8/8/1k6/R6K b - - 0 1"), Some(Wdl::Draw));
+    // stalemate
+    assert_eq!(wdl("k7/8/K7/8/8/8/8/1R6 b - - 0 1"), Some(Wdl::Draw));
+
+    assert_eq!(wdl("8/8/8/4k3/8/8/8/Q3K3 b - - 0 1"), Some(Wdl::Loss));
+    assert_eq!(wdl("8/8/8/4k3/8/8/8/B3K3 w - - 0 1"), Some(Wdl::Draw));
+    assert_eq!(wdl("8/8/8/4k3/8/8/8/N3K3 w - - 0 1"), Some(Wdl::Draw));
+}
+
+#[test]
+fn probes_pawn_endings() {
+    // the king can't catch the pawn
+    assert_eq!(wdl("8/8/8/8/8/8/4P3/4K2k w - - 0 1"), Some(Wdl::Win));
+    // rook pawn with the defending king in the corner
+    assert_eq!(wdl("k7/8/8/8/P7/8/8/4K3 w - - 0 1"), Some(Wdl::Draw));
+
+    // the king in front of its pawn wins with the opposition and only then
+    assert_eq!(wdl("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(Wdl::Draw));
+    assert_eq!(wdl("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"), Some(Wdl::Loss));
+    assert_eq!(wdl("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"), Some(Wdl::Loss));
+
+    // stalemate
+    assert_eq!(wdl("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(Wdl::Draw));
+}
+
+#[test]
+fn probes_either_color() {
+    assert_eq!(wdl("r3k3/8/8/8/4K3/8/8/8 b - - 0 1"), Some(Wdl::Win));
+    assert_eq!(wdl("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1"), Some(Wdl::Loss));
+    assert_eq!(wdl("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1"), Some(Wdl::Draw));
+    assert_eq!(dtz("7r/8/8/8/8/1k6/8/K7 b - - 0 1"), Some(1));
+}
+
+#[test]
+fn counts_plies_to_zeroing() {
+    // mate in one
+    assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R w - - 0 1"), Some(1));
+    // the only move walks into it
+    assert_eq!(dtz("k7/8/1K6/8/8/8/8/7R b - - 0 1"), Some(-2));
+
+    // a pawn move is the zeroing move
+    assert_eq!(dtz("8/8/8/8/8/8/4P3/4K2k w - - 0 1"), Some(1));
+    assert_eq!(dtz("k7/8/8/8/P7/8/8/4K3 w - - 0 1"), Some(0));
+
+    // the longest mates with a rook and with a queen, sixteen and ten moves
+    assert_eq!(dtz("8/8/8/8/8/2k5/1R6/K7 w - - 0 1"), Some(31));
+    assert_eq!(dtz("8/8/8/5k2/8/8/1Q6/K7 w - - 0 1"), Some(19));
+}
+
+#[test]
+fn keeps_root_moves_that_win() {
+    assert_eq!(filtered("k7/8/1K6/8/8/8/8/7R w - - 0 1"), ["h1h8"]);
+
+    // every move saving the rook wins, the ones leaving it draw
+    let fen = "8/8/8/8/8/8/1k6/R3K3 w - - 0 1";
+    let position = Position::from_fen(fen).unwrap();
+    let moves = filtered(fen);
+
+    assert!(!moves.is_empty());
+    assert!(!moves.contains(&"e1e2".to_string()));
+    for halfmove in gen_legal_moves(&position) {
+        let mut new_pos = position.clone();
+        execute_halfmove(&mut new_pos, halfmove);
+
+        if moves.contains(&halfmove.move_to_uci(false)) {
+            assert_eq!(tablebase().probe_wdl(&new_pos), Some(Wdl::Loss));
+        }
+    }
+
+    // nothing to lose in a draw
+    let fen = "k7/8/8/8/P7/8/8/4K3 w - - 0 1";
+    let position = Position::from_fen(fen).unwrap();
+    assert_eq!(filtered(fen).len(), gen_legal_moves(&position).len());
+}
+
+#[test]
+fn searches_only_winning_moves() {
+    let mut engine = Engine::new();
+    engine.tablebase = Arc::new(Tablebase::open(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/syzygy"
+    )));
+
+    let position = Position::from_fen("8/8/8/8/8/8/1k6/R3K3 w - - 0 1").unwrap();
+    let limits = SearchLimits {
+        depth: Some(2),
+        ..Default::default()
+    };
+    let result = engine
+        .searcher()
+        .search(position.clone(), &limits, &info::SilentInfo);
+
+    let best = result.moves[0];
+    let mut new_pos = position;
+    execute_halfmove(&mut new_pos, best);
+    assert_eq!(tablebase().probe_wdl(&new_pos), Some(Wdl::Loss));
+}
+
+#[test]
+fn probes_four_man_endings() {
+    // the rook takes the rook with check
+    assert_eq!(wdl("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1"), Some(Wdl::Win));
+    assert_eq!(dtz("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1"), Some(1));
+    // the king has to step out of check and leaves its rook to be taken
+    assert_eq!(wdl("4k3/8/8/8/8/8/8/R3K2r w - - 0 1"), Some(Wdl::Loss));
+
+    // rook against bishop is mostly a draw, but not with the bishop lost
+    assert_eq!(wdl("8/8/8/3k4/8/8/8/R5Kb w - - 0 1"), Some(Wdl::Win));
+    assert_eq!(wdl("b3k3/8/8/8/8/8/8/R3K3 b - - 0 1"), Some(Wdl::Draw));
+}
+
+// from the test suite of shakmaty-syzygy, which checks its prober against python-chess
+// on the same files
+#[test]
+fn matches_the_reference_values() {
+    for (epd, expected_wdl, expected_dtz) in [
+        ("8/4r3/8/K7/4R3/8/8/5k2 w - -", 2, 1),
+        ("8/8/R2k4/8/8/K7/8/4r3 b - -", 0, 0),
+        ("R7/8/6k1/8/8/8/2K4r/8 w - -", 0, 0),
+        ("8/8/K7/5k2/3R4/8/7b/8 w - -", 0, 0),
+        ("8/3r4/7K/5R2/8/8/8/5k2 b - -", 0, 0),
+        ("8/5p2/6k1/K7/8/8/8/8 w - -", -2, -2),
+        ("7k/1R6/8/8/8/8/4r3/K7 w - -", 0, 0),
+        ("2b5/3K4/4R3/3k4/8/8/8/8 w - -", 0, 0),
+        ("8/8/8/2K5/5kp1/8/8/8 b - -", 2, 1),
+        ("3K1b2/2R5/8/8/8/1k6/8/8 w - -", 0, 0),
+        ("5k2/8/4K3/8/3r1B2/8/8/8 b - -", 2, 1),
+        ("8/7r/8/1k4K1/8/8/8/3B4 w - -", 0, 0),
+        ("8/8/8/2k5/8/2b5/8/K2R4 w - -", 0, 0),
+        ("8/7k/K7/6r1/8/5R2/8/8 w - -", 0, 0),
+        ("6R1/8/5b2/5K2/8/8/8/1k6 b - -", 0, 0),
+        ("8/8/8/2R5/1K6/8/5k2/8 w - -", 2, 21),
+        ("2B5/8/1K6/r7/5k2/8/8/8 w - -", 0, 0),
+        ("8/3k4/8/8/8/8/4P3/3K4 w - -", 0, 0),
+        ("6k1/8/8/8/8/4n3/8/K7 b - -", 0, 0),
+    ] {
+        let fen = format!("{} 0 1", epd);
+        assert_eq!(
+            (wdl(&fen).map(|wdl| wdl as i32), dtz(&fen)),
+            (Some(expected_wdl), Some(expected_dtz)),
+            "{}",
+            epd
+        );
+    }
+}
+
+#[test]
+fn fails_without_tables() {
+    let position = Position::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+    let empty = Tablebase::empty();
+
+    assert!(!empty.can_probe(&position, 7));
+    assert_eq!(empty.probe_wdl(&position), None);
+    assert_eq!(empty.probe_dtz(&position), None);
+}
@@ -0,0 +1,315 @@
+#![allow(clippy::needless_return)]
+
+// runs the engine binary and talks uci to it over its stdin and stdout
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// a hanging engine fails the test instead of blocking it
+const REPLY_TIMEOUT: Duration = Duration::from_secs(20);
+
+struct Engine {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    lines: Receiver<String>,
+}
+
+impl Engine {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_endgame2"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+
+        return Self {
+            stdin: child.stdin.take(),
+            child,
+            lines,
+        };
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin.as_mut().unwrap(), "{}", command).unwrap();
+    }
+
+    // the lines up to and including the first one starting with prefix
+    fn wait_for(&self, prefix: &str) -> Vec<String> {
+        let mut lines = vec![];
+
+        loop {
+            let line = self
+                .lines
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| panic!("no {} after {:?}", prefix, lines));
+            let found = line.starts_with(prefix);
+            lines.push(line);
+
+            if found {
+                return lines;
+            }
+        }
+    }
+
+    // everything printed in the next while
+    fn lines_for(&self, duration: Duration) -> Vec<String> {
+        let end = Instant::now() + duration;
+        let mut lines = vec![];
+
+        while let Ok(line) = self
+            .lines
+            .recv_timeout(end.saturating_duration_since(Instant::now()))
+        {
+            lines.push(line);
+        }
+
+        return lines;
+    }
+
+    // closes stdin and returns everything printed until the engine exits
+    fn finish(mut self) -> Vec<String> {
+        drop(self.stdin.take());
+
+        let mut lines = vec![];
+        while let Ok(line) = self.lines.recv_timeout(REPLY_TIMEOUT) {
+            lines.push(line);
+        }
+        assert!(self.child.wait().unwrap().success());
+
+        return lines;
+    }
+}
+
+// the replies to a script sent at once, without the search output
+fn run(script: &[&str]) -> Vec<String> {
+    let mut engine = Engine::start();
+    for command in script {
+        engine.send(command);
+    }
+
+    return engine
+        .finish()
+        .into_iter()
+        .filter(|line| !line.starts_with("info depth") && !line.starts_with("info currmove"))
+        .filter(|line| !line.starts_with("id ") && !line.starts_with("option "))
+        .collect();
+}
+
+#[test]
+fn answers_everything_before_quit() {
+    assert_eq!(run(&["uci", "isready", "quit"]), ["uciok", "readyok"]);
+
+    // searches queued behind quit end at once, but still give their move
+    let replies = run(&["go infinite", "go depth 30", "isready", "quit"]);
+    let bestmoves = replies.iter().filter(|line| line.starts_with("bestmove"));
+    assert_eq!(bestmoves.count(), 2);
+    assert!(replies.contains(&"readyok".to_string()));
+}
+
+#[test]
+fn survives_garbled_moves() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4 xx");
+    engine.send("fen");
+    engine.send("position startpos moves P@z9");
+    engine.send("position startpos moves e2");
+    engine.send("isready");
+
+    assert_eq!(
+        engine.wait_for("readyok"),
+        [
+            "info string Error - illegal or unparsable move xx!",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "info string Error - illegal or unparsable move P@z9!",
+            "info string Error - illegal or unparsable move e2!",
+            "readyok",
+        ]
+    );
+
+    // the worker is still there to search
+    engine.send("go searchmoves e2 e9e10 g1f3 depth 2");
+    let replies = engine.wait_for("bestmove");
+    assert!(replies.contains(&"info string Error - searchmove e2 is not legal!".to_string()));
+    assert!(replies.contains(&"info string Error - searchmove e9e10 is not legal!".to_string()));
+    assert!(replies.last().unwrap().starts_with("bestmove g1f3"));
+
+    engine.send("isready");
+    assert_eq!(engine.wait_for("readyok"), ["readyok"]);
+    engine.finish();
+}
+
+fn has_bestmove(lines: &[String]) -> bool {
+    return lines.iter().any(|line| line.starts_with("bestmove"));
+}
+
+#[test]
+fn stays_responsive_while_searching() {
+    let mut engine = Engine::start();
+    engine.send("go infinite");
+    engine.wait_for("info depth");
+
+    // answered by the input thread, without waiting for the search
+    engine.send("isready");
+    assert!(!has_bestmove(&engine.wait_for("readyok")));
+
+    // an infinite search only ends when told to
+    assert!(!has_bestmove(&engine.lines_for(Duration::from_millis(300))));
+
+    // a new position waits for the search, which still finishes on the old one
+    engine.send("position startpos moves e2e4");
+    engine.send("fen");
+    engine.send("stop");
+    let replies = engine.wait_for("bestmove");
+    assert!(!replies.iter().any(|line| line.starts_with("rnbqkbnr")));
+    assert_eq!(
+        engine.wait_for("rnbqkbnr"),
+        ["rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"]
+    );
+
+    engine.send("isready");
+    assert_eq!(engine.wait_for("readyok"), ["readyok"]);
+    engine.finish();
+}
+
+#[test]
+fn clears_stop_and_ponderhit_between_searches() {
+    let mut engine = Engine::start();
+
+    // a ponder search holds its move, even past its time, until the ponderhit
+    engine.send("go ponder movetime 50");
+    assert!(!has_bestmove(&engine.lines_for(Duration::from_millis(300))));
+    engine.send("ponderhit");
+    engine.wait_for("bestmove");
+
+    // the ponderhit is not left over for the next ponder search
+    engine.send("go ponder movetime 50");
+    assert!(!has_bestmove(&engine.lines_for(Duration::from_millis(300))));
+    engine.send("stop");
+    engine.wait_for("bestmove");
+
+    // and the stop doesn't cut the next search short
+    engine.send("go depth 4");
+    let replies = engine.wait_for("bestmove");
+    assert!(
+        replies.iter().any(|line| line.starts_with("info depth 4 ")),
+        "{:?}",
+        replies
+    );
+
+    engine.finish();
+}
+
+#[test]
+fn reports_once_with_helper_threads() {
+    let mut engine = Engine::start();
+    engine.send("setoption name Threads value 3");
+
+    engine.send("go depth 4");
+    engine.wait_for("bestmove");
+    engine.send("isready");
+    assert_eq!(engine.wait_for("readyok"), ["readyok"]);
+
+    // the helpers stop with the main search and print nothing of their own
+    engine.send("go infinite");
+    assert!(!has_bestmove(&engine.lines_for(Duration::from_millis(300))));
+    engine.send("stop");
+    engine.wait_for("bestmove");
+    engine.send("isready");
+    assert_eq!(engine.wait_for("readyok"), ["readyok"]);
+
+    engine.finish();
+}
+
+#[test]
+fn castles_as_king_takes_rook_with_chess960() {
+    let mut engine = Engine::start();
+    engine.send("setoption name UCI_Chess960 value true");
+    engine.send("position fen 4k3/8/8/8/8/8/8/RK5R w HA - 0 1 moves b1a1");
+    engine.send("fen");
+    assert_eq!(engine.wait_for("4k3"), ["4k3/8/8/8/8/8/8/2KR3R b - - 1 1"]);
+
+    engine.send("position fen 4k3/8/8/8/8/8/8/RK5R w HA - 0 1");
+    engine.send("moves");
+    let moves = engine.wait_for("");
+    assert!(moves[0].split(' ').any(|uci| uci == "b1h1"), "{:?}", moves);
+
+    engine.send("go searchmoves b1h1 depth 2");
+    assert!(engine
+        .wait_for("bestmove")
+        .last()
+        .unwrap()
+        .starts_with("bestmove b1h1"));
+
+    engine.finish();
+}
+
+#[test]
+fn keeps_to_movetime_in_a_mate_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+
+    // there is no mate to find, so the engine moves once the time is up
+    let start = Instant::now();
+    engine.send("go mate 6 movetime 500");
+    let replies = engine.wait_for("bestmove");
+    assert!(
+        start.elapsed() < Duration::from_millis(1500),
+        "{:?}",
+        replies
+    );
+    assert!(!replies.last().unwrap().starts_with("bestmove 0000"));
+
+    engine.finish();
+}
+
+#[test]
+fn proves_a_long_mate_without_limits() {
+    let mut engine = Engine::start();
+    engine.send("position fen k7/8/8/8/8/8/8/KR6 w - - 0 1");
+
+    // more nodes than a bare go gets, so the mate search has to run until it is done
+    engine.send("go mate 7");
+    let replies = engine.wait_for("bestmove");
+    assert!(
+        replies.iter().any(|line| line.contains(" score mate 7 ")),
+        "{:?}",
+        replies
+    );
+
+    engine.finish();
+}
+
+#[test]
+fn keeps_to_the_legal_searchmoves() {
+    let mut engine = Engine::start();
+
+    // the illegal ones are reported and left out
+    engine.send("go searchmoves e2e5 d2d4 depth 2");
+    let replies = engine.wait_for("bestmove");
+    assert!(
+        replies.iter().any(|line| line.contains("e2e5")),
+        "{:?}",
+        replies
+    );
+    assert_eq!(replies.last().unwrap().split(' ').nth(1), Some("d2d4"));
+
+    // and without any legal one there is no move to give
+    engine.send("go searchmoves e2e5 a1a8 depth 2");
+    let replies = engine.wait_for("bestmove");
+    assert_eq!(replies.last().unwrap(), "bestmove 0000");
+
+    engine.finish();
+}